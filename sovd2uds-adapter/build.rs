@@ -1,7 +1,16 @@
 use std::env;
 use std::path::PathBuf;
 
+/// First line of the generated placeholder header
+const PLACEHOLDER_MARKER: &str = "// Placeholder header file for UDS and DoIP client libraries";
+
 fn main() {
+    // The mock-uds feature serves requests from the built-in ECU simulator,
+    // so neither the C libraries nor their bindings are needed
+    if env::var_os("CARGO_FEATURE_MOCK_UDS").is_some() {
+        return;
+    }
+
     // Get the path to C library headers
     // These paths should be adjusted based on where libudsclient and libdoipclient are located
     let uds_include_path = env::var("UDS_INCLUDE_PATH")
@@ -16,10 +25,17 @@ fn main() {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
 
-    // Check if header file exists, if not create a placeholder
-    let wrapper_path = PathBuf::from("wrapper.h");
-    if !wrapper_path.exists() {
+    // Use wrapper.h when the real library headers are provided; otherwise (or
+    // when it is a placeholder left by an earlier build) generate the
+    // placeholder into OUT_DIR so it always matches this version
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let mut wrapper_path = PathBuf::from("wrapper.h");
+    let is_placeholder = std::fs::read_to_string(&wrapper_path)
+        .map(|header| header.contains(PLACEHOLDER_MARKER))
+        .unwrap_or(true);
+    if is_placeholder {
         println!("cargo:warning=wrapper.h not found, FFI bindings will be generated with placeholders");
+        wrapper_path = out_path.join("wrapper.h");
         std::fs::write(&wrapper_path, r#"
 // Placeholder header file for UDS and DoIP client libraries
 // Replace this with actual header files from libudsclient and libdoipclient
@@ -27,6 +43,7 @@ fn main() {
 #ifndef SOVD2UDS_WRAPPER_H
 #define SOVD2UDS_WRAPPER_H
 
+#include <stddef.h>
 #include <stdint.h>
 #include <stdbool.h>
 
@@ -55,6 +72,9 @@ uds_response_t* uds_security_access(uds_client_t* client, uint8_t access_type, c
 uds_response_t* uds_read_dtc_information(uds_client_t* client, uint8_t sub_function);
 uds_response_t* uds_clear_diagnostic_information(uds_client_t* client, uint32_t group);
 uds_response_t* uds_routine_control(uds_client_t* client, uint8_t routine_type, uint16_t routine_id, const uint8_t* params, uint32_t params_length);
+// Send one request PDU; the response PDU is copied to the caller's buffer,
+// whose capacity is passed in *response_len and replaced by the length
+int uds_send_request(uint8_t service_id, uint8_t* data, size_t data_len, uint8_t* response, size_t* response_len);
 void uds_response_free(uds_response_t* response);
 
 doip_client_t* doip_client_create(const char* ip_address, uint16_t port);
@@ -71,7 +91,7 @@ int32_t doip_client_disconnect(doip_client_t* client);
     // the resulting bindings.
     let bindings = bindgen::Builder::default()
         // The input header we would like to generate bindings for.
-        .header("wrapper.h")
+        .header(wrapper_path.to_string_lossy())
         // Add include paths for C libraries
         .clang_arg(format!("-I{}", uds_include_path))
         .clang_arg(format!("-I{}", doip_include_path))
@@ -84,7 +104,6 @@ int32_t doip_client_disconnect(doip_client_t* client);
         .expect("Unable to generate bindings");
 
     // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
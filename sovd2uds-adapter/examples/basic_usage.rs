@@ -3,8 +3,8 @@
 use sovd2uds_adapter::{
     config::Config,
     translation::SovdUdsTranslator,
-    uds::{UdsClient, UdsClientPool},
-    models::uds::DiagnosticSessionType,
+    uds::UdsClientPool,
+    models::uds::{DiagnosticSessionType, DtcReportType},
 };
 use std::sync::Arc;
 
@@ -14,7 +14,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Arc::new(Config::load()?);
 
     // Create translator
-    let translator = SovdUdsTranslator::new();
+    let translator = SovdUdsTranslator::from_config(&config)?;
 
     // Create UDS client pool
     let pool = UdsClientPool::new(Arc::clone(&config));
@@ -39,7 +39,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Example 4: Read DTCs
     println!("\n=== Example 4: Read DTCs ===");
-    let dtc_data = engine_client.read_dtc_information(0x02).await?;
+    let dtc_data = engine_client
+        .read_dtc_information(DtcReportType::DtcByStatusMask, &[0xFF])
+        .await?;
     println!("DTC Data: {} bytes", dtc_data.len());
 
     // Cleanup
@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use crate::models::uds::security_levels;
+use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
 
 /// Configuration for the SOVD2UDS adapter
@@ -7,12 +8,88 @@ pub struct Config {
     pub server: ServerConfig,
     pub uds: UdsConfig,
     pub doip: DoipConfig,
+    #[serde(default)]
+    pub isotp: IsoTpConfig,
+    #[serde(default)]
+    pub simulator: SimulatorConfig,
+    #[serde(default)]
+    pub dictionary: DictionaryConfig,
     pub logging: LoggingConfig,
-    pub components: HashMap<String, u32>,
+    /// Component registry (component_id = component)
+    #[serde(deserialize_with = "deserialize_components")]
+    pub components: HashMap<String, ComponentConfig>,
     pub security: SecurityConfig,
     pub performance: PerformanceConfig,
 }
 
+/// A component (ECU) of the registry
+///
+/// Written as `engine = 0x7E0` or as a `[components.engine]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ComponentConfig {
+    /// Display name; the component ID when unset
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// ECU address: CAN ID of requests, or DoIP logical target address;
+    /// `uds.default_address` when unset
+    pub address: Option<u32>,
+    /// Transport override, like an entry of `[uds.transports]`
+    pub transport: Option<TransportKind>,
+    /// Dictionaries of the component, loaded after those of
+    /// `[dictionary.components]`
+    pub dictionaries: Vec<String>,
+    /// Shared base of `[dictionary.bases]` the component inherits
+    pub inherit: Option<String>,
+    /// ODX variant (SHORT-NAME) of the component
+    pub odx_variant: Option<String>,
+}
+
+impl ComponentConfig {
+    /// Component at an address, without further settings
+    pub fn new(address: u32) -> Self {
+        Self {
+            address: Some(address),
+            ..Self::default()
+        }
+    }
+}
+
+/// `[components]` entry: an address or a full component
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ComponentEntry {
+    Address(u32),
+    Component(ComponentConfig),
+}
+
+/// Component of the registry a per-component entry of `section` names
+fn registered<'a>(
+    components: &'a mut HashMap<String, ComponentConfig>,
+    section: &str,
+    component_id: &str,
+) -> Result<&'a mut ComponentConfig, String> {
+    components
+        .get_mut(component_id)
+        .ok_or_else(|| format!("[{}] names unknown component '{}'", section, component_id))
+}
+
+fn deserialize_components<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, ComponentConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = HashMap::<String, ComponentEntry>::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .map(|(id, entry)| match entry {
+            ComponentEntry::Address(address) => (id, ComponentConfig::new(address)),
+            ComponentEntry::Component(component) => (id, component),
+        })
+        .collect())
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
@@ -25,7 +102,99 @@ pub struct UdsConfig {
     pub interface: String,
     pub default_address: u32,
     pub timeout: u32,
+    /// Retries of idempotent reads after busy (NRC 0x21), timeout or link errors
     pub max_retries: u32,
+    /// Idle time after which TesterPresent keeps a non-default session open,
+    /// in milliseconds (0 disables the keep-alive)
+    #[serde(default = "default_tester_present_interval")]
+    pub tester_present_interval: u32,
+    /// Delay before the first retry in milliseconds, doubled on each further retry
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u32,
+    /// Upper bound for the retry delay in milliseconds
+    #[serde(default = "default_retry_max_delay")]
+    pub retry_max_delay: u32,
+    /// Default transport; derived from `doip.enabled` / `isotp.enabled` when unset
+    #[serde(default)]
+    pub transport: Option<TransportKind>,
+    /// Per-component transport overrides (component_id = transport)
+    #[serde(default)]
+    pub transports: HashMap<String, TransportKind>,
+    /// P2*: time to wait after a response pending (NRC 0x78), in milliseconds.
+    /// `timeout` is used as P2.
+    #[serde(default = "default_p2_star_timeout")]
+    pub p2_star_timeout: u32,
+    /// Added to the P2/P2* values an ECU reports in its session control response
+    #[serde(default = "default_p2_margin")]
+    pub p2_margin: u32,
+    /// Per-component P2/P2* overrides
+    #[serde(default)]
+    pub timing: HashMap<String, ComponentTimingConfig>,
+    /// DIDs combined into one ReadDataByIdentifier request by bulk reads
+    #[serde(default = "default_max_dids_per_request")]
+    pub max_dids_per_request: usize,
+    /// Largest positive response bulk reads ask for, in bytes (SID included)
+    #[serde(default = "default_max_response_length")]
+    pub max_response_length: usize,
+}
+
+fn default_tester_present_interval() -> u32 {
+    2000
+}
+
+fn default_retry_delay() -> u32 {
+    100
+}
+
+fn default_retry_max_delay() -> u32 {
+    2000
+}
+
+fn default_p2_star_timeout() -> u32 {
+    5000
+}
+
+fn default_p2_margin() -> u32 {
+    50
+}
+
+fn default_max_dids_per_request() -> usize {
+    8
+}
+
+fn default_max_response_length() -> usize {
+    // Largest ISO-TP message without escape sequence length
+    4095
+}
+
+/// P2/P2* override for one component, in milliseconds
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ComponentTimingConfig {
+    pub p2: Option<u32>,
+    pub p2_star: Option<u32>,
+}
+
+/// Response timing used for a component, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdsTiming {
+    /// Time to wait for the first response to a request
+    pub p2: u32,
+    /// Time to wait after a response pending (NRC 0x78)
+    pub p2_star: u32,
+}
+
+/// Transport used to reach a component's ECU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// libudsclient through FFI
+    Ffi,
+    /// Native DoIP client
+    Doip,
+    /// Native ISO-TP over SocketCAN
+    IsoTp,
+    /// In-process virtual ECU
+    Simulator,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -35,6 +204,320 @@ pub struct DoipConfig {
     pub port: u16,
     pub source_address: u32,
     pub target_logical_address: u32,
+    /// DoIP protocol version sent in the generic header
+    #[serde(default = "default_doip_protocol_version")]
+    pub protocol_version: u8,
+    /// Routing activation type
+    #[serde(default)]
+    pub activation_type: u8,
+    /// TCP connect and routing activation timeout in milliseconds
+    #[serde(default = "default_doip_connect_timeout")]
+    pub connect_timeout: u32,
+    /// Diagnostic message acknowledge timeout in milliseconds
+    #[serde(default = "default_doip_ack_timeout")]
+    pub ack_timeout: u32,
+}
+
+fn default_doip_protocol_version() -> u8 {
+    0x02
+}
+
+fn default_doip_connect_timeout() -> u32 {
+    2000
+}
+
+fn default_doip_ack_timeout() -> u32 {
+    2000
+}
+
+/// ISO-TP (ISO 15765-2) over SocketCAN settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct IsoTpConfig {
+    /// Use the native ISO-TP transport on `uds.interface` instead of libudsclient
+    pub enabled: bool,
+    /// Response CAN ID = request CAN ID (component address) + offset
+    pub response_id_offset: u32,
+    pub addressing: IsoTpAddressing,
+    /// Extended addressing: first data byte of transmitted frames
+    pub target_address_extension: u8,
+    /// Extended addressing: first data byte expected in received frames
+    pub source_address_extension: u8,
+    /// Block size advertised in our flow control frames (0 = no limit)
+    pub block_size: u8,
+    /// STmin advertised in our flow control frames
+    pub st_min: u8,
+    /// Pad frames to 8 bytes with this value (no padding if unset)
+    pub padding: Option<u8>,
+    /// N_Bs: flow control timeout in milliseconds
+    pub timeout_bs: u32,
+    /// N_Cr: consecutive frame timeout in milliseconds
+    pub timeout_cr: u32,
+    /// Maximum number of consecutive FC.WAIT frames accepted
+    pub max_wait_frames: u32,
+    /// Longest message accepted from the ECU in bytes; longer ones are
+    /// answered with FC.OVFLW
+    pub max_message_length: usize,
+}
+
+/// ISO-TP addressing format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IsoTpAddressing {
+    Normal,
+    Extended,
+}
+
+impl Default for IsoTpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            response_id_offset: 0x08,
+            addressing: IsoTpAddressing::Normal,
+            target_address_extension: 0x00,
+            source_address_extension: 0x00,
+            block_size: 0,
+            st_min: 0,
+            padding: Some(0xCC),
+            timeout_bs: 1000,
+            timeout_cr: 1000,
+            max_wait_frames: 10,
+            max_message_length: 4095,
+        }
+    }
+}
+
+/// Virtual ECUs served by the simulator transport
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SimulatorConfig {
+    /// ECU used for components without their own definition
+    #[serde(flatten)]
+    pub ecu: SimulatedEcuConfig,
+    /// Per-component ECU definitions (component_id = ECU)
+    pub components: HashMap<String, SimulatedEcuConfig>,
+}
+
+impl SimulatorConfig {
+    /// Get the virtual ECU definition for a component
+    pub fn get_component_ecu(&self, component_id: &str) -> &SimulatedEcuConfig {
+        self.components.get(component_id).unwrap_or(&self.ecu)
+    }
+}
+
+/// Content and behaviour of one virtual ECU
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulatedEcuConfig {
+    pub dids: Vec<SimulatedDid>,
+    pub dtcs: Vec<SimulatedDtc>,
+    pub routines: Vec<SimulatedRoutine>,
+    /// DTC status bits supported by the ECU
+    pub dtc_status_availability_mask: u8,
+    /// RequestSeed sub-functions the ECU accepts (odd; level n uses 2n - 1)
+    pub security_levels: Vec<u8>,
+    /// Algorithm the ECU checks keys with
+    pub seed_key: SeedKeyAlgorithmConfig,
+    /// Invalid keys accepted before the ECU locks security access
+    pub max_key_attempts: u8,
+    /// Lockout delay after too many invalid keys, in milliseconds
+    pub security_delay: u32,
+    /// S3 server timeout: non-default session falls back after this many milliseconds
+    pub s3_timeout: u32,
+    /// P2 server time reported in session control responses, in milliseconds
+    pub p2: u16,
+    /// P2* server time reported in session control responses, in milliseconds
+    pub p2_star: u32,
+    /// Artificial delay before every response, in milliseconds
+    pub response_delay: u32,
+    /// Size of the memory served by Read/WriteMemoryByAddress, starting at address 0
+    pub memory_size: u32,
+}
+
+/// A data identifier served by a virtual ECU
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedDid {
+    pub id: u16,
+    /// Value as text (stored as ASCII)
+    #[serde(default)]
+    pub ascii: Option<String>,
+    /// Value as hex bytes, e.g. "0C 1F"
+    #[serde(default)]
+    pub hex: Option<String>,
+    /// Accept WriteDataByIdentifier (extended session required)
+    #[serde(default)]
+    pub writable: bool,
+    /// Security level that must be unlocked to write
+    #[serde(default)]
+    pub security_level: Option<u8>,
+}
+
+/// A stored DTC of a virtual ECU
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedDtc {
+    /// 24-bit DTC number
+    pub code: u32,
+    pub status: u8,
+    /// Snapshot records (freeze frames), holding the current values of DIDs
+    #[serde(default)]
+    pub snapshots: Vec<SimulatedSnapshot>,
+    /// Extended data records
+    #[serde(default)]
+    pub extended_data: Vec<SimulatedExtendedData>,
+    /// Fault detection counter (-128 passed .. 127 failed)
+    #[serde(default)]
+    pub fault_detection_counter: i8,
+    /// WWH-OBD DTC severity and class
+    #[serde(default)]
+    pub severity: u8,
+    /// Reported as permanent DTC (WWH-OBD)
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+impl SimulatedDtc {
+    fn new(code: u32, status: u8) -> Self {
+        Self {
+            code,
+            status,
+            snapshots: Vec::new(),
+            extended_data: Vec::new(),
+            fault_detection_counter: 0,
+            severity: 0,
+            permanent: false,
+        }
+    }
+}
+
+/// A snapshot record of a simulated DTC
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedSnapshot {
+    pub record: u8,
+    /// DIDs whose values the record holds
+    pub dids: Vec<u16>,
+}
+
+/// An extended data record of a simulated DTC
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedExtendedData {
+    pub record: u8,
+    /// Record data as hex bytes
+    pub hex: String,
+}
+
+/// A routine of a virtual ECU
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedRoutine {
+    pub id: u16,
+    /// Routine status record returned by start/results, as hex bytes
+    #[serde(default)]
+    pub result: Option<String>,
+    /// Security level that must be unlocked to start the routine
+    #[serde(default)]
+    pub security_level: Option<u8>,
+    /// Time the routine takes to start, in milliseconds; the ECU answers
+    /// with response pending (NRC 0x78) meanwhile
+    #[serde(default)]
+    pub duration: u32,
+}
+
+impl SimulatedDid {
+    fn ascii(id: u16, value: &str) -> Self {
+        Self {
+            id,
+            ascii: Some(value.to_string()),
+            hex: None,
+            writable: false,
+            security_level: None,
+        }
+    }
+}
+
+impl SimulatedRoutine {
+    fn new(id: u16) -> Self {
+        Self {
+            id,
+            result: Some("00".to_string()),
+            security_level: None,
+            duration: 0,
+        }
+    }
+}
+
+impl Default for SimulatedEcuConfig {
+    fn default() -> Self {
+        Self {
+            dids: vec![
+                SimulatedDid {
+                    writable: true,
+                    security_level: Some(0x01),
+                    ..SimulatedDid::ascii(0xF190, "WVWZZZ1JZXW000001")
+                },
+                SimulatedDid::ascii(0xF18C, "SIM0000001"),
+                SimulatedDid::ascii(0xF191, "HW-1.0"),
+                SimulatedDid::ascii(0xF194, "SW-1.0.0"),
+                SimulatedDid::ascii(0xF18B, "2024-01-01"),
+                SimulatedDid::ascii(0xF18A, "SOVD"),
+            ],
+            dtcs: vec![
+                SimulatedDtc {
+                    snapshots: vec![SimulatedSnapshot {
+                        record: 0x01,
+                        dids: vec![0xF18C],
+                    }],
+                    extended_data: vec![SimulatedExtendedData {
+                        record: 0x01,
+                        hex: "05".to_string(),
+                    }],
+                    fault_detection_counter: 127,
+                    severity: 0x44,
+                    permanent: true,
+                    ..SimulatedDtc::new(0x010011, 0x09)
+                },
+                SimulatedDtc {
+                    fault_detection_counter: 32,
+                    severity: 0x22,
+                    ..SimulatedDtc::new(0x030000, 0x24)
+                },
+            ],
+            routines: vec![
+                SimulatedRoutine::new(0x0201),
+                SimulatedRoutine::new(0x0202),
+                SimulatedRoutine::new(0x0203),
+            ],
+            dtc_status_availability_mask: 0xFF,
+            security_levels: vec![0x01],
+            seed_key: SeedKeyAlgorithmConfig::default(),
+            max_key_attempts: 3,
+            security_delay: 10000,
+            s3_timeout: 5000,
+            p2: 50,
+            p2_star: 5000,
+            response_delay: 0,
+            memory_size: 0x1000,
+        }
+    }
+}
+
+/// Data dictionaries with data item definitions (YAML, TOML or JSON)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DictionaryConfig {
+    /// Dictionaries for all components, loaded in order; later items replace
+    /// earlier ones with the same ID
+    pub files: Vec<String>,
+    /// Per-component dictionaries (component_id = files), taking precedence
+    pub components: HashMap<String, Vec<String>>,
+    /// Shared dictionaries by name (name = files), inherited through `inherit`
+    pub bases: HashMap<String, Vec<String>>,
+    /// Shared dictionary of `bases` a component inherits (component_id =
+    /// name); it takes precedence over `files` and yields to the component's
+    pub inherit: HashMap<String, String>,
+    /// ODX files and PDX archives, imported before the dictionaries
+    pub odx: Vec<String>,
+    /// ODX variant (SHORT-NAME) per component; components without an entry
+    /// use the variant named like the component, if any
+    pub odx_variants: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -49,12 +532,101 @@ pub struct LoggingConfig {
 pub struct SecurityConfig {
     pub require_security_access: bool,
     pub security_level: u8,
+    /// Seed/key algorithms by component and level; the key is the seed
+    /// XOR 0xAA when no entry matches
+    #[serde(default)]
+    pub algorithms: Vec<SeedKeyRegistration>,
+    /// Invalid keys the ECUs accept before locking security access out
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u8,
+    /// Lockout after too many invalid keys, in milliseconds
+    #[serde(default = "default_lockout_delay")]
+    pub lockout_delay: u32,
+    /// Bearer token clients must present to unlock or release security
+    /// access; the lock endpoints are open when unset
+    #[serde(default)]
+    pub api_token: Option<String>,
+}
+
+fn default_max_attempts() -> u8 {
+    3
+}
+
+fn default_lockout_delay() -> u32 {
+    10000
+}
+
+/// Seed/key algorithm used for a component and/or security level
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeedKeyRegistration {
+    /// Component the algorithm applies to; all components when unset
+    #[serde(default)]
+    pub component: Option<String>,
+    /// Security level the algorithm applies to; all levels when unset
+    #[serde(default)]
+    pub level: Option<u8>,
+    #[serde(flatten)]
+    pub algorithm: SeedKeyAlgorithmConfig,
+}
+
+/// Seed/key algorithm and its parameters, selected by `type`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SeedKeyAlgorithmConfig {
+    /// Key = seed XOR `mask` (hex, repeated over the seed)
+    Xor { mask: String },
+    /// Key = CRC-32 of the seed followed by `secret` (hex)
+    Crc32 {
+        #[serde(default)]
+        secret: String,
+    },
+    /// Key = AES-CMAC of the seed under `key` (16 or 32 bytes hex),
+    /// truncated to `key_length` bytes
+    AesCmac {
+        key: String,
+        #[serde(default)]
+        key_length: Option<usize>,
+    },
+    /// Shared library exporting `GenerateKeyEx`
+    Library {
+        path: String,
+        #[serde(default)]
+        variant: String,
+    },
+    /// Program run as `command... <level> <seed hex>`, printing the key as hex
+    Command {
+        command: Vec<String>,
+        /// Milliseconds the program may run
+        #[serde(default = "default_seed_key_command_timeout")]
+        timeout: u32,
+    },
+}
+
+fn default_seed_key_command_timeout() -> u32 {
+    2000
+}
+
+impl Default for SeedKeyAlgorithmConfig {
+    fn default() -> Self {
+        Self::Xor {
+            mask: "AA".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct PerformanceConfig {
+    /// API requests handled at once; further requests get 429 (0 = unlimited)
     pub max_concurrent_requests: usize,
+    /// ECU connections kept open; idle ones are closed to make room
     pub connection_pool_size: usize,
+    /// Requests queued per ECU, the one being served included
+    #[serde(default = "default_ecu_queue_length")]
+    pub ecu_queue_length: usize,
+}
+
+fn default_ecu_queue_length() -> usize {
+    16
 }
 
 impl Config {
@@ -70,26 +642,133 @@ impl Config {
                     .try_parsing(true),
             );
 
-        let config = builder.build()?;
-        config.try_deserialize()
+        let mut config: Self = builder.build()?.try_deserialize()?;
+        config
+            .resolve_components()
+            .map_err(config::ConfigError::Message)?;
+        config
+            .validate_security_levels()
+            .map_err(config::ConfigError::Message)?;
+        Ok(config)
     }
 
-    /// Get ECU address for a component
+    /// Reject security levels whose SecurityAccess sub-functions do not exist
+    fn validate_security_levels(&self) -> Result<(), String> {
+        let check = |key: &str, level: u8| {
+            if security_levels::is_valid(level) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} {} is outside 1-{}",
+                    key,
+                    level,
+                    security_levels::MAX
+                ))
+            }
+        };
+
+        check("security.security_level", self.security.security_level)?;
+        for registration in &self.security.algorithms {
+            if let Some(level) = registration.level {
+                check("security.algorithms.level", level)?;
+            }
+        }
+        let ecus = std::iter::once(&self.simulator.ecu).chain(self.simulator.components.values());
+        for ecu in ecus {
+            let dids = ecu.dids.iter().map(|did| did.security_level);
+            let routines = ecu.routines.iter().map(|routine| routine.security_level);
+            for level in dids.chain(routines).flatten() {
+                check("simulator security_level", level)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the per-component entries of other sections into the component
+    /// registry; entries of unknown components are rejected
+    fn resolve_components(&mut self) -> Result<(), String> {
+        let components = &mut self.components;
+        for (component_id, transport) in &self.uds.transports {
+            let component = registered(components, "uds.transports", component_id)?;
+            component.transport.get_or_insert(*transport);
+        }
+        for (component_id, paths) in &self.dictionary.components {
+            let component = registered(components, "dictionary.components", component_id)?;
+            component.dictionaries.splice(0..0, paths.iter().cloned());
+        }
+        for (component_id, base) in &self.dictionary.inherit {
+            let component = registered(components, "dictionary.inherit", component_id)?;
+            component.inherit.get_or_insert_with(|| base.clone());
+        }
+        for (component_id, variant) in &self.dictionary.odx_variants {
+            let component = registered(components, "dictionary.odx_variants", component_id)?;
+            component.odx_variant.get_or_insert_with(|| variant.clone());
+        }
+        for component_id in self.uds.timing.keys() {
+            registered(components, "uds.timing", component_id)?;
+        }
+        Ok(())
+    }
+
+    /// Get a component of the registry
+    pub fn get_component(&self, component_id: &str) -> Option<&ComponentConfig> {
+        self.components.get(component_id)
+    }
+
+    /// Get the transport used to reach a component
+    ///
+    /// Builds with the `mock-uds` feature default to the simulator.
+    pub fn get_component_transport(&self, component_id: &str) -> TransportKind {
+        self.get_component(component_id)
+            .and_then(|component| component.transport)
+            .or(self.uds.transport)
+            .unwrap_or(if cfg!(feature = "mock-uds") {
+                TransportKind::Simulator
+            } else if self.doip.enabled {
+                TransportKind::Doip
+            } else if self.isotp.enabled {
+                TransportKind::IsoTp
+            } else {
+                TransportKind::Ffi
+            })
+    }
+
+    /// Get the initial P2/P2* timing for a component
+    pub fn get_component_timing(&self, component_id: &str) -> UdsTiming {
+        let timing = self.uds.timing.get(component_id);
+        UdsTiming {
+            p2: timing.and_then(|t| t.p2).unwrap_or(self.uds.timeout),
+            p2_star: timing
+                .and_then(|t| t.p2_star)
+                .unwrap_or(self.uds.p2_star_timeout),
+        }
+    }
+
+    /// Get ECU address for a component; none for unknown components
     pub fn get_component_address(&self, component_id: &str) -> Option<u32> {
-        self.components
-            .get(component_id)
-            .copied()
-            .or(Some(self.uds.default_address))
+        self.get_component(component_id)
+            .map(|component| component.address.unwrap_or(self.uds.default_address))
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let mut components = HashMap::new();
-        components.insert("engine".to_string(), 0x7E0);
-        components.insert("transmission".to_string(), 0x7E1);
-        components.insert("abs".to_string(), 0x7E2);
-        components.insert("airbag".to_string(), 0x7E3);
+        let components = [
+            ("engine", "Engine Control Unit", "Main engine control unit", 0x7E0),
+            ("transmission", "Transmission Control Unit", "Automatic transmission control", 0x7E1),
+            ("abs", "ABS Control Unit", "Anti-lock braking system", 0x7E2),
+            ("airbag", "Airbag Control Unit", "Airbag and restraint system", 0x7E3),
+        ]
+        .into_iter()
+        .map(|(id, name, description, address)| {
+            let component = ComponentConfig {
+                name: Some(name.to_string()),
+                description: Some(description.to_string()),
+                ..ComponentConfig::new(address)
+            };
+            (id.to_string(), component)
+        })
+        .collect();
 
         Self {
             server: ServerConfig {
@@ -102,6 +781,16 @@ impl Default for Config {
                 default_address: 0x7E0,
                 timeout: 5000,
                 max_retries: 3,
+                tester_present_interval: default_tester_present_interval(),
+                retry_delay: default_retry_delay(),
+                retry_max_delay: default_retry_max_delay(),
+                transport: None,
+                transports: HashMap::new(),
+                p2_star_timeout: default_p2_star_timeout(),
+                p2_margin: default_p2_margin(),
+                max_dids_per_request: default_max_dids_per_request(),
+                max_response_length: default_max_response_length(),
+                timing: HashMap::new(),
             },
             doip: DoipConfig {
                 enabled: true,
@@ -109,7 +798,14 @@ impl Default for Config {
                 port: 13400,
                 source_address: 0x0E80,
                 target_logical_address: 0x1000,
+                protocol_version: default_doip_protocol_version(),
+                activation_type: 0x00,
+                connect_timeout: default_doip_connect_timeout(),
+                ack_timeout: default_doip_ack_timeout(),
             },
+            isotp: IsoTpConfig::default(),
+            simulator: SimulatorConfig::default(),
+            dictionary: DictionaryConfig::default(),
             logging: LoggingConfig {
                 level: "info".to_string(),
                 format: "pretty".to_string(),
@@ -120,11 +816,46 @@ impl Default for Config {
             security: SecurityConfig {
                 require_security_access: false,
                 security_level: 0x01,
+                algorithms: Vec::new(),
+                max_attempts: default_max_attempts(),
+                lockout_delay: default_lockout_delay(),
+                api_token: None,
             },
             performance: PerformanceConfig {
                 max_concurrent_requests: 10,
                 connection_pool_size: 5,
+                ecu_queue_length: default_ecu_queue_length(),
             },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_component_sections_must_name_registered_components() {
+        let timing = ComponentTimingConfig {
+            p2: Some(150),
+            p2_star: None,
+        };
+
+        let mut config = Config::default();
+        config.uds.timing.insert("engine".to_string(), timing.clone());
+        config.resolve_components().unwrap();
+        assert_eq!(config.get_component_timing("engine").p2, 150);
+
+        let mut config = Config::default();
+        config.uds.timing.insert("engnie".to_string(), timing);
+        let error = config.resolve_components().unwrap_err();
+        assert!(error.contains("uds.timing") && error.contains("engnie"), "{}", error);
+
+        let mut config = Config::default();
+        config
+            .uds
+            .transports
+            .insert("engnie".to_string(), TransportKind::Simulator);
+        assert!(config.resolve_components().is_err());
+    }
+}
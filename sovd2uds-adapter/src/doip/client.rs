@@ -0,0 +1,777 @@
+use super::protocol::*;
+use crate::config::DoipConfig;
+use crate::error::{Result, Sovd2UdsError};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant};
+use tracing::{debug, info, warn};
+
+/// Active TCP connection with routing activated
+///
+/// A reader task receives every message and sorts it into the inbox, so
+/// waiting for one ECU never blocks requests to another.
+struct DoipConnection {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    entity_address: u16,
+    inbox: Arc<Inbox>,
+    reader: JoinHandle<()>,
+}
+
+impl Drop for DoipConnection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Messages received on a connection, waiting to be picked up
+#[derive(Default)]
+struct Inbox {
+    state: std::sync::Mutex<InboxState>,
+    /// Woken whenever a message arrives or the connection ends
+    arrived: Notify,
+}
+
+#[derive(Default)]
+struct InboxState {
+    /// UDS responses by ECU (source) address
+    responses: HashMap<u16, VecDeque<Vec<u8>>>,
+    /// Diagnostic message acknowledges by ECU address: ACK or the NACK code
+    acks: HashMap<u16, VecDeque<std::result::Result<(), u8>>>,
+    /// Generic NACKs received so far and the code of the last one
+    generic_nacks: u64,
+    generic_nack_code: u8,
+    /// Why the connection ended, once the reader stopped
+    closed: Option<String>,
+}
+
+impl Inbox {
+    fn update(&self, change: impl FnOnce(&mut InboxState)) {
+        change(&mut self.state.lock().unwrap());
+        self.arrived.notify_waiters();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed.is_some()
+    }
+
+    /// Wait until `take` finds what the caller is waiting for
+    async fn wait<T>(
+        &self,
+        deadline: Instant,
+        what: &str,
+        mut take: impl FnMut(&mut InboxState) -> Option<Result<T>>,
+    ) -> Result<T> {
+        loop {
+            // Register before looking so an arrival in between is not missed
+            let arrived = self.arrived.notified();
+            tokio::pin!(arrived);
+            arrived.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(result) = take(&mut state) {
+                    return result;
+                }
+                if let Some(reason) = &state.closed {
+                    return Err(Sovd2UdsError::UdsCommunication(format!(
+                        "DoIP connection lost: {}",
+                        reason
+                    )));
+                }
+            }
+
+            if tokio::time::timeout_at(deadline, arrived).await.is_err() {
+                return Err(Sovd2UdsError::Timeout(what.to_string()));
+            }
+        }
+    }
+}
+
+/// Native async DoIP (ISO 13400-2) client
+///
+/// A single TCP connection to the DoIP entity is shared by every ECU
+/// behind it; responses are demultiplexed by their source address.
+pub struct DoipClient {
+    config: DoipConfig,
+    source_address: u16,
+    connection: Mutex<Option<Arc<DoipConnection>>>,
+}
+
+impl DoipClient {
+    /// Create a new DoIP client (no connection is opened yet)
+    pub fn new(config: DoipConfig) -> Result<Self> {
+        let source_address = u16::try_from(config.source_address).map_err(|_| {
+            Sovd2UdsError::Config(format!(
+                "DoIP source address 0x{:X} does not fit 16 bits",
+                config.source_address
+            ))
+        })?;
+        Ok(Self {
+            config,
+            source_address,
+            connection: Mutex::new(None),
+        })
+    }
+
+    /// Tester logical address
+    pub fn source_address(&self) -> u16 {
+        self.source_address
+    }
+
+    /// Connect to the DoIP entity and activate routing (no-op if already connected)
+    pub async fn connect(&self) -> Result<()> {
+        self.ensure_connected().await.map(|_| ())
+    }
+
+    /// Close the TCP connection
+    pub async fn disconnect(&self) -> Result<()> {
+        let connection = self.connection.lock().await.take();
+        if let Some(conn) = connection {
+            let _ = conn.writer.lock().await.shutdown().await;
+            info!(
+                "Disconnected from DoIP entity 0x{:04X}",
+                conn.entity_address
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether a routed connection is currently open
+    pub async fn is_connected(&self) -> bool {
+        self.connection
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|conn| !conn.inbox.is_closed())
+    }
+
+    /// Logical address reported by the DoIP entity during routing activation
+    pub async fn entity_address(&self) -> Option<u16> {
+        self.connection
+            .lock()
+            .await
+            .as_ref()
+            .map(|conn| conn.entity_address)
+    }
+
+    /// Send a UDS request to `target_address` and wait for the DoIP acknowledge
+    pub async fn send_diagnostic(&self, target_address: u16, user_data: &[u8]) -> Result<()> {
+        let conn = self.ensure_connected().await?;
+
+        let result = self.send_on(&conn, target_address, user_data).await;
+        self.drop_on_link_error(&conn, &result).await;
+        result
+    }
+
+    /// Wait for the next UDS response sent by `source_address`
+    pub async fn receive_diagnostic(&self, source_address: u16, wait: Duration) -> Result<Vec<u8>> {
+        let conn = self.connection.lock().await.clone().ok_or_else(|| {
+            Sovd2UdsError::UdsCommunication("DoIP connection not established".to_string())
+        })?;
+
+        let result = Self::receive_on(&conn, source_address, wait).await;
+        self.drop_on_link_error(&conn, &result).await;
+        result
+    }
+
+    /// Send a UDS request and wait for its response in one exchange
+    pub async fn request(
+        &self,
+        target_address: u16,
+        user_data: &[u8],
+        wait: Duration,
+    ) -> Result<Vec<u8>> {
+        let conn = self.ensure_connected().await?;
+
+        let result = match self.send_on(&conn, target_address, user_data).await {
+            Ok(()) => Self::receive_on(&conn, target_address, wait).await,
+            Err(e) => Err(e),
+        };
+        self.drop_on_link_error(&conn, &result).await;
+        result
+    }
+
+    /// Send a vehicle identification request over UDP and collect announcements
+    pub async fn identify_vehicles(&self, wait: Duration) -> Result<Vec<VehicleAnnouncement>> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.set_broadcast(true)?;
+
+        let request =
+            DoipMessage::vehicle_identification_request().to_bytes(self.config.protocol_version);
+        let target = format!("{}:{}", self.config.target_address, self.config.port);
+        socket.send_to(&request, &target).await?;
+        debug!("Sent vehicle identification request to {}", target);
+
+        let deadline = Instant::now() + wait;
+        let mut announcements = Vec::new();
+        let mut buffer = [0u8; 512];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let received = match timeout(remaining, socket.recv_from(&mut buffer)).await {
+                Ok(received) => received?,
+                Err(_) => break,
+            };
+
+            let (length, peer) = received;
+            match DoipMessage::from_bytes(&buffer[..length]) {
+                Ok(message) if message.kind() == Some(PayloadType::VehicleAnnouncement) => {
+                    let announcement = VehicleAnnouncement::from_payload(&message.payload)?;
+                    info!(
+                        "Vehicle announcement from {}: VIN={}, address=0x{:04X}",
+                        peer, announcement.vin, announcement.logical_address
+                    );
+                    announcements.push(announcement);
+                }
+                Ok(message) => {
+                    debug!(
+                        "Ignoring DoIP payload type 0x{:04X} from {}",
+                        message.payload_type, peer
+                    );
+                }
+                Err(e) => warn!("Invalid DoIP datagram from {}: {}", peer, e),
+            }
+        }
+
+        Ok(announcements)
+    }
+
+    /// Open the TCP socket and perform routing activation
+    async fn open(&self) -> Result<DoipConnection> {
+        let address = format!("{}:{}", self.config.target_address, self.config.port);
+        let connect_timeout = Duration::from_millis(self.config.connect_timeout as u64);
+
+        let stream = timeout(connect_timeout, TcpStream::connect(&address))
+            .await
+            .map_err(|_| Sovd2UdsError::Timeout(format!("DoIP connect to {}", address)))?
+            .map_err(|e| {
+                Sovd2UdsError::UdsCommunication(format!(
+                    "DoIP connect to {} failed: {}",
+                    address, e
+                ))
+            })?;
+        stream.set_nodelay(true)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = MessageReader::new(reader);
+
+        let request = DoipMessage::routing_activation_request(
+            self.source_address(),
+            self.config.activation_type,
+        );
+        write_message(&mut writer, &request, self.config.protocol_version).await?;
+
+        let deadline = Instant::now() + connect_timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = timeout(remaining, reader.next())
+                .await
+                .map_err(|_| Sovd2UdsError::Timeout("DoIP routing activation".to_string()))??;
+
+            match message.kind() {
+                Some(PayloadType::RoutingActivationResponse) => {
+                    if message.payload.len() < 5 {
+                        return Err(Sovd2UdsError::UdsCommunication(
+                            "Routing activation response too short".to_string(),
+                        ));
+                    }
+                    let entity_address =
+                        u16::from_be_bytes([message.payload[2], message.payload[3]]);
+                    let code = message.payload[4];
+                    if code != routing_activation::SUCCESS {
+                        return Err(Sovd2UdsError::UdsCommunication(format!(
+                            "Routing activation rejected (0x{:02X}): {}",
+                            code,
+                            routing_activation::description(code)
+                        )));
+                    }
+
+                    if entity_address as u32 != self.config.target_logical_address {
+                        warn!(
+                            "DoIP entity reported logical address 0x{:04X}, expected 0x{:04X}",
+                            entity_address, self.config.target_logical_address
+                        );
+                    }
+
+                    info!(
+                        "Routing activated on {} (tester 0x{:04X}, entity 0x{:04X})",
+                        address,
+                        self.source_address(),
+                        entity_address
+                    );
+                    let writer = Arc::new(Mutex::new(writer));
+                    let inbox = Arc::new(Inbox::default());
+                    let task = Self::read_loop(
+                        reader,
+                        Arc::clone(&writer),
+                        Arc::clone(&inbox),
+                        self.source_address(),
+                        self.config.protocol_version,
+                    );
+                    return Ok(DoipConnection {
+                        writer,
+                        entity_address,
+                        inbox,
+                        reader: tokio::spawn(task),
+                    });
+                }
+                Some(PayloadType::AliveCheckRequest) => {
+                    let response = DoipMessage::alive_check_response(self.source_address());
+                    write_message(&mut writer, &response, self.config.protocol_version).await?;
+                }
+                Some(PayloadType::GenericNack) => {
+                    return Err(Self::generic_nack_error(
+                        message.payload.first().copied().unwrap_or(0xFF),
+                    ));
+                }
+                _ => {
+                    debug!(
+                        "Ignoring DoIP payload type 0x{:04X} during routing activation",
+                        message.payload_type
+                    );
+                }
+            }
+        }
+    }
+
+    /// Current connection, opened first when there is none or it was lost
+    async fn ensure_connected(&self) -> Result<Arc<DoipConnection>> {
+        let mut connection = self.connection.lock().await;
+        match connection.as_ref() {
+            Some(conn) if !conn.inbox.is_closed() => Ok(Arc::clone(conn)),
+            _ => {
+                let conn = Arc::new(self.open().await?);
+                *connection = Some(Arc::clone(&conn));
+                Ok(conn)
+            }
+        }
+    }
+
+    /// Receive messages until the connection fails, answering alive checks
+    /// and sorting diagnostic messages and acknowledges into the inbox
+    async fn read_loop(
+        mut reader: MessageReader<OwnedReadHalf>,
+        writer: Arc<Mutex<OwnedWriteHalf>>,
+        inbox: Arc<Inbox>,
+        source_address: u16,
+        version: u8,
+    ) {
+        let reason = loop {
+            let message = match reader.next().await {
+                Ok(message) => message,
+                Err(e) => break e.to_string(),
+            };
+
+            match message.kind() {
+                Some(PayloadType::DiagnosticMessage) => {
+                    if let Some((source, _, user_data)) = message.diagnostic_parts() {
+                        let user_data = user_data.to_vec();
+                        inbox.update(|state| {
+                            state.responses.entry(source).or_default().push_back(user_data)
+                        });
+                    }
+                }
+                Some(PayloadType::DiagnosticMessageAck | PayloadType::DiagnosticMessageNack) => {
+                    let Some((source, _, rest)) = message.diagnostic_parts() else {
+                        warn!("Ignoring malformed diagnostic acknowledge");
+                        continue;
+                    };
+                    let ack = match message.kind() {
+                        Some(PayloadType::DiagnosticMessageAck) => Ok(()),
+                        _ => Err(rest.first().copied().unwrap_or(0xFF)),
+                    };
+                    inbox.update(|state| state.acks.entry(source).or_default().push_back(ack));
+                }
+                Some(PayloadType::AliveCheckRequest) => {
+                    debug!("Answering DoIP alive check");
+                    let response = DoipMessage::alive_check_response(source_address);
+                    let mut writer = writer.lock().await;
+                    if let Err(e) = write_message(&mut *writer, &response, version).await {
+                        break e.to_string();
+                    }
+                }
+                Some(PayloadType::GenericNack) => {
+                    let code = message.payload.first().copied().unwrap_or(0xFF);
+                    inbox.update(|state| {
+                        state.generic_nacks += 1;
+                        state.generic_nack_code = code;
+                    });
+                }
+                _ => {
+                    debug!("Ignoring DoIP payload type 0x{:04X}", message.payload_type);
+                }
+            }
+        };
+
+        warn!("DoIP connection lost: {}", reason);
+        inbox.update(|state| state.closed = Some(reason));
+    }
+
+    async fn send_on(
+        &self,
+        conn: &DoipConnection,
+        target_address: u16,
+        user_data: &[u8],
+    ) -> Result<()> {
+        // Acknowledges left from an earlier request that timed out are stale
+        let generic_nacks = {
+            let mut state = conn.inbox.state.lock().unwrap();
+            state.acks.remove(&target_address);
+            state.generic_nacks
+        };
+
+        let message =
+            DoipMessage::diagnostic_message(self.source_address(), target_address, user_data);
+        // Written by a task of its own: a request dropped at its deadline must
+        // not leave half a message on the shared connection
+        let writer = Arc::clone(&conn.writer);
+        let version = self.config.protocol_version;
+        tokio::spawn(async move {
+            let mut writer = writer.lock().await;
+            write_message(&mut *writer, &message, version).await
+        })
+        .await
+        .map_err(|e| Sovd2UdsError::Internal(format!("DoIP write task failed: {}", e)))??;
+
+        let ack_timeout = Duration::from_millis(self.config.ack_timeout as u64);
+        let deadline = Instant::now() + ack_timeout;
+        conn.inbox
+            .wait(deadline, "DoIP diagnostic acknowledge", |state| {
+                if state.generic_nacks > generic_nacks {
+                    return Some(Err(Self::generic_nack_error(state.generic_nack_code)));
+                }
+                let ack = state.acks.get_mut(&target_address)?.pop_front()?;
+                Some(ack.map_err(|code| {
+                    Sovd2UdsError::UdsCommunication(format!(
+                        "Diagnostic message to 0x{:04X} rejected (0x{:02X}): {}",
+                        target_address,
+                        code,
+                        diagnostic_nack::description(code)
+                    ))
+                }))
+            })
+            .await
+    }
+
+    async fn receive_on(
+        conn: &DoipConnection,
+        source_address: u16,
+        wait: Duration,
+    ) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + wait;
+        conn.inbox
+            .wait(deadline, "UDS response over DoIP", |state| {
+                state.responses.get_mut(&source_address)?.pop_front().map(Ok)
+            })
+            .await
+    }
+
+    fn generic_nack_error(code: u8) -> Sovd2UdsError {
+        Sovd2UdsError::UdsCommunication(format!("DoIP generic NACK (0x{:02X})", code))
+    }
+
+    /// Forget the connection after IO failures and malformed messages so the
+    /// next call reconnects; timeouts leave it, and the other ECUs, untouched
+    async fn drop_on_link_error<T>(&self, conn: &Arc<DoipConnection>, result: &Result<T>) {
+        match result {
+            Err(e) if conn.inbox.is_closed() || e.is_link_error() => {
+                let mut connection = self.connection.lock().await;
+                if connection.as_ref().is_some_and(|current| Arc::ptr_eq(current, conn)) {
+                    warn!("Dropping DoIP connection: {}", e);
+                    *connection = None;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::doip::DoipGateway;
+    use std::sync::Arc;
+
+    const GATEWAY_ADDRESS: u16 = 0x1000;
+    const ECU_ADDRESS: u16 = 0x0010;
+    const OTHER_ECU_ADDRESS: u16 = 0x0011;
+    const VERSION: u8 = protocol_version::ISO_13400_2012;
+
+    /// Gateway answering ReadDataByIdentifier for ECU_ADDRESS and
+    /// OTHER_ECU_ADDRESS, suppressing the response to TesterPresent and
+    /// rejecting other targets
+    async fn gateway() -> std::net::SocketAddr {
+        let handler = Arc::new(|target: u16, request: &[u8]| match (target, request) {
+            (ECU_ADDRESS | OTHER_ECU_ADDRESS, [0x22, did @ ..]) => {
+                Some([&[0x62][..], did, b"OK"].concat())
+            }
+            (ECU_ADDRESS, [0x3E, 0x80]) => Some(Vec::new()),
+            (ECU_ADDRESS, [sid, ..]) => Some(vec![0x7F, *sid, 0x11]),
+            _ => None,
+        });
+        let gateway = DoipGateway::new(GATEWAY_ADDRESS, "WVWZZZ1JZXW000001", handler);
+        gateway.spawn("127.0.0.1:0").await.unwrap().0
+    }
+
+    fn client(address: std::net::SocketAddr) -> DoipClient {
+        let mut config = Config::default().doip;
+        config.target_address = address.ip().to_string();
+        config.port = address.port();
+        config.target_logical_address = GATEWAY_ADDRESS as u32;
+        config.connect_timeout = 500;
+        config.ack_timeout = 500;
+        DoipClient::new(config).unwrap()
+    }
+
+    #[test]
+    fn source_address_must_fit_16_bits() {
+        let mut config = Config::default().doip;
+        config.source_address = 0x1_0E80;
+        assert!(matches!(DoipClient::new(config), Err(Sovd2UdsError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn routing_activation_reports_entity_address() {
+        let client = client(gateway().await);
+        assert!(!client.is_connected().await);
+
+        client.connect().await.unwrap();
+        assert!(client.is_connected().await);
+        assert_eq!(client.entity_address().await, Some(GATEWAY_ADDRESS));
+
+        client.disconnect().await.unwrap();
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn diagnostic_message_is_acknowledged_and_answered() {
+        let client = client(gateway().await);
+        let response = client
+            .request(ECU_ADDRESS, &[0x22, 0xF1, 0x90], Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response, [0x62, 0xF1, 0x90, b'O', b'K']);
+
+        // Negative UDS responses are regular diagnostic messages
+        let response = client
+            .request(ECU_ADDRESS, &[0x2E, 0xF1, 0x90], Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response, [0x7F, 0x2E, 0x11]);
+    }
+
+    #[tokio::test]
+    async fn diagnostic_nack_keeps_the_connection() {
+        let client = client(gateway().await);
+        let error = client
+            .send_diagnostic(0x0020, &[0x22, 0xF1, 0x90])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, Sovd2UdsError::UdsCommunication(msg) if msg.contains("(0x03)")),
+            "{}",
+            error
+        );
+        assert!(client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn timeout_keeps_the_connection() {
+        let client = client(gateway().await);
+        client
+            .send_diagnostic(ECU_ADDRESS, &[0x3E, 0x80])
+            .await
+            .unwrap();
+        let error = client
+            .receive_diagnostic(ECU_ADDRESS, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Sovd2UdsError::Timeout(_)));
+        assert!(client.is_connected().await);
+
+        let response = client
+            .request(ECU_ADDRESS, &[0x22, 0xF1, 0x90], Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response[0], 0x62);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_one_ecu_does_not_block_another() {
+        let client = Arc::new(client(gateway().await));
+        client
+            .send_diagnostic(ECU_ADDRESS, &[0x3E, 0x80])
+            .await
+            .unwrap();
+        let waiting = {
+            let client = Arc::clone(&client);
+            tokio::spawn(async move {
+                client
+                    .receive_diagnostic(ECU_ADDRESS, Duration::from_millis(600))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let started = Instant::now();
+        let response = client
+            .request(OTHER_ECU_ADDRESS, &[0x22, 0xF1, 0x90], Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response[0], 0x62);
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(!waiting.is_finished());
+
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(Sovd2UdsError::Timeout(_))
+        ));
+        assert!(client.is_connected().await);
+    }
+
+    /// DoIP entity that activates routing and then hands the stream to
+    /// `script`
+    async fn scripted_entity<F, Fut>(script: F) -> std::net::SocketAddr
+    where
+        F: FnOnce(TcpStream) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_message(&mut stream).await.unwrap();
+            let tester = u16::from_be_bytes([request.payload[0], request.payload[1]]);
+            let response = DoipMessage::routing_activation_response(
+                tester,
+                GATEWAY_ADDRESS,
+                routing_activation::SUCCESS,
+            );
+            write_message(&mut stream, &response, VERSION)
+                .await
+                .unwrap();
+            script(stream).await;
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn client_answers_alive_check_while_waiting() {
+        let address = scripted_entity(|mut stream| async move {
+            let request = read_message(&mut stream).await.unwrap();
+            let (tester, _, _) = request.diagnostic_parts().unwrap();
+            let ack = DoipMessage::diagnostic_ack(ECU_ADDRESS, tester, diagnostic_nack::ACK);
+            write_message(&mut stream, &ack, VERSION).await.unwrap();
+
+            write_message(&mut stream, &DoipMessage::alive_check_request(), VERSION)
+                .await
+                .unwrap();
+            let answer = read_message(&mut stream).await.unwrap();
+            assert_eq!(answer.kind(), Some(PayloadType::AliveCheckResponse));
+            assert_eq!(answer.payload, tester.to_be_bytes());
+
+            let reply = DoipMessage::diagnostic_message(ECU_ADDRESS, tester, &[0x50, 0x03]);
+            write_message(&mut stream, &reply, VERSION).await.unwrap();
+            // Keep the connection open until the client is done
+            let _ = read_message(&mut stream).await;
+        })
+        .await;
+
+        let client = client(address);
+        let response = client
+            .request(ECU_ADDRESS, &[0x10, 0x03], Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response, [0x50, 0x03]);
+    }
+
+    #[tokio::test]
+    async fn message_split_across_a_timeout_is_received() {
+        let address = scripted_entity(|mut stream| async move {
+            let request = read_message(&mut stream).await.unwrap();
+            let (tester, _, _) = request.diagnostic_parts().unwrap();
+            let ack = DoipMessage::diagnostic_ack(ECU_ADDRESS, tester, diagnostic_nack::ACK);
+            write_message(&mut stream, &ack, VERSION).await.unwrap();
+
+            let reply = DoipMessage::diagnostic_message(ECU_ADDRESS, tester, &[0x50, 0x03])
+                .to_bytes(VERSION);
+            stream.write_all(&reply[..5]).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stream.write_all(&reply[5..]).await.unwrap();
+            let _ = read_message(&mut stream).await;
+        })
+        .await;
+
+        let client = client(address);
+        client
+            .send_diagnostic(ECU_ADDRESS, &[0x10, 0x03])
+            .await
+            .unwrap();
+        let error = client
+            .receive_diagnostic(ECU_ADDRESS, Duration::from_millis(20))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Sovd2UdsError::Timeout(_)));
+
+        let response = client
+            .receive_diagnostic(ECU_ADDRESS, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(response, [0x50, 0x03]);
+    }
+
+    #[tokio::test]
+    async fn malformed_header_drops_the_connection() {
+        let address = scripted_entity(|mut stream| async move {
+            let _ = read_message(&mut stream).await;
+            stream
+                .write_all(&[0x02, 0x02, 0x80, 0x02, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            let _ = read_message(&mut stream).await;
+        })
+        .await;
+
+        let client = client(address);
+        let error = client
+            .send_diagnostic(ECU_ADDRESS, &[0x10, 0x03])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, Sovd2UdsError::UdsCommunication(_)),
+            "{}",
+            error
+        );
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn gateway_answers_alive_check() {
+        let address = gateway().await;
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        let activation = DoipMessage::routing_activation_request(0x0E80, 0x00);
+        write_message(&mut stream, &activation, VERSION)
+            .await
+            .unwrap();
+        let response = read_message(&mut stream).await.unwrap();
+        assert_eq!(
+            response.kind(),
+            Some(PayloadType::RoutingActivationResponse)
+        );
+        assert_eq!(response.payload[4], routing_activation::SUCCESS);
+
+        write_message(&mut stream, &DoipMessage::alive_check_request(), VERSION)
+            .await
+            .unwrap();
+        let response = read_message(&mut stream).await.unwrap();
+        assert_eq!(response.kind(), Some(PayloadType::AliveCheckResponse));
+        assert_eq!(response.payload, GATEWAY_ADDRESS.to_be_bytes());
+    }
+}
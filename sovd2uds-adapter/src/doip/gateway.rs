@@ -0,0 +1,185 @@
+use super::protocol::*;
+use crate::error::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Produces the UDS response for a request sent to a target address.
+///
+/// `None` makes the gateway NACK the message with "unknown target address";
+/// an empty response is acknowledged but never answered (suppressed response).
+pub type DiagnosticHandler = Arc<dyn Fn(u16, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Minimal in-process DoIP entity standing in for a vehicle gateway
+///
+/// Answers vehicle identification over UDP, routing activation and
+/// diagnostic messages over TCP, forwarding UDS payloads to a handler.
+pub struct DoipGateway {
+    logical_address: u16,
+    vin: String,
+    version: u8,
+    handler: DiagnosticHandler,
+}
+
+impl DoipGateway {
+    /// Create a gateway with the given logical address and VIN
+    pub fn new(logical_address: u16, vin: &str, handler: DiagnosticHandler) -> Self {
+        Self {
+            logical_address,
+            vin: vin.to_string(),
+            version: protocol_version::ISO_13400_2012,
+            handler,
+        }
+    }
+
+    /// Bind TCP and UDP on `address` (use port 0 for an ephemeral port) and serve in the background
+    pub async fn spawn(self, address: &str) -> Result<(SocketAddr, JoinHandle<()>)> {
+        let listener = TcpListener::bind(address).await?;
+        let local_address = listener.local_addr()?;
+        let udp = UdpSocket::bind(local_address).await?;
+        info!("DoIP gateway stand-in listening on {}", local_address);
+
+        let gateway = Arc::new(self);
+
+        let discovery = Arc::clone(&gateway);
+        tokio::spawn(async move { discovery.serve_discovery(udp).await });
+
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        debug!("DoIP gateway accepted connection from {}", peer);
+                        let gateway = Arc::clone(&gateway);
+                        tokio::spawn(async move {
+                            if let Err(e) = gateway.serve_connection(stream).await {
+                                debug!("DoIP gateway connection from {} closed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        warn!("DoIP gateway accept failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok((local_address, task))
+    }
+
+    fn announcement(&self) -> VehicleAnnouncement {
+        VehicleAnnouncement {
+            vin: self.vin.clone(),
+            logical_address: self.logical_address,
+            eid: [0x00, 0x1A, 0x37, 0x00, 0x00, 0x01],
+            gid: [0x00; 6],
+            further_action: 0x00,
+            sync_status: None,
+        }
+    }
+
+    async fn serve_discovery(&self, socket: UdpSocket) {
+        let mut buffer = [0u8; 512];
+        loop {
+            let (length, peer) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("DoIP gateway discovery socket failed: {}", e);
+                    return;
+                }
+            };
+
+            let Ok(message) = DoipMessage::from_bytes(&buffer[..length]) else {
+                continue;
+            };
+
+            if matches!(
+                message.kind(),
+                Some(PayloadType::VehicleIdentificationRequest)
+                    | Some(PayloadType::VehicleIdentificationRequestEid)
+                    | Some(PayloadType::VehicleIdentificationRequestVin)
+            ) {
+                let response = DoipMessage::new(
+                    PayloadType::VehicleAnnouncement,
+                    self.announcement().to_payload(),
+                );
+                let _ = socket.send_to(&response.to_bytes(self.version), peer).await;
+            }
+        }
+    }
+
+    async fn serve_connection(&self, mut stream: TcpStream) -> Result<()> {
+        let mut tester_address: Option<u16> = None;
+
+        loop {
+            let message = read_message(&mut stream).await?;
+
+            match message.kind() {
+                Some(PayloadType::RoutingActivationRequest) => {
+                    if message.payload.len() < 7 {
+                        let nack = DoipMessage::generic_nack(generic_nack::INVALID_PAYLOAD_LENGTH);
+                        write_message(&mut stream, &nack, self.version).await?;
+                        continue;
+                    }
+                    let source = u16::from_be_bytes([message.payload[0], message.payload[1]]);
+                    tester_address = Some(source);
+                    let response = DoipMessage::routing_activation_response(
+                        source,
+                        self.logical_address,
+                        routing_activation::SUCCESS,
+                    );
+                    write_message(&mut stream, &response, self.version).await?;
+                }
+                Some(PayloadType::DiagnosticMessage) => {
+                    let Some((source, target, user_data)) = message.diagnostic_parts() else {
+                        let nack = DoipMessage::generic_nack(generic_nack::INVALID_PAYLOAD_LENGTH);
+                        write_message(&mut stream, &nack, self.version).await?;
+                        continue;
+                    };
+
+                    if tester_address != Some(source) {
+                        let nack = DoipMessage::diagnostic_ack(
+                            target,
+                            source,
+                            diagnostic_nack::INVALID_SOURCE_ADDRESS,
+                        );
+                        write_message(&mut stream, &nack, self.version).await?;
+                        continue;
+                    }
+
+                    match (self.handler)(target, user_data) {
+                        Some(response) => {
+                            let ack =
+                                DoipMessage::diagnostic_ack(target, source, diagnostic_nack::ACK);
+                            write_message(&mut stream, &ack, self.version).await?;
+                            if !response.is_empty() {
+                                let reply =
+                                    DoipMessage::diagnostic_message(target, source, &response);
+                                write_message(&mut stream, &reply, self.version).await?;
+                            }
+                        }
+                        None => {
+                            let nack = DoipMessage::diagnostic_ack(
+                                target,
+                                source,
+                                diagnostic_nack::UNKNOWN_TARGET_ADDRESS,
+                            );
+                            write_message(&mut stream, &nack, self.version).await?;
+                        }
+                    }
+                }
+                Some(PayloadType::AliveCheckRequest) => {
+                    let response = DoipMessage::alive_check_response(self.logical_address);
+                    write_message(&mut stream, &response, self.version).await?;
+                }
+                Some(PayloadType::AliveCheckResponse) => {}
+                _ => {
+                    let nack = DoipMessage::generic_nack(generic_nack::UNKNOWN_PAYLOAD_TYPE);
+                    write_message(&mut stream, &nack, self.version).await?;
+                }
+            }
+        }
+    }
+}
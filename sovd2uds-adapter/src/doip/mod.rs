@@ -0,0 +1,9 @@
+pub mod client;
+pub mod gateway;
+pub mod protocol;
+pub mod transport;
+
+pub use client::DoipClient;
+pub use gateway::{DiagnosticHandler, DoipGateway};
+pub use protocol::{DoipMessage, PayloadType, VehicleAnnouncement};
+pub use transport::DoipTransport;
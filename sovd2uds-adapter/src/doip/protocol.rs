@@ -0,0 +1,427 @@
+use crate::error::{Result, Sovd2UdsError};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Default DoIP port (UDP discovery and TCP data)
+pub const DOIP_PORT: u16 = 13400;
+
+/// Length of the generic DoIP header
+pub const HEADER_LENGTH: usize = 8;
+
+/// Largest payload accepted from the network
+pub const MAX_PAYLOAD_LENGTH: u32 = 0x0001_0000;
+
+/// Length of a VIN in vehicle announcements
+pub const VIN_LENGTH: usize = 17;
+
+/// DoIP protocol versions
+pub mod protocol_version {
+    pub const ISO_13400_2010: u8 = 0x01;
+    pub const ISO_13400_2012: u8 = 0x02;
+    pub const ISO_13400_2019: u8 = 0x03;
+    /// Default value accepted in vehicle identification requests
+    pub const DEFAULT: u8 = 0xFF;
+}
+
+/// DoIP payload types (ISO 13400-2)
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadType {
+    GenericNack = 0x0000,
+    VehicleIdentificationRequest = 0x0001,
+    VehicleIdentificationRequestEid = 0x0002,
+    VehicleIdentificationRequestVin = 0x0003,
+    VehicleAnnouncement = 0x0004,
+    RoutingActivationRequest = 0x0005,
+    RoutingActivationResponse = 0x0006,
+    AliveCheckRequest = 0x0007,
+    AliveCheckResponse = 0x0008,
+    EntityStatusRequest = 0x4001,
+    EntityStatusResponse = 0x4002,
+    PowerModeRequest = 0x4003,
+    PowerModeResponse = 0x4004,
+    DiagnosticMessage = 0x8001,
+    DiagnosticMessageAck = 0x8002,
+    DiagnosticMessageNack = 0x8003,
+}
+
+impl PayloadType {
+    pub fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            0x0000 => Some(Self::GenericNack),
+            0x0001 => Some(Self::VehicleIdentificationRequest),
+            0x0002 => Some(Self::VehicleIdentificationRequestEid),
+            0x0003 => Some(Self::VehicleIdentificationRequestVin),
+            0x0004 => Some(Self::VehicleAnnouncement),
+            0x0005 => Some(Self::RoutingActivationRequest),
+            0x0006 => Some(Self::RoutingActivationResponse),
+            0x0007 => Some(Self::AliveCheckRequest),
+            0x0008 => Some(Self::AliveCheckResponse),
+            0x4001 => Some(Self::EntityStatusRequest),
+            0x4002 => Some(Self::EntityStatusResponse),
+            0x4003 => Some(Self::PowerModeRequest),
+            0x4004 => Some(Self::PowerModeResponse),
+            0x8001 => Some(Self::DiagnosticMessage),
+            0x8002 => Some(Self::DiagnosticMessageAck),
+            0x8003 => Some(Self::DiagnosticMessageNack),
+            _ => None,
+        }
+    }
+}
+
+/// Generic DoIP header negative acknowledge codes
+pub mod generic_nack {
+    pub const INCORRECT_PATTERN_FORMAT: u8 = 0x00;
+    pub const UNKNOWN_PAYLOAD_TYPE: u8 = 0x01;
+    pub const MESSAGE_TOO_LARGE: u8 = 0x02;
+    pub const OUT_OF_MEMORY: u8 = 0x03;
+    pub const INVALID_PAYLOAD_LENGTH: u8 = 0x04;
+}
+
+/// Routing activation response codes
+pub mod routing_activation {
+    pub const UNKNOWN_SOURCE_ADDRESS: u8 = 0x00;
+    pub const ALL_SOCKETS_REGISTERED: u8 = 0x01;
+    pub const SOURCE_ADDRESS_MISMATCH: u8 = 0x02;
+    pub const SOURCE_ADDRESS_ALREADY_ACTIVE: u8 = 0x03;
+    pub const MISSING_AUTHENTICATION: u8 = 0x04;
+    pub const REJECTED_CONFIRMATION: u8 = 0x05;
+    pub const UNSUPPORTED_ACTIVATION_TYPE: u8 = 0x06;
+    pub const TLS_REQUIRED: u8 = 0x07;
+    pub const SUCCESS: u8 = 0x10;
+    pub const CONFIRMATION_REQUIRED: u8 = 0x11;
+
+    /// Activation type used for normal diagnostic communication
+    pub const ACTIVATION_TYPE_DEFAULT: u8 = 0x00;
+
+    pub fn description(code: u8) -> &'static str {
+        match code {
+            UNKNOWN_SOURCE_ADDRESS => "Unknown source address",
+            ALL_SOCKETS_REGISTERED => "All TCP sockets registered and active",
+            SOURCE_ADDRESS_MISMATCH => {
+                "Source address differs from the one registered on this socket"
+            }
+            SOURCE_ADDRESS_ALREADY_ACTIVE => {
+                "Source address already registered on a different socket"
+            }
+            MISSING_AUTHENTICATION => "Missing authentication",
+            REJECTED_CONFIRMATION => "Rejected confirmation",
+            UNSUPPORTED_ACTIVATION_TYPE => "Unsupported routing activation type",
+            TLS_REQUIRED => "Secure connection (TLS) required",
+            SUCCESS => "Routing successfully activated",
+            CONFIRMATION_REQUIRED => "Routing will be activated, confirmation required",
+            _ => "Reserved",
+        }
+    }
+}
+
+/// Diagnostic message negative acknowledge codes
+pub mod diagnostic_nack {
+    pub const INVALID_SOURCE_ADDRESS: u8 = 0x02;
+    pub const UNKNOWN_TARGET_ADDRESS: u8 = 0x03;
+    pub const DIAGNOSTIC_MESSAGE_TOO_LARGE: u8 = 0x04;
+    pub const OUT_OF_MEMORY: u8 = 0x05;
+    pub const TARGET_UNREACHABLE: u8 = 0x06;
+    pub const UNKNOWN_NETWORK: u8 = 0x07;
+    pub const TRANSPORT_PROTOCOL_ERROR: u8 = 0x08;
+
+    /// Positive acknowledge code for diagnostic messages
+    pub const ACK: u8 = 0x00;
+
+    pub fn description(code: u8) -> &'static str {
+        match code {
+            INVALID_SOURCE_ADDRESS => "Invalid source address",
+            UNKNOWN_TARGET_ADDRESS => "Unknown target address",
+            DIAGNOSTIC_MESSAGE_TOO_LARGE => "Diagnostic message too large",
+            OUT_OF_MEMORY => "Out of memory",
+            TARGET_UNREACHABLE => "Target unreachable",
+            UNKNOWN_NETWORK => "Unknown network",
+            TRANSPORT_PROTOCOL_ERROR => "Transport protocol error",
+            _ => "Reserved",
+        }
+    }
+}
+
+/// A single DoIP message (header + payload)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoipMessage {
+    pub payload_type: u16,
+    pub payload: Vec<u8>,
+}
+
+impl DoipMessage {
+    pub fn new(payload_type: PayloadType, payload: Vec<u8>) -> Self {
+        Self {
+            payload_type: payload_type as u16,
+            payload,
+        }
+    }
+
+    /// Known payload type of this message, if any
+    pub fn kind(&self) -> Option<PayloadType> {
+        PayloadType::from_u16(self.payload_type)
+    }
+
+    /// Vehicle identification request (no filter)
+    pub fn vehicle_identification_request() -> Self {
+        Self::new(PayloadType::VehicleIdentificationRequest, vec![])
+    }
+
+    /// Routing activation request for the given tester address
+    pub fn routing_activation_request(source_address: u16, activation_type: u8) -> Self {
+        let mut payload = Vec::with_capacity(7);
+        payload.extend_from_slice(&source_address.to_be_bytes());
+        payload.push(activation_type);
+        payload.extend_from_slice(&[0x00; 4]); // ISO reserved
+        Self::new(PayloadType::RoutingActivationRequest, payload)
+    }
+
+    /// Routing activation response
+    pub fn routing_activation_response(tester_address: u16, entity_address: u16, code: u8) -> Self {
+        let mut payload = Vec::with_capacity(9);
+        payload.extend_from_slice(&tester_address.to_be_bytes());
+        payload.extend_from_slice(&entity_address.to_be_bytes());
+        payload.push(code);
+        payload.extend_from_slice(&[0x00; 4]); // ISO reserved
+        Self::new(PayloadType::RoutingActivationResponse, payload)
+    }
+
+    /// Alive check request
+    pub fn alive_check_request() -> Self {
+        Self::new(PayloadType::AliveCheckRequest, vec![])
+    }
+
+    /// Alive check response carrying the tester address
+    pub fn alive_check_response(source_address: u16) -> Self {
+        Self::new(
+            PayloadType::AliveCheckResponse,
+            source_address.to_be_bytes().to_vec(),
+        )
+    }
+
+    /// Diagnostic message carrying a UDS PDU
+    pub fn diagnostic_message(source_address: u16, target_address: u16, user_data: &[u8]) -> Self {
+        let mut payload = Vec::with_capacity(4 + user_data.len());
+        payload.extend_from_slice(&source_address.to_be_bytes());
+        payload.extend_from_slice(&target_address.to_be_bytes());
+        payload.extend_from_slice(user_data);
+        Self::new(PayloadType::DiagnosticMessage, payload)
+    }
+
+    /// Diagnostic message positive (code 0x00) or negative acknowledge
+    pub fn diagnostic_ack(source_address: u16, target_address: u16, code: u8) -> Self {
+        let payload_type = if code == diagnostic_nack::ACK {
+            PayloadType::DiagnosticMessageAck
+        } else {
+            PayloadType::DiagnosticMessageNack
+        };
+        let mut payload = Vec::with_capacity(5);
+        payload.extend_from_slice(&source_address.to_be_bytes());
+        payload.extend_from_slice(&target_address.to_be_bytes());
+        payload.push(code);
+        Self::new(payload_type, payload)
+    }
+
+    /// Generic header negative acknowledge
+    pub fn generic_nack(code: u8) -> Self {
+        Self::new(PayloadType::GenericNack, vec![code])
+    }
+
+    /// Serialize the message including the generic header
+    pub fn to_bytes(&self, version: u8) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + self.payload.len());
+        bytes.push(version);
+        bytes.push(!version);
+        bytes.extend_from_slice(&self.payload_type.to_be_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parse a complete datagram (used for UDP discovery)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LENGTH {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "DoIP message too short: {} bytes",
+                bytes.len()
+            )));
+        }
+
+        let header: [u8; HEADER_LENGTH] = bytes[..HEADER_LENGTH].try_into().unwrap();
+        let (payload_type, length) = parse_header(&header)?;
+        let payload = &bytes[HEADER_LENGTH..];
+        if payload.len() != length as usize {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "DoIP payload length mismatch: header says {}, got {}",
+                length,
+                payload.len()
+            )));
+        }
+
+        Ok(Self {
+            payload_type,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Source/target address pair and user data of a diagnostic message or (N)ACK
+    pub fn diagnostic_parts(&self) -> Option<(u16, u16, &[u8])> {
+        if self.payload.len() < 4 {
+            return None;
+        }
+        let source = u16::from_be_bytes([self.payload[0], self.payload[1]]);
+        let target = u16::from_be_bytes([self.payload[2], self.payload[3]]);
+        Some((source, target, &self.payload[4..]))
+    }
+}
+
+/// Validate a generic header and return (payload type, payload length)
+pub fn parse_header(header: &[u8; HEADER_LENGTH]) -> Result<(u16, u32)> {
+    if header[0] != !header[1] {
+        return Err(Sovd2UdsError::UdsCommunication(format!(
+            "Invalid DoIP header: version 0x{:02X}, inverse 0x{:02X}",
+            header[0], header[1]
+        )));
+    }
+
+    let payload_type = u16::from_be_bytes([header[2], header[3]]);
+    let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if length > MAX_PAYLOAD_LENGTH {
+        return Err(Sovd2UdsError::UdsCommunication(format!(
+            "DoIP payload too large: {} bytes",
+            length
+        )));
+    }
+
+    Ok((payload_type, length))
+}
+
+/// Read one DoIP message from a stream
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<DoipMessage> {
+    let mut header = [0u8; HEADER_LENGTH];
+    reader.read_exact(&mut header).await?;
+    let (payload_type, length) = parse_header(&header)?;
+
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload).await?;
+
+    Ok(DoipMessage {
+        payload_type,
+        payload,
+    })
+}
+
+/// Buffered DoIP message reader
+///
+/// `next` is cancel-safe: bytes of a message that is only partly received
+/// when the read is abandoned stay buffered, so the stream stays in step.
+pub struct MessageReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Read the next message; fails on IO errors, end of stream and malformed headers
+    pub async fn next(&mut self) -> Result<DoipMessage> {
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(message);
+            }
+
+            let mut chunk = [0u8; 4096];
+            let length = self.reader.read(&mut chunk).await?;
+            if length == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            self.buffer.extend_from_slice(&chunk[..length]);
+        }
+    }
+
+    /// Remove the first message from the buffer once it is complete
+    fn take_message(&mut self) -> Result<Option<DoipMessage>> {
+        if self.buffer.len() < HEADER_LENGTH {
+            return Ok(None);
+        }
+        let header: [u8; HEADER_LENGTH] = self.buffer[..HEADER_LENGTH].try_into().unwrap();
+        let (payload_type, length) = parse_header(&header)?;
+
+        let end = HEADER_LENGTH + length as usize;
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+        let payload = self.buffer[HEADER_LENGTH..end].to_vec();
+        self.buffer.drain(..end);
+        Ok(Some(DoipMessage {
+            payload_type,
+            payload,
+        }))
+    }
+}
+
+/// Write one DoIP message to a stream
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &DoipMessage,
+    version: u8,
+) -> Result<()> {
+    writer.write_all(&message.to_bytes(version)).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Vehicle announcement / vehicle identification response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VehicleAnnouncement {
+    pub vin: String,
+    pub logical_address: u16,
+    pub eid: [u8; 6],
+    pub gid: [u8; 6],
+    pub further_action: u8,
+    pub sync_status: Option<u8>,
+}
+
+impl VehicleAnnouncement {
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        if payload.len() < 32 {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "Vehicle announcement too short: {} bytes",
+                payload.len()
+            )));
+        }
+
+        let vin = String::from_utf8_lossy(&payload[..VIN_LENGTH]).to_string();
+        let logical_address = u16::from_be_bytes([payload[17], payload[18]]);
+        let eid = payload[19..25].try_into().unwrap();
+        let gid = payload[25..31].try_into().unwrap();
+
+        Ok(Self {
+            vin,
+            logical_address,
+            eid,
+            gid,
+            further_action: payload[31],
+            sync_status: payload.get(32).copied(),
+        })
+    }
+
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(33);
+        let mut vin = self.vin.as_bytes().to_vec();
+        vin.resize(VIN_LENGTH, 0x00);
+        payload.extend_from_slice(&vin);
+        payload.extend_from_slice(&self.logical_address.to_be_bytes());
+        payload.extend_from_slice(&self.eid);
+        payload.extend_from_slice(&self.gid);
+        payload.push(self.further_action);
+        if let Some(sync_status) = self.sync_status {
+            payload.push(sync_status);
+        }
+        payload
+    }
+}
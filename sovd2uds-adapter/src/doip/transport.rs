@@ -0,0 +1,49 @@
+use super::DoipClient;
+use crate::error::Result;
+use crate::uds::UdsTransport;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// UDS transport addressing one ECU behind a (shared) DoIP entity
+pub struct DoipTransport {
+    client: Arc<DoipClient>,
+    target_address: u16,
+}
+
+impl DoipTransport {
+    pub fn new(client: Arc<DoipClient>, target_address: u16) -> Self {
+        Self {
+            client,
+            target_address,
+        }
+    }
+}
+
+#[async_trait]
+impl UdsTransport for DoipTransport {
+    async fn connect(&self) -> Result<()> {
+        self.client.connect().await
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        // The DoIP connection is shared with other components; the pool closes it
+        Ok(())
+    }
+
+    async fn send(&self, request: &[u8]) -> Result<()> {
+        self.client
+            .send_diagnostic(self.target_address, request)
+            .await
+    }
+
+    async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+        self.client
+            .receive_diagnostic(self.target_address, timeout)
+            .await
+    }
+
+    fn name(&self) -> &'static str {
+        "doip"
+    }
+}
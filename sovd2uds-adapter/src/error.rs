@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Main error type for the SOVD2UDS adapter
@@ -25,12 +26,35 @@ pub enum Sovd2UdsError {
     #[error("Data item not found: {0}")]
     DataItemNotFound(String),
 
+    #[error("Actuator not found: {0}")]
+    ActuatorNotFound(String),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    #[error("Security access to level {level} denied: {description}")]
+    SecurityAccessDenied {
+        level: u8,
+        nrc: u8,
+        description: String,
+        /// Keys that may still be tried before the ECU locks out
+        remaining_attempts: u8,
+        /// Milliseconds until the ECU accepts requestSeed again
+        retry_after: Option<u64>,
+    },
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+
     #[error("FFI error: {0}")]
     Ffi(String),
 
@@ -44,6 +68,37 @@ pub enum Sovd2UdsError {
     Internal(String),
 }
 
+impl Sovd2UdsError {
+    /// Error for a negative response to `service`
+    pub fn negative_response(service: u8, nrc: u8) -> Self {
+        let description = UdsNegativeResponseCode::from_u8(nrc)
+            .map(|code| code.description().to_string())
+            .unwrap_or_else(|| format!("Unknown negative response code 0x{:02X}", nrc));
+        Self::UdsProtocol {
+            service,
+            nrc,
+            description,
+        }
+    }
+
+    /// Whether repeating the same request may succeed: the ECU was busy
+    /// (NRC 0x21), no response arrived in time, or the link failed
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::UdsProtocol { nrc, .. } => {
+                *nrc == UdsNegativeResponseCode::BusyRepeatRequest as u8
+            }
+            Self::Timeout(_) | Self::Io(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the transport link failed and must be reopened
+    pub fn is_link_error(&self) -> bool {
+        matches!(self, Self::Io(_))
+    }
+}
+
 /// UDS Negative Response Codes (NRC)
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -131,3 +186,7 @@ impl UdsNegativeResponseCode {
 
 /// Result type for SOVD2UDS operations
 pub type Result<T> = std::result::Result<T, Sovd2UdsError>;
+
+/// Result whose error may be reported for several items, e.g. one failed
+/// request of a bulk read
+pub type SharedResult<T> = std::result::Result<T, Arc<Sovd2UdsError>>;
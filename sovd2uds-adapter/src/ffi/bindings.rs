@@ -7,8 +7,14 @@
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
 use crate::error::{Result, Sovd2UdsError};
-use std::ffi::{CStr, CString};
-use std::ptr;
+use std::ffi::CString;
+use std::sync::Mutex;
+
+/// Response buffer passed to `uds_send_request`: the largest UDS message
+const MAX_RESPONSE_LENGTH: usize = 4095;
+
+/// Serializes `uds_send_request`, which works on the library's connection
+static SEND_LOCK: Mutex<()> = Mutex::new(());
 
 /// Safe wrapper around the raw UDS client pointer
 pub struct UdsClientHandle {
@@ -60,276 +66,43 @@ impl UdsClientHandle {
         }
     }
 
-    /// Read data by identifier
-    pub fn read_data_by_identifier(&self, did: u16) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_read_data_by_identifier(self.client, did);
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "UDS error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
-    }
-
-    /// Write data by identifier
-    pub fn write_data_by_identifier(&self, did: u16, data: &[u8]) -> Result<()> {
-        unsafe {
-            let response = uds_write_data_by_identifier(
-                self.client,
-                did,
-                data.as_ptr(),
-                data.len() as u32,
-            );
-            
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            let error_code = response_ref.error_code;
-            uds_response_free(response);
-
-            if error_code != 0 {
-                return Err(Sovd2UdsError::UdsCommunication(format!(
-                    "Write failed with error code: {}",
-                    error_code
-                )));
-            }
-
-            Ok(())
-        }
-    }
-
-    /// Diagnostic session control
-    pub fn diagnostic_session_control(&self, session_type: u8) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_diagnostic_session_control(self.client, session_type);
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "Session control failed with error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
-    }
-
-    /// ECU reset
-    pub fn ecu_reset(&self, reset_type: u8) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_ecu_reset(self.client, reset_type);
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "ECU reset failed with error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
-    }
-
-    /// Security access
-    pub fn security_access(&self, access_type: u8, key: &[u8]) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_security_access(
-                self.client,
-                access_type,
-                key.as_ptr(),
-                key.len() as u32,
-            );
-            
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "Security access failed with error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
-    }
-
-    /// Read DTC information
-    pub fn read_dtc_information(&self, sub_function: u8) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_read_dtc_information(self.client, sub_function);
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "Read DTC failed with error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
-    }
-
-    /// Clear diagnostic information
-    pub fn clear_diagnostic_information(&self, group: u32) -> Result<()> {
-        unsafe {
-            let response = uds_clear_diagnostic_information(self.client, group);
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            let error_code = response_ref.error_code;
-            uds_response_free(response);
-
-            if error_code != 0 {
-                return Err(Sovd2UdsError::UdsCommunication(format!(
-                    "Clear DTC failed with error code: {}",
-                    error_code
-                )));
-            }
-
-            Ok(())
-        }
-    }
-
-    /// Routine control
-    pub fn routine_control(
-        &self,
-        routine_type: u8,
-        routine_id: u16,
-        params: &[u8],
-    ) -> Result<Vec<u8>> {
-        unsafe {
-            let response = uds_routine_control(
-                self.client,
-                routine_type,
-                routine_id,
-                params.as_ptr(),
-                params.len() as u32,
-            );
-            
-            if response.is_null() {
-                return Err(Sovd2UdsError::UdsCommunication(
-                    "Null response received".to_string(),
-                ));
-            }
-
-            let response_ref = &*response;
-            
-            if response_ref.error_code != 0 {
-                let error = Sovd2UdsError::UdsCommunication(format!(
-                    "Routine control failed with error code: {}",
-                    response_ref.error_code
-                ));
-                uds_response_free(response);
-                return Err(error);
-            }
-
-            let data = if !response_ref.data.is_null() && response_ref.data_length > 0 {
-                std::slice::from_raw_parts(response_ref.data, response_ref.data_length as usize)
-                    .to_vec()
-            } else {
-                vec![]
-            };
-
-            uds_response_free(response);
-            Ok(data)
-        }
+    /// Send a complete request PDU and return the complete response PDU
+    ///
+    /// libudsclient's `uds_send_request` has no client argument and copies
+    /// the response into a buffer owned by the caller; calls are serialized
+    /// across handles. Negative responses are returned as PDUs like any other.
+    pub fn send_request(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let (&service_id, data) = request
+            .split_first()
+            .ok_or_else(|| Sovd2UdsError::InvalidRequest("Empty UDS request".to_string()))?;
+        let mut data = data.to_vec();
+        let mut response = vec![0u8; MAX_RESPONSE_LENGTH];
+        let mut response_len = response.len();
+
+        let _guard = SEND_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let result = unsafe {
+            uds_send_request(
+                service_id,
+                data.as_mut_ptr(),
+                data.len(),
+                response.as_mut_ptr(),
+                &mut response_len,
+            )
+        };
+        if result != 0 {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "Request failed with error code: {}",
+                result
+            )));
+        }
+        if response_len == 0 || response_len > MAX_RESPONSE_LENGTH {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "Invalid response length: {}",
+                response_len
+            )));
+        }
+        response.truncate(response_len);
+        Ok(response)
     }
 
     /// Get raw client pointer (for advanced use cases)
@@ -1,3 +1,5 @@
 pub mod bindings;
+pub mod transport;
 
 pub use bindings::{DoipClientHandle, UdsClientHandle};
+pub use transport::FfiTransport;
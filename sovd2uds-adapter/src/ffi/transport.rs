@@ -0,0 +1,82 @@
+use super::UdsClientHandle;
+use crate::error::{Result, Sovd2UdsError};
+use crate::models::uds::UdsRequest;
+use crate::uds::UdsTransport;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// UDS transport backed by libudsclient
+///
+/// Requests are sent as complete PDUs with `uds_send_request`, so every
+/// service is available. libudsclient calls block until the ECU answers, so
+/// they run on the blocking pool; the response is kept for `receive`.
+pub struct FfiTransport {
+    handle: Arc<UdsClientHandle>,
+    responses: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl FfiTransport {
+    pub fn new(handle: UdsClientHandle) -> Self {
+        Self {
+            handle: Arc::new(handle),
+            responses: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl UdsTransport for FfiTransport {
+    async fn connect(&self) -> Result<()> {
+        let handle = Arc::clone(&self.handle);
+        blocking(move || handle.connect()).await
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        let handle = Arc::clone(&self.handle);
+        blocking(move || handle.disconnect()).await
+    }
+
+    async fn send(&self, request: &[u8]) -> Result<()> {
+        let handle = Arc::clone(&self.handle);
+        let pdu = request.to_vec();
+        // Dropped at the deadline, the call completes on its own and its
+        // response is discarded
+        let response = blocking(move || handle.send_request(&pdu)).await;
+        // libudsclient reports a missing response or a broken link only as a
+        // failed call; it is a link failure, so idempotent reads are retried
+        // after reconnecting
+        let response = response.map_err(|e| match e {
+            Sovd2UdsError::UdsCommunication(message) => {
+                Sovd2UdsError::Io(std::io::Error::other(message))
+            }
+            e => e,
+        })?;
+        // Nobody waits for the answer to a suppressed request
+        if !UdsRequest::from_bytes(request).is_some_and(|r| r.suppress_positive_response()) {
+            self.responses.lock().unwrap().push_back(response);
+        }
+        Ok(())
+    }
+
+    async fn receive(&self, _timeout: Duration) -> Result<Vec<u8>> {
+        // libudsclient calls are synchronous: the response is already there
+        self.responses.lock().unwrap().pop_front().ok_or_else(|| {
+            Sovd2UdsError::UdsCommunication("No pending libudsclient response".to_string())
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "ffi"
+    }
+}
+
+/// Run a libudsclient call on the blocking pool
+async fn blocking<T: Send + 'static>(
+    call: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(call)
+        .await
+        .map_err(|e| Sovd2UdsError::Ffi(format!("libudsclient call failed: {}", e)))?
+}
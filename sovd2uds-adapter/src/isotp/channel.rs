@@ -0,0 +1,562 @@
+use super::frame::*;
+use super::CanFrameIo;
+use crate::config::{IsoTpAddressing, IsoTpConfig};
+use crate::error::{Result, Sovd2UdsError};
+use std::time::Duration;
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{debug, trace};
+
+/// ISO 15765-2 connection between the tester and one ECU
+///
+/// Segments outgoing messages into single/first/consecutive frames and
+/// reassembles incoming ones, honouring block size and STmin in both
+/// directions.
+pub struct IsoTpChannel<T: CanFrameIo> {
+    io: T,
+    tx_id: u32,
+    rx_id: u32,
+    config: IsoTpConfig,
+}
+
+impl<T: CanFrameIo> IsoTpChannel<T> {
+    /// Create a channel sending on `tx_id` and listening on `rx_id`
+    pub fn new(io: T, tx_id: u32, rx_id: u32, config: IsoTpConfig) -> Self {
+        Self {
+            io,
+            tx_id,
+            rx_id,
+            config,
+        }
+    }
+
+    /// CAN identifier used for requests
+    pub fn tx_id(&self) -> u32 {
+        self.tx_id
+    }
+
+    /// CAN identifier used for responses
+    pub fn rx_id(&self) -> u32 {
+        self.rx_id
+    }
+
+    /// Number of bytes taken by the address extension
+    fn address_length(&self) -> usize {
+        match self.config.addressing {
+            IsoTpAddressing::Normal => 0,
+            IsoTpAddressing::Extended => 1,
+        }
+    }
+
+    /// Largest message that fits in a single frame
+    fn single_frame_capacity(&self) -> usize {
+        CAN_FRAME_LENGTH - 1 - self.address_length()
+    }
+
+    /// Send a complete message
+    pub async fn send(&self, payload: &[u8]) -> Result<()> {
+        if payload.is_empty() {
+            return Err(Sovd2UdsError::InvalidRequest(
+                "Cannot send an empty ISO-TP message".to_string(),
+            ));
+        }
+
+        let address_length = self.address_length();
+        if payload.len() <= self.single_frame_capacity() {
+            return self
+                .send_pdu(&IsoTpFrame::Single {
+                    data: payload.to_vec(),
+                })
+                .await;
+        }
+
+        let header_length = if payload.len() <= MAX_SHORT_MESSAGE_LENGTH {
+            2
+        } else {
+            6
+        };
+        let first_length = CAN_FRAME_LENGTH - header_length - address_length;
+        self.send_pdu(&IsoTpFrame::First {
+            total_length: payload.len(),
+            data: payload[..first_length].to_vec(),
+        })
+        .await?;
+        trace!("Sent first frame ({} bytes total)", payload.len());
+
+        let consecutive_capacity = CAN_FRAME_LENGTH - 1 - address_length;
+        let mut chunks = payload[first_length..]
+            .chunks(consecutive_capacity)
+            .peekable();
+        let mut sequence_number: u8 = 1;
+
+        while chunks.peek().is_some() {
+            let (block_size, separation_time) = self.wait_for_flow_control().await?;
+            let mut sent_in_block: u32 = 0;
+
+            while let Some(chunk) = chunks.next() {
+                self.send_pdu(&IsoTpFrame::Consecutive {
+                    sequence_number,
+                    data: chunk.to_vec(),
+                })
+                .await?;
+                sequence_number = (sequence_number + 1) & 0x0F;
+                sent_in_block += 1;
+
+                if chunks.peek().is_none() {
+                    break;
+                }
+                if block_size != 0 && sent_in_block == block_size as u32 {
+                    break;
+                }
+                if !separation_time.is_zero() {
+                    sleep(separation_time).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receive a complete message, waiting at most `wait` for its first frame
+    pub async fn receive(&self, wait: Duration) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + wait;
+
+        loop {
+            match self.next_pdu(deadline, "ISO-TP response").await? {
+                IsoTpFrame::Single { data } => return Ok(data),
+                IsoTpFrame::First { total_length, data } => {
+                    return self.receive_segmented(total_length, data).await;
+                }
+                other => debug!("Ignoring unexpected ISO-TP frame {:?}", other),
+            }
+        }
+    }
+
+    async fn receive_segmented(&self, total_length: usize, first: Vec<u8>) -> Result<Vec<u8>> {
+        if total_length <= self.single_frame_capacity() {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "ISO-TP first frame announces {} bytes, which fit a single frame",
+                total_length
+            )));
+        }
+        if total_length > self.config.max_message_length {
+            self.send_flow_control(FlowStatus::Overflow).await?;
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "ISO-TP message of {} bytes exceeds isotp.max_message_length ({})",
+                total_length, self.config.max_message_length
+            )));
+        }
+
+        let mut message = Vec::with_capacity(total_length);
+        message.extend_from_slice(&first);
+
+        let block_size = self.config.block_size;
+        let mut expected_sequence: u8 = 1;
+        let consecutive_timeout = Duration::from_millis(self.config.timeout_cr as u64);
+
+        while message.len() < total_length {
+            self.send_flow_control(FlowStatus::ContinueToSend).await?;
+            let mut received_in_block: u32 = 0;
+
+            while message.len() < total_length {
+                let deadline = Instant::now() + consecutive_timeout;
+                match self
+                    .next_pdu(deadline, "ISO-TP consecutive frame (N_Cr)")
+                    .await?
+                {
+                    IsoTpFrame::Consecutive {
+                        sequence_number,
+                        data,
+                    } => {
+                        if sequence_number != expected_sequence {
+                            return Err(Sovd2UdsError::UdsCommunication(format!(
+                                "ISO-TP sequence error: expected {}, got {}",
+                                expected_sequence, sequence_number
+                            )));
+                        }
+                        expected_sequence = (expected_sequence + 1) & 0x0F;
+                        message.extend_from_slice(&data);
+                        received_in_block += 1;
+                    }
+                    other => debug!("Ignoring ISO-TP frame {:?} during reception", other),
+                }
+
+                if block_size != 0 && received_in_block == block_size as u32 {
+                    break;
+                }
+            }
+        }
+
+        message.truncate(total_length);
+        Ok(message)
+    }
+
+    /// Wait for a ContinueToSend flow control, returning (block size, STmin)
+    async fn wait_for_flow_control(&self) -> Result<(u8, Duration)> {
+        let flow_control_timeout = Duration::from_millis(self.config.timeout_bs as u64);
+        let mut wait_frames = 0;
+
+        loop {
+            let deadline = Instant::now() + flow_control_timeout;
+            match self
+                .next_pdu(deadline, "ISO-TP flow control (N_Bs)")
+                .await?
+            {
+                IsoTpFrame::FlowControl {
+                    status,
+                    block_size,
+                    st_min,
+                } => match status {
+                    FlowStatus::ContinueToSend => {
+                        return Ok((block_size, st_min_to_duration(st_min)));
+                    }
+                    FlowStatus::Wait => {
+                        wait_frames += 1;
+                        if wait_frames > self.config.max_wait_frames {
+                            return Err(Sovd2UdsError::UdsCommunication(
+                                "ISO-TP receiver sent too many wait frames".to_string(),
+                            ));
+                        }
+                    }
+                    FlowStatus::Overflow => {
+                        return Err(Sovd2UdsError::UdsCommunication(
+                            "ISO-TP receiver buffer overflow".to_string(),
+                        ));
+                    }
+                },
+                other => debug!(
+                    "Ignoring ISO-TP frame {:?} while waiting for flow control",
+                    other
+                ),
+            }
+        }
+    }
+
+    async fn send_flow_control(&self, status: FlowStatus) -> Result<()> {
+        self.send_pdu(&IsoTpFrame::FlowControl {
+            status,
+            block_size: self.config.block_size,
+            st_min: self.config.st_min,
+        })
+        .await
+    }
+
+    /// Add the address extension and padding, then put the frame on the bus
+    async fn send_pdu(&self, pdu: &IsoTpFrame) -> Result<()> {
+        let mut data = Vec::with_capacity(CAN_FRAME_LENGTH);
+        if self.config.addressing == IsoTpAddressing::Extended {
+            data.push(self.config.target_address_extension);
+        }
+        data.extend_from_slice(&pdu.to_bytes());
+        if let Some(padding) = self.config.padding {
+            data.resize(CAN_FRAME_LENGTH, padding);
+        }
+
+        self.io.send_frame(&CanFrame::new(self.tx_id, data)).await
+    }
+
+    /// Next PDU addressed to us, with the address extension stripped
+    async fn next_pdu(&self, deadline: Instant, what: &str) -> Result<IsoTpFrame> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = timeout(remaining, self.io.receive_frame())
+                .await
+                .map_err(|_| Sovd2UdsError::Timeout(what.to_string()))??;
+
+            if frame.id != self.rx_id {
+                continue;
+            }
+
+            let payload = match self.config.addressing {
+                IsoTpAddressing::Normal => &frame.data[..],
+                IsoTpAddressing::Extended => match frame.data.split_first() {
+                    Some((&address, rest)) if address == self.config.source_address_extension => {
+                        rest
+                    }
+                    _ => continue,
+                },
+            };
+
+            match IsoTpFrame::from_bytes(payload) {
+                Ok(pdu) => return Ok(pdu),
+                Err(e) => debug!(
+                    "Dropping malformed ISO-TP frame from 0x{:X}: {}",
+                    frame.id, e
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc;
+
+    const TX_ID: u32 = 0x7E0;
+    const RX_ID: u32 = 0x7E8;
+
+    /// CAN bus recording sent frames and delivering frames queued by the test
+    struct FakeBus {
+        sent: Mutex<Vec<CanFrame>>,
+        incoming: tokio::sync::Mutex<mpsc::UnboundedReceiver<CanFrame>>,
+    }
+
+    impl CanFrameIo for FakeBus {
+        async fn send_frame(&self, frame: &CanFrame) -> Result<()> {
+            self.sent.lock().unwrap().push(frame.clone());
+            Ok(())
+        }
+
+        async fn receive_frame(&self) -> Result<CanFrame> {
+            self.incoming.lock().await.recv().await.ok_or_else(|| {
+                Sovd2UdsError::UdsCommunication("Fake bus closed".to_string())
+            })
+        }
+    }
+
+    /// Unpadded channel with 100 ms timeouts and the sender of its incoming frames
+    fn channel(
+        configure: impl FnOnce(&mut IsoTpConfig),
+    ) -> (IsoTpChannel<FakeBus>, mpsc::UnboundedSender<CanFrame>) {
+        let mut config = IsoTpConfig {
+            padding: None,
+            timeout_bs: 100,
+            timeout_cr: 100,
+            ..IsoTpConfig::default()
+        };
+        configure(&mut config);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let bus = FakeBus {
+            sent: Mutex::new(Vec::new()),
+            incoming: tokio::sync::Mutex::new(receiver),
+        };
+        (IsoTpChannel::new(bus, TX_ID, RX_ID, config), sender)
+    }
+
+    fn flow_control(status: FlowStatus, block_size: u8, st_min: u8) -> CanFrame {
+        let pdu = IsoTpFrame::FlowControl {
+            status,
+            block_size,
+            st_min,
+        };
+        CanFrame::new(RX_ID, pdu.to_bytes())
+    }
+
+    fn sent_pdus(channel: &IsoTpChannel<FakeBus>) -> Vec<IsoTpFrame> {
+        let sent = channel.io.sent.lock().unwrap();
+        sent.iter()
+            .inspect(|frame| assert_eq!(frame.id, TX_ID))
+            .map(|frame| IsoTpFrame::from_bytes(&frame.data).unwrap())
+            .collect()
+    }
+
+    /// Payload bytes 0, 1, 2, ...
+    fn payload(length: usize) -> Vec<u8> {
+        (0..length).map(|i| i as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn short_message_is_a_padded_single_frame() {
+        let (channel, _bus) = channel(|config| config.padding = Some(0xCC));
+        channel.send(&[0x3E, 0x00]).await.unwrap();
+
+        let sent = channel.io.sent.lock().unwrap();
+        assert_eq!(sent[0].data, [0x02, 0x3E, 0x00, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC]);
+    }
+
+    #[tokio::test]
+    async fn long_message_is_segmented_with_wrapping_sequence_numbers() {
+        let (channel, bus) = channel(|_| {});
+        // First frame with 6 bytes, then 18 consecutive frames of up to 7
+        let message = payload(6 + 7 * 17 + 3);
+        bus.send(flow_control(FlowStatus::ContinueToSend, 0, 0))
+            .unwrap();
+        channel.send(&message).await.unwrap();
+
+        let pdus = sent_pdus(&channel);
+        assert_eq!(pdus.len(), 19);
+        let mut reassembled = match &pdus[0] {
+            IsoTpFrame::First { total_length, data } => {
+                assert_eq!(*total_length, message.len());
+                data.clone()
+            }
+            other => panic!("expected a first frame, got {:?}", other),
+        };
+        for (index, pdu) in pdus[1..].iter().enumerate() {
+            match pdu {
+                IsoTpFrame::Consecutive {
+                    sequence_number,
+                    data,
+                } => {
+                    assert_eq!(*sequence_number as usize, (index + 1) % 16);
+                    reassembled.extend_from_slice(data);
+                }
+                other => panic!("expected a consecutive frame, got {:?}", other),
+            }
+        }
+        assert_eq!(reassembled, message);
+    }
+
+    #[tokio::test]
+    async fn sender_waits_for_flow_control_after_each_block() {
+        let (channel, bus) = channel(|_| {});
+        let message = payload(6 + 7 * 5);
+        bus.send(flow_control(FlowStatus::ContinueToSend, 2, 0))
+            .unwrap();
+
+        let sending = tokio::spawn(async move {
+            let result = channel.send(&message).await;
+            (channel, result)
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // First frame and one block of two consecutive frames
+        assert!(!sending.is_finished());
+
+        bus.send(flow_control(FlowStatus::ContinueToSend, 2, 0))
+            .unwrap();
+        bus.send(flow_control(FlowStatus::ContinueToSend, 2, 0))
+            .unwrap();
+        let (channel, result) = sending.await.unwrap();
+        result.unwrap();
+        assert_eq!(sent_pdus(&channel).len(), 6);
+    }
+
+    #[tokio::test]
+    async fn sender_keeps_the_separation_time() {
+        let (channel, bus) = channel(|_| {});
+        bus.send(flow_control(FlowStatus::ContinueToSend, 0, 10))
+            .unwrap();
+
+        let started = Instant::now();
+        channel.send(&payload(6 + 7 * 4)).await.unwrap();
+        // Three gaps between four consecutive frames
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn sender_accepts_wait_frames_up_to_the_limit() {
+        let (channel, bus) = channel(|config| config.max_wait_frames = 2);
+        for status in [FlowStatus::Wait, FlowStatus::Wait, FlowStatus::ContinueToSend] {
+            bus.send(flow_control(status, 0, 0)).unwrap();
+        }
+        channel.send(&payload(20)).await.unwrap();
+
+        for _ in 0..3 {
+            bus.send(flow_control(FlowStatus::Wait, 0, 0)).unwrap();
+        }
+        let error = channel.send(&payload(20)).await.unwrap_err();
+        assert!(error.to_string().contains("wait frames"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn sender_fails_on_overflow_and_missing_flow_control() {
+        let (channel, bus) = channel(|_| {});
+        bus.send(flow_control(FlowStatus::Overflow, 0, 0)).unwrap();
+        let error = channel.send(&payload(20)).await.unwrap_err();
+        assert!(error.to_string().contains("overflow"), "{}", error);
+
+        let error = channel.send(&payload(20)).await.unwrap_err();
+        assert!(matches!(error, Sovd2UdsError::Timeout(_)), "{}", error);
+    }
+
+    /// Frames of a segmented message sent by the ECU
+    fn segmented(message: &[u8]) -> Vec<CanFrame> {
+        let first = IsoTpFrame::First {
+            total_length: message.len(),
+            data: message[..6].to_vec(),
+        };
+        let consecutive = message[6..].chunks(7).enumerate().map(|(index, chunk)| {
+            IsoTpFrame::Consecutive {
+                sequence_number: ((index + 1) % 16) as u8,
+                data: chunk.to_vec(),
+            }
+        });
+        std::iter::once(first)
+            .chain(consecutive)
+            .map(|pdu| CanFrame::new(RX_ID, pdu.to_bytes()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn segmented_message_is_reassembled() {
+        let (channel, bus) = channel(|config| {
+            config.block_size = 4;
+            config.st_min = 5;
+        });
+        let message = payload(6 + 7 * 17 + 3);
+        // Frames for other IDs are ignored
+        bus.send(CanFrame::new(0x123, vec![0x01, 0xFF])).unwrap();
+        for frame in segmented(&message) {
+            bus.send(frame).unwrap();
+        }
+
+        let received = channel.receive(Duration::from_millis(100)).await.unwrap();
+        assert_eq!(received, message);
+
+        // One flow control per block of 4 of the 18 consecutive frames
+        let pdus = sent_pdus(&channel);
+        assert_eq!(pdus.len(), 5);
+        for pdu in pdus {
+            assert_eq!(
+                pdu,
+                IsoTpFrame::FlowControl {
+                    status: FlowStatus::ContinueToSend,
+                    block_size: 4,
+                    st_min: 5,
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn receiver_rejects_sequence_errors() {
+        let (channel, bus) = channel(|_| {});
+        let mut frames = segmented(&payload(30));
+        frames.remove(2);
+        for frame in frames {
+            bus.send(frame).unwrap();
+        }
+
+        let error = channel.receive(Duration::from_millis(100)).await.unwrap_err();
+        assert!(error.to_string().contains("sequence"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn receiver_refuses_messages_beyond_the_limit() {
+        let (channel, bus) = channel(|config| config.max_message_length = 20);
+        for frame in segmented(&payload(30)) {
+            bus.send(frame).unwrap();
+        }
+
+        let error = channel.receive(Duration::from_millis(100)).await.unwrap_err();
+        assert!(error.to_string().contains("max_message_length"), "{}", error);
+        assert_eq!(
+            sent_pdus(&channel),
+            [IsoTpFrame::FlowControl {
+                status: FlowStatus::Overflow,
+                block_size: 0,
+                st_min: 0,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn extended_addressing_adds_and_checks_the_address_byte() {
+        let (channel, bus) = channel(|config| {
+            config.addressing = IsoTpAddressing::Extended;
+            config.target_address_extension = 0x10;
+            config.source_address_extension = 0xF1;
+        });
+        channel.send(&[0x3E, 0x00]).await.unwrap();
+        assert_eq!(channel.io.sent.lock().unwrap()[0].data, [0x10, 0x02, 0x3E, 0x00]);
+
+        // Frames for another address extension are ignored
+        bus.send(CanFrame::new(RX_ID, vec![0x22, 0x02, 0x7E, 0x00]))
+            .unwrap();
+        bus.send(CanFrame::new(RX_ID, vec![0xF1, 0x02, 0x7E, 0x00]))
+            .unwrap();
+        let received = channel.receive(Duration::from_millis(100)).await.unwrap();
+        assert_eq!(received, [0x7E, 0x00]);
+    }
+}
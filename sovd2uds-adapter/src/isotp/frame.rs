@@ -0,0 +1,298 @@
+use crate::error::{Result, Sovd2UdsError};
+use std::time::Duration;
+
+/// Payload length of a classic CAN frame
+pub const CAN_FRAME_LENGTH: usize = 8;
+
+/// Largest message length encodable in a 12-bit first frame
+pub const MAX_SHORT_MESSAGE_LENGTH: usize = 0x0FFF;
+
+/// Largest message length supported (32-bit first frame escape)
+pub const MAX_MESSAGE_LENGTH: usize = u32::MAX as usize;
+
+/// A raw CAN frame as seen on the bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanFrame {
+    /// Arbitration ID (11-bit or 29-bit)
+    pub id: u32,
+    /// Whether `id` is a 29-bit extended identifier
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+impl CanFrame {
+    pub fn new(id: u32, data: Vec<u8>) -> Self {
+        Self {
+            id,
+            extended: id > 0x7FF,
+            data,
+        }
+    }
+}
+
+/// Flow status carried in a flow control frame
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowStatus {
+    ContinueToSend = 0x00,
+    Wait = 0x01,
+    Overflow = 0x02,
+}
+
+impl FlowStatus {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => Some(Self::ContinueToSend),
+            0x01 => Some(Self::Wait),
+            0x02 => Some(Self::Overflow),
+            _ => None,
+        }
+    }
+}
+
+/// ISO 15765-2 protocol data unit (without address extension and padding)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsoTpFrame {
+    Single {
+        data: Vec<u8>,
+    },
+    First {
+        total_length: usize,
+        data: Vec<u8>,
+    },
+    Consecutive {
+        sequence_number: u8,
+        data: Vec<u8>,
+    },
+    FlowControl {
+        status: FlowStatus,
+        block_size: u8,
+        st_min: u8,
+    },
+}
+
+impl IsoTpFrame {
+    /// Encode the PCI and payload bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Single { data } => {
+                let mut bytes = vec![data.len() as u8 & 0x0F];
+                bytes.extend_from_slice(data);
+                bytes
+            }
+            Self::First { total_length, data } => {
+                let mut bytes = if *total_length <= MAX_SHORT_MESSAGE_LENGTH {
+                    vec![
+                        0x10 | ((*total_length >> 8) as u8 & 0x0F),
+                        *total_length as u8,
+                    ]
+                } else {
+                    let mut escape = vec![0x10, 0x00];
+                    escape.extend_from_slice(&(*total_length as u32).to_be_bytes());
+                    escape
+                };
+                bytes.extend_from_slice(data);
+                bytes
+            }
+            Self::Consecutive {
+                sequence_number,
+                data,
+            } => {
+                let mut bytes = vec![0x20 | (sequence_number & 0x0F)];
+                bytes.extend_from_slice(data);
+                bytes
+            }
+            Self::FlowControl {
+                status,
+                block_size,
+                st_min,
+            } => vec![0x30 | *status as u8, *block_size, *st_min],
+        }
+    }
+
+    /// Decode a PCI and payload (address extension already removed)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let pci = *bytes
+            .first()
+            .ok_or_else(|| Sovd2UdsError::UdsCommunication("Empty ISO-TP frame".to_string()))?;
+
+        match pci >> 4 {
+            0x0 => {
+                let length = (pci & 0x0F) as usize;
+                if length == 0 || bytes.len() < 1 + length {
+                    return Err(Sovd2UdsError::UdsCommunication(format!(
+                        "Invalid single frame length {}",
+                        length
+                    )));
+                }
+                Ok(Self::Single {
+                    data: bytes[1..1 + length].to_vec(),
+                })
+            }
+            0x1 => {
+                if bytes.len() < 2 {
+                    return Err(Sovd2UdsError::UdsCommunication(
+                        "First frame too short".to_string(),
+                    ));
+                }
+                let short_length = (((pci & 0x0F) as usize) << 8) | bytes[1] as usize;
+                if short_length != 0 {
+                    return Ok(Self::First {
+                        total_length: short_length,
+                        data: bytes[2..].to_vec(),
+                    });
+                }
+                if bytes.len() < 6 {
+                    return Err(Sovd2UdsError::UdsCommunication(
+                        "First frame escape sequence too short".to_string(),
+                    ));
+                }
+                let total_length = u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+                Ok(Self::First {
+                    total_length: total_length as usize,
+                    data: bytes[6..].to_vec(),
+                })
+            }
+            0x2 => Ok(Self::Consecutive {
+                sequence_number: pci & 0x0F,
+                data: bytes[1..].to_vec(),
+            }),
+            0x3 => {
+                if bytes.len() < 3 {
+                    return Err(Sovd2UdsError::UdsCommunication(
+                        "Flow control frame too short".to_string(),
+                    ));
+                }
+                let status = FlowStatus::from_u8(pci & 0x0F).ok_or_else(|| {
+                    Sovd2UdsError::UdsCommunication(format!(
+                        "Invalid flow status 0x{:X}",
+                        pci & 0x0F
+                    ))
+                })?;
+                Ok(Self::FlowControl {
+                    status,
+                    block_size: bytes[1],
+                    st_min: bytes[2],
+                })
+            }
+            _ => Err(Sovd2UdsError::UdsCommunication(format!(
+                "Unknown ISO-TP frame type 0x{:02X}",
+                pci
+            ))),
+        }
+    }
+}
+
+/// Convert an STmin byte to a separation time
+///
+/// 0x00-0x7F are milliseconds, 0xF1-0xF9 are 100-900 microseconds;
+/// reserved values are treated as the maximum (127 ms).
+pub fn st_min_to_duration(st_min: u8) -> Duration {
+    match st_min {
+        0x00..=0x7F => Duration::from_millis(st_min as u64),
+        0xF1..=0xF9 => Duration::from_micros((st_min - 0xF0) as u64 * 100),
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let cases = [
+            (
+                IsoTpFrame::Single {
+                    data: vec![0x22, 0xF1, 0x90],
+                },
+                vec![0x03, 0x22, 0xF1, 0x90],
+            ),
+            (
+                IsoTpFrame::First {
+                    total_length: 20,
+                    data: vec![0x62, 0xF1, 0x90, 0x57, 0x56, 0x57],
+                },
+                vec![0x10, 0x14, 0x62, 0xF1, 0x90, 0x57, 0x56, 0x57],
+            ),
+            // 12-bit length: the high nibble goes into the PCI byte
+            (
+                IsoTpFrame::First {
+                    total_length: 0x0FFF,
+                    data: vec![0xAA; 6],
+                },
+                [&[0x1F, 0xFF][..], &[0xAA; 6]].concat(),
+            ),
+            // Longer messages use the escape sequence with a 32-bit length
+            (
+                IsoTpFrame::First {
+                    total_length: 0x1000,
+                    data: vec![0xAA, 0xBB],
+                },
+                vec![0x10, 0x00, 0x00, 0x00, 0x10, 0x00, 0xAA, 0xBB],
+            ),
+            (
+                IsoTpFrame::Consecutive {
+                    sequence_number: 0x0F,
+                    data: vec![1, 2, 3],
+                },
+                vec![0x2F, 1, 2, 3],
+            ),
+            (
+                IsoTpFrame::FlowControl {
+                    status: FlowStatus::Wait,
+                    block_size: 8,
+                    st_min: 0xF3,
+                },
+                vec![0x31, 0x08, 0xF3],
+            ),
+        ];
+
+        for (frame, bytes) in cases {
+            assert_eq!(frame.to_bytes(), bytes, "{:?}", frame);
+            assert_eq!(IsoTpFrame::from_bytes(&bytes).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn single_frame_ignores_padding() {
+        let frame = IsoTpFrame::from_bytes(&[0x02, 0x50, 0x03, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC]);
+        assert_eq!(
+            frame.unwrap(),
+            IsoTpFrame::Single {
+                data: vec![0x50, 0x03]
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        let cases: [&[u8]; 8] = [
+            &[],
+            // Single frame of length 0 or longer than the frame
+            &[0x00, 0x11],
+            &[0x05, 0x01, 0x02],
+            &[0x10],
+            // Escape sequence cut short
+            &[0x10, 0x00, 0x00, 0x01],
+            &[0x30, 0x00],
+            // Reserved flow status and frame type
+            &[0x33, 0x00, 0x00],
+            &[0x40, 0x00],
+        ];
+        for bytes in cases {
+            assert!(IsoTpFrame::from_bytes(bytes).is_err(), "{:02X?}", bytes);
+        }
+    }
+
+    #[test]
+    fn st_min_values() {
+        assert_eq!(st_min_to_duration(0x00), Duration::ZERO);
+        assert_eq!(st_min_to_duration(0x7F), Duration::from_millis(127));
+        assert_eq!(st_min_to_duration(0xF1), Duration::from_micros(100));
+        assert_eq!(st_min_to_duration(0xF9), Duration::from_micros(900));
+        // Reserved values
+        assert_eq!(st_min_to_duration(0x80), Duration::from_millis(127));
+        assert_eq!(st_min_to_duration(0xFA), Duration::from_millis(127));
+    }
+}
@@ -0,0 +1,25 @@
+pub mod channel;
+pub mod frame;
+#[cfg(target_os = "linux")]
+pub mod socket;
+#[cfg(target_os = "linux")]
+pub mod transport;
+
+pub use channel::IsoTpChannel;
+pub use frame::{CanFrame, FlowStatus, IsoTpFrame};
+#[cfg(target_os = "linux")]
+pub use socket::CanSocket;
+#[cfg(target_os = "linux")]
+pub use transport::IsoTpTransport;
+
+use crate::error::Result;
+use std::future::Future;
+
+/// Access to a CAN bus, one frame at a time
+pub trait CanFrameIo: Send + Sync {
+    /// Put a frame on the bus
+    fn send_frame(&self, frame: &CanFrame) -> impl Future<Output = Result<()>> + Send;
+
+    /// Wait for the next frame from the bus
+    fn receive_frame(&self) -> impl Future<Output = Result<CanFrame>> + Send;
+}
@@ -0,0 +1,184 @@
+use super::frame::{CanFrame, CAN_FRAME_LENGTH};
+use super::CanFrameIo;
+use crate::error::{Result, Sovd2UdsError};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use tokio::io::unix::AsyncFd;
+
+/// Non-blocking raw CAN socket (Linux SocketCAN)
+pub struct CanSocket {
+    fd: AsyncFd<OwnedFd>,
+    interface: String,
+}
+
+impl CanSocket {
+    /// Open a raw CAN socket bound to `interface` (e.g. "can0" or "vcan0")
+    pub fn open(interface: &str) -> Result<Self> {
+        let name = CString::new(interface)
+            .map_err(|e| Sovd2UdsError::Config(format!("Invalid CAN interface name: {}", e)))?;
+
+        unsafe {
+            let index = libc::if_nametoindex(name.as_ptr());
+            if index == 0 {
+                return Err(Sovd2UdsError::UdsCommunication(format!(
+                    "CAN interface '{}' not found: {}",
+                    interface,
+                    io::Error::last_os_error()
+                )));
+            }
+
+            let raw = libc::socket(
+                libc::PF_CAN,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::CAN_RAW,
+            );
+            if raw < 0 {
+                return Err(Sovd2UdsError::Io(io::Error::last_os_error()));
+            }
+            let fd = OwnedFd::from_raw_fd(raw);
+
+            let mut address: libc::sockaddr_can = mem::zeroed();
+            address.can_family = libc::AF_CAN as libc::sa_family_t;
+            address.can_ifindex = index as libc::c_int;
+
+            let result = libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_can as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+            );
+            if result < 0 {
+                return Err(Sovd2UdsError::Io(io::Error::last_os_error()));
+            }
+
+            Ok(Self {
+                fd: AsyncFd::new(fd)?,
+                interface: interface.to_string(),
+            })
+        }
+    }
+
+    /// Only deliver frames with one of the given identifiers
+    pub fn set_filter(&self, ids: &[(u32, bool)]) -> Result<()> {
+        let filters: Vec<libc::can_filter> = ids
+            .iter()
+            .map(|&(id, extended)| {
+                let mut filter: libc::can_filter = unsafe { mem::zeroed() };
+                if extended {
+                    filter.can_id = id | libc::CAN_EFF_FLAG;
+                    filter.can_mask = libc::CAN_EFF_MASK | libc::CAN_EFF_FLAG;
+                } else {
+                    filter.can_id = id;
+                    filter.can_mask = libc::CAN_SFF_MASK | libc::CAN_EFF_FLAG;
+                }
+                filter
+            })
+            .collect();
+
+        let result = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::SOL_CAN_RAW,
+                libc::CAN_RAW_FILTER,
+                filters.as_ptr() as *const libc::c_void,
+                (filters.len() * mem::size_of::<libc::can_filter>()) as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(Sovd2UdsError::Io(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Interface name the socket is bound to
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    fn write_frame(fd: &OwnedFd, frame: &libc::can_frame) -> io::Result<()> {
+        let written = unsafe {
+            libc::write(
+                fd.as_raw_fd(),
+                frame as *const libc::can_frame as *const libc::c_void,
+                mem::size_of::<libc::can_frame>(),
+            )
+        };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn read_frame(fd: &OwnedFd) -> io::Result<libc::can_frame> {
+        let mut frame: libc::can_frame = unsafe { mem::zeroed() };
+        let read = unsafe {
+            libc::read(
+                fd.as_raw_fd(),
+                &mut frame as *mut libc::can_frame as *mut libc::c_void,
+                mem::size_of::<libc::can_frame>(),
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(frame)
+    }
+}
+
+impl CanFrameIo for CanSocket {
+    async fn send_frame(&self, frame: &CanFrame) -> Result<()> {
+        if frame.data.len() > CAN_FRAME_LENGTH {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "CAN frame too long: {} bytes",
+                frame.data.len()
+            )));
+        }
+
+        let mut raw: libc::can_frame = unsafe { mem::zeroed() };
+        raw.can_id = if frame.extended {
+            frame.id | libc::CAN_EFF_FLAG
+        } else {
+            frame.id
+        };
+        raw.can_dlc = frame.data.len() as u8;
+        raw.data[..frame.data.len()].copy_from_slice(&frame.data);
+
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match guard.try_io(|fd| Self::write_frame(fd.get_ref(), &raw)) {
+                Ok(result) => return Ok(result?),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn receive_frame(&self) -> Result<CanFrame> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let raw = match guard.try_io(|fd| Self::read_frame(fd.get_ref())) {
+                Ok(result) => result?,
+                Err(_would_block) => continue,
+            };
+
+            // Skip error and remote frames
+            if raw.can_id & (libc::CAN_ERR_FLAG | libc::CAN_RTR_FLAG) != 0 {
+                continue;
+            }
+
+            let extended = raw.can_id & libc::CAN_EFF_FLAG != 0;
+            let id = if extended {
+                raw.can_id & libc::CAN_EFF_MASK
+            } else {
+                raw.can_id & libc::CAN_SFF_MASK
+            };
+            let length = (raw.can_dlc as usize).min(CAN_FRAME_LENGTH);
+
+            return Ok(CanFrame {
+                id,
+                extended,
+                data: raw.data[..length].to_vec(),
+            });
+        }
+    }
+}
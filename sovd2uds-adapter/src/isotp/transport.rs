@@ -0,0 +1,123 @@
+use super::{CanSocket, IsoTpChannel};
+use crate::config::IsoTpConfig;
+use crate::error::{Result, Sovd2UdsError};
+use crate::uds::UdsTransport;
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// UDS transport using ISO-TP on a SocketCAN interface
+pub struct IsoTpTransport {
+    interface: String,
+    tx_id: u32,
+    rx_id: u32,
+    config: IsoTpConfig,
+    channel: RwLock<Option<IsoTpChannel<CanSocket>>>,
+}
+
+impl IsoTpTransport {
+    /// Create a transport for the ECU answering on `rx_id` to requests on `tx_id`
+    pub fn new(interface: &str, tx_id: u32, rx_id: u32, config: IsoTpConfig) -> Self {
+        Self {
+            interface: interface.to_string(),
+            tx_id,
+            rx_id,
+            config,
+            channel: RwLock::new(None),
+        }
+    }
+
+    fn not_connected(&self) -> Sovd2UdsError {
+        Sovd2UdsError::UdsCommunication(format!(
+            "ISO-TP channel 0x{:X} on {} is not open",
+            self.tx_id, self.interface
+        ))
+    }
+}
+
+#[async_trait]
+impl UdsTransport for IsoTpTransport {
+    async fn connect(&self) -> Result<()> {
+        let mut channel = self.channel.write().await;
+        if channel.is_none() {
+            let socket = CanSocket::open(&self.interface)?;
+            socket.set_filter(&[(self.rx_id, self.rx_id > 0x7FF)])?;
+            *channel = Some(IsoTpChannel::new(
+                socket,
+                self.tx_id,
+                self.rx_id,
+                self.config.clone(),
+            ));
+            info!(
+                "Opened ISO-TP channel on {} (tx 0x{:X}, rx 0x{:X})",
+                self.interface, self.tx_id, self.rx_id
+            );
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.channel.write().await.take();
+        Ok(())
+    }
+
+    async fn send(&self, request: &[u8]) -> Result<()> {
+        let channel = self.channel.read().await;
+        channel
+            .as_ref()
+            .ok_or_else(|| self.not_connected())?
+            .send(request)
+            .await
+    }
+
+    async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+        let channel = self.channel.read().await;
+        channel
+            .as_ref()
+            .ok_or_else(|| self.not_connected())?
+            .receive(timeout)
+            .await
+    }
+
+    fn name(&self) -> &'static str {
+        "isotp"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transport(interface: &str) -> IsoTpTransport {
+        IsoTpTransport::new(interface, 0x7E0, 0x7E8, IsoTpConfig::default())
+    }
+
+    #[tokio::test]
+    async fn channel_must_be_open_to_exchange_pdus() {
+        let transport = transport("can0");
+        let error = transport.send(&[0x3E, 0x00]).await.unwrap_err();
+        assert!(matches!(
+            &error,
+            Sovd2UdsError::UdsCommunication(message) if message == "ISO-TP channel 0x7E0 on can0 is not open"
+        ));
+        let error = transport
+            .receive(Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Sovd2UdsError::UdsCommunication(_)));
+        transport.disconnect().await.unwrap();
+        assert_eq!(transport.name(), "isotp");
+    }
+
+    #[tokio::test]
+    async fn missing_interfaces_fail_to_connect() {
+        let error = transport("sovdcan9").connect().await.unwrap_err();
+        assert!(matches!(
+            &error,
+            Sovd2UdsError::UdsCommunication(message) if message.starts_with("CAN interface 'sovdcan9' not found")
+        ));
+        let error = transport("can\0").connect().await.unwrap_err();
+        assert!(matches!(error, Sovd2UdsError::Config(_)));
+    }
+}
@@ -0,0 +1,15 @@
+//! SOVD to UDS protocol adapter
+
+pub mod config;
+pub mod doip;
+pub mod error;
+#[cfg(not(feature = "mock-uds"))]
+pub mod ffi;
+pub mod isotp;
+pub mod models;
+pub mod odx;
+pub mod security;
+pub mod server;
+pub mod simulator;
+pub mod translation;
+pub mod uds;
@@ -1,19 +1,11 @@
-mod config;
-mod error;
-mod ffi;
-mod models;
-mod server;
-mod translation;
-mod uds;
-
-use config::Config;
-use server::{create_router, AppState};
+use sovd2uds_adapter::config::Config;
+use sovd2uds_adapter::server::{create_router, AppState};
+use sovd2uds_adapter::translation::SovdUdsTranslator;
+use sovd2uds_adapter::uds::UdsClientPool;
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 use tracing::{info, Level};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
-use translation::SovdUdsTranslator;
-use uds::UdsClientPool;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -31,7 +23,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create shared state
     let config = Arc::new(config);
-    let translator = Arc::new(SovdUdsTranslator::new());
+    let translator = Arc::new(SovdUdsTranslator::from_config(&config)?);
     let client_pool = Arc::new(UdsClientPool::new(Arc::clone(&config)));
 
     let state = AppState {
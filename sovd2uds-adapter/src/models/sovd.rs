@@ -27,12 +27,37 @@ pub struct DataItem {
     pub data_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Diagnostic session the item is read in ("extended", ...); default if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// Length of the value in bytes, when fixed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// "read", "write" or "read_write"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<String>,
 }
 
 /// SOVD Data Items response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DataItemsResponse {
     pub items: Vec<DataItem>,
+    /// Values of the items that could be read (`include_values=true`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<DataItemValue>>,
+    /// Items that could not be read (`include_values=true`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<DataItemError>>,
+}
+
+/// A data item that could not be read
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataItemError {
+    pub id: String,
+    #[serde(flatten)]
+    pub error: ErrorResponse,
 }
 
 /// SOVD Data Item Value
@@ -42,12 +67,21 @@ pub struct DataItemValue {
     pub name: String,
     pub category: String,
     pub data: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub quality: Option<String>,
 }
 
+/// SOVD Data Item write request
+#[derive(Debug, Deserialize)]
+pub struct DataItemWriteRequest {
+    /// New value, typed like the item's `data_type`
+    pub data: serde_json::Value,
+}
+
 /// SOVD Actuator Control Request
 #[derive(Debug, Deserialize)]
 pub struct ActuatorControlRequest {
@@ -74,11 +108,28 @@ pub struct ActuatorControlResponse {
 }
 
 /// SOVD DTC Management Request
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct DtcManagementRequest {
-    pub action: String, // "clear", "read", "freeze_frame"
+    pub action: String, // "clear", "read", "count", "freeze_frame", ...
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dtcs: Option<Vec<String>>,
+    /// Status mask of "read", "count" and "wwh_obd" (default 0xFF)
+    #[serde(default)]
+    pub status_mask: Option<u8>,
+    /// Status filter of "read" by bit names ("confirmed,pending"), instead
+    /// of `status_mask`
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Snapshot or extended data record of "freeze_frame" and
+    /// "extended_data" (default 0xFF, all records)
+    #[serde(default)]
+    pub record_number: Option<u8>,
+    /// WWH-OBD functional group of "wwh_obd" and "permanent" (default 0x33)
+    #[serde(default)]
+    pub functional_group: Option<u8>,
+    /// Severity mask of "wwh_obd" (default 0xFF)
+    #[serde(default)]
+    pub severity_mask: Option<u8>,
 }
 
 /// SOVD DTC Management Response
@@ -115,11 +166,69 @@ pub struct ServiceResponse {
     pub timestamp: Option<DateTime<Utc>>,
 }
 
-/// SOVD Error Response
+/// Diagnostic session state of a component
 #[derive(Debug, Serialize)]
+pub struct SessionStateResponse {
+    pub component_id: String,
+    /// Session name ("default", "programming", "extended", "safety")
+    pub session: String,
+    /// Session identifier (e.g. "0x03")
+    pub session_id: String,
+    /// Unlocked security level, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_level: Option<u8>,
+    pub session_since: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_since: Option<DateTime<Utc>>,
+    pub last_activity: DateTime<Utc>,
+    /// Whether TesterPresent keeps the session alive
+    pub keep_alive: bool,
+}
+
+/// Request to unlock a security level
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityAccessRequest {
+    /// Security level (requestSeed sub-function = level * 2 - 1)
+    pub level: u8,
+}
+
+/// Security access state of a component
+#[derive(Debug, Serialize)]
+pub struct SecurityAccessResponse {
+    pub component_id: String,
+    /// Whether a security level is unlocked
+    pub unlocked: bool,
+    /// Unlocked security level, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unlocked_since: Option<DateTime<Utc>>,
+    /// Keys that may still be tried before the ECU locks out
+    pub remaining_attempts: u8,
+    /// Milliseconds until the ECU accepts a new seed request after a lockout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+/// SOVD Error Response
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
     pub code: u16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+    /// Negative response code returned by the ECU (e.g. "0x33")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nrc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nrc_description: Option<String>,
+    /// Service the ECU rejected (e.g. "0x2E")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Security access keys left before the ECU locks out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_attempts: Option<u8>,
+    /// Milliseconds until security access may be requested again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
 }
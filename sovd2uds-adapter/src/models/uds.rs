@@ -45,6 +45,39 @@ pub enum DiagnosticSessionType {
     SafetySystemDiagnosticSession = 0x04,
 }
 
+impl DiagnosticSessionType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x01 => Some(Self::DefaultSession),
+            0x02 => Some(Self::ProgrammingSession),
+            0x03 => Some(Self::ExtendedDiagnosticSession),
+            0x04 => Some(Self::SafetySystemDiagnosticSession),
+            _ => None,
+        }
+    }
+
+    /// Name used in the API ("default", "programming", "extended", "safety")
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DefaultSession => "default",
+            Self::ProgrammingSession => "programming",
+            Self::ExtendedDiagnosticSession => "extended",
+            Self::SafetySystemDiagnosticSession => "safety",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::DefaultSession,
+            Self::ProgrammingSession,
+            Self::ExtendedDiagnosticSession,
+            Self::SafetySystemDiagnosticSession,
+        ]
+        .into_iter()
+        .find(|session| session.name() == name)
+    }
+}
+
 /// UDS ECU Reset Types
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -65,6 +98,188 @@ pub enum RoutineControlType {
     RequestRoutineResults = 0x03,
 }
 
+/// UDS ReadDTCInformation report types (sub-functions)
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcReportType {
+    /// Number of DTCs matching a status mask
+    NumberOfDtcByStatusMask = 0x01,
+    /// DTCs matching a status mask
+    DtcByStatusMask = 0x02,
+    /// DTCs and numbers of their snapshot records
+    DtcSnapshotIdentification = 0x03,
+    /// Snapshot records (freeze frames) of a DTC
+    DtcSnapshotRecordByDtcNumber = 0x04,
+    /// Extended data records of a DTC
+    DtcExtDataRecordByDtcNumber = 0x06,
+    /// All DTCs the ECU supports
+    SupportedDtc = 0x0A,
+    /// Fault detection counters of prefailed DTCs
+    DtcFaultDetectionCounter = 0x14,
+    /// WWH-OBD DTCs matching a status and severity mask
+    WwhObdDtcByMaskRecord = 0x42,
+    /// WWH-OBD DTCs with permanent status
+    WwhObdDtcWithPermanentStatus = 0x55,
+}
+
+/// DTC format identifiers of ReadDTCInformation responses
+pub mod dtc_formats {
+    pub const SAE_J2012_DA_00: u8 = 0x00;
+    pub const ISO_14229_1: u8 = 0x01;
+    pub const SAE_J1939_73: u8 = 0x02;
+    pub const ISO_11992_4: u8 = 0x03;
+    pub const SAE_J2012_DA_04: u8 = 0x04;
+
+    /// Name of a DTC format identifier
+    pub fn name(format: u8) -> String {
+        match format {
+            SAE_J2012_DA_00 => "SAE_J2012-DA_DTCFormat_00".to_string(),
+            ISO_14229_1 => "ISO_14229-1_DTCFormat".to_string(),
+            SAE_J1939_73 => "SAE_J1939-73_DTCFormat".to_string(),
+            ISO_11992_4 => "ISO_11992-4_DTCFormat".to_string(),
+            SAE_J2012_DA_04 => "SAE_J2012-DA_DTCFormat_04".to_string(),
+            _ => format!("0x{:02X}", format),
+        }
+    }
+}
+
+/// WWH-OBD functional group of emissions-related systems
+pub const FUNCTIONAL_GROUP_EMISSIONS: u8 = 0x33;
+
+/// UDS ControlDTCSetting Types
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcSettingType {
+    On = 0x01,
+    Off = 0x02,
+}
+
+/// UDS CommunicationControl Types
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommunicationControlType {
+    EnableRxAndTx = 0x00,
+    EnableRxAndDisableTx = 0x01,
+    DisableRxAndEnableTx = 0x02,
+    DisableRxAndTx = 0x03,
+}
+
+/// UDS CommunicationControl communication types
+pub mod communication_types {
+    pub const NORMAL: u8 = 0x01;
+    pub const NETWORK_MANAGEMENT: u8 = 0x02;
+    pub const NORMAL_AND_NETWORK_MANAGEMENT: u8 = 0x03;
+}
+
+/// SecurityAccess levels; level n is unlocked with RequestSeed 2n - 1 and SendKey 2n
+pub mod security_levels {
+    /// Highest level, whose SendKey sub-function 0x7E is the last one ISO 14229-1 allows
+    pub const MAX: u8 = 0x3F;
+
+    pub fn is_valid(level: u8) -> bool {
+        (1..=MAX).contains(&level)
+    }
+
+    /// RequestSeed and SendKey sub-functions of a level, if it is valid
+    pub fn sub_functions(level: u8) -> Option<(u8, u8)> {
+        is_valid(level).then(|| (level * 2 - 1, level * 2))
+    }
+}
+
+/// A block of ECU memory addressed by ReadMemoryByAddress, WriteMemoryByAddress
+/// and DynamicallyDefineDataIdentifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub address: u64,
+    pub size: u64,
+}
+
+impl MemoryRegion {
+    pub fn new(address: u64, size: u64) -> Self {
+        Self { address, size }
+    }
+
+    /// Smallest (address, size) byte lengths able to hold this region
+    pub fn format(&self) -> (usize, usize) {
+        (byte_length(self.address), byte_length(self.size))
+    }
+
+    /// Encode addressAndLengthFormatIdentifier, memoryAddress and memorySize
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (address_length, size_length) = self.format();
+        let mut bytes = vec![((size_length as u8) << 4) | address_length as u8];
+        bytes.extend_from_slice(&self.encode(address_length, size_length));
+        bytes
+    }
+
+    /// Encode memoryAddress and memorySize with the given byte lengths
+    pub fn encode(&self, address_length: usize, size_length: usize) -> Vec<u8> {
+        let mut bytes = self.address.to_be_bytes()[8 - address_length..].to_vec();
+        bytes.extend_from_slice(&self.size.to_be_bytes()[8 - size_length..]);
+        bytes
+    }
+}
+
+fn byte_length(value: u64) -> usize {
+    ((64 - value.leading_zeros() as usize).div_ceil(8)).max(1)
+}
+
+/// Source of a dynamically defined DID: `size` bytes of `did` from 1-based `position`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DidSource {
+    pub did: u16,
+    pub position: u8,
+    pub size: u8,
+}
+
+/// UDS DynamicallyDefineDataIdentifier request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicDidDefinition {
+    ByIdentifier { did: u16, sources: Vec<DidSource> },
+    ByMemoryAddress { did: u16, regions: Vec<MemoryRegion> },
+    /// Clear one dynamically defined DID, or all of them
+    Clear { did: Option<u16> },
+}
+
+impl DynamicDidDefinition {
+    /// Sub-function and parameters of the request
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::ByIdentifier { did, sources } => {
+                let mut bytes = vec![0x01];
+                bytes.extend_from_slice(&did.to_be_bytes());
+                for source in sources {
+                    bytes.extend_from_slice(&source.did.to_be_bytes());
+                    bytes.push(source.position);
+                    bytes.push(source.size);
+                }
+                bytes
+            }
+            Self::ByMemoryAddress { did, regions } => {
+                let (address_length, size_length) =
+                    regions.iter().fold((1, 1), |(a, s), region| {
+                        let (ra, rs) = region.format();
+                        (a.max(ra), s.max(rs))
+                    });
+                let mut bytes = vec![0x02];
+                bytes.extend_from_slice(&did.to_be_bytes());
+                bytes.push(((size_length as u8) << 4) | address_length as u8);
+                for region in regions {
+                    bytes.extend_from_slice(&region.encode(address_length, size_length));
+                }
+                bytes
+            }
+            Self::Clear { did } => {
+                let mut bytes = vec![0x03];
+                if let Some(did) = did {
+                    bytes.extend_from_slice(&did.to_be_bytes());
+                }
+                bytes
+            }
+        }
+    }
+}
+
 /// UDS DTC Status Mask
 #[derive(Debug, Clone, Copy)]
 pub struct DtcStatusMask(pub u8);
@@ -78,6 +293,32 @@ impl DtcStatusMask {
     pub const TEST_FAILED_SINCE_LAST_CLEAR: u8 = 0x20;
     pub const TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE: u8 = 0x40;
     pub const WARNING_INDICATOR_REQUESTED: u8 = 0x80;
+
+    /// Status bits and their ISO 14229-1 names, lowest bit first
+    pub const BITS: [(u8, &'static str); 8] = [
+        (Self::TEST_FAILED, "testFailed"),
+        (
+            Self::TEST_FAILED_THIS_OPERATION_CYCLE,
+            "testFailedThisOperationCycle",
+        ),
+        (Self::PENDING_DTC, "pendingDTC"),
+        (Self::CONFIRMED_DTC, "confirmedDTC"),
+        (
+            Self::TEST_NOT_COMPLETED_SINCE_LAST_CLEAR,
+            "testNotCompletedSinceLastClear",
+        ),
+        (Self::TEST_FAILED_SINCE_LAST_CLEAR, "testFailedSinceLastClear"),
+        (
+            Self::TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE,
+            "testNotCompletedThisOperationCycle",
+        ),
+        (Self::WARNING_INDICATOR_REQUESTED, "warningIndicatorRequested"),
+    ];
+
+    /// Whether any of the `bits` is set
+    pub fn is_set(self, bits: u8) -> bool {
+        self.0 & bits != 0
+    }
 }
 
 /// UDS Request structure
@@ -95,11 +336,38 @@ impl UdsRequest {
         }
     }
 
+    /// Build a request from raw bytes (SID + parameters)
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&service_id, data) = bytes.split_first()?;
+        Some(Self {
+            service_id,
+            data: data.to_vec(),
+        })
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = vec![self.service_id];
         bytes.extend_from_slice(&self.data);
         bytes
     }
+
+    /// Whether the first parameter byte is a sub-function
+    pub fn has_sub_function(&self) -> bool {
+        matches!(
+            self.service_id,
+            0x10 | 0x11 | 0x19 | 0x27 | 0x28 | 0x2C | 0x31 | 0x3E | 0x83 | 0x85 | 0x86 | 0x87
+        )
+    }
+
+    /// Whether the request only reads from the ECU and is safe to repeat
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self.service_id, 0x19 | 0x22 | 0x23 | 0x24)
+    }
+
+    /// Whether the suppressPosRspMsgIndicationBit is set
+    pub fn suppress_positive_response(&self) -> bool {
+        self.has_sub_function() && self.data.first().is_some_and(|b| b & 0x80 != 0)
+    }
 }
 
 /// UDS Response structure
@@ -0,0 +1,232 @@
+//! Coding of values described by DATA-OBJECT-PROPs and STRUCTUREs
+
+use super::{child, children, descendant, short_name, text, xsi_type, Odx};
+use crate::translation::{ByteOrder, Codec, Conversion, DataType, FieldDefinition, TextTableEntry};
+use roxmltree::Node;
+
+/// Nesting depth of STRUCTUREs that is followed
+const MAX_DEPTH: usize = 8;
+
+/// Codec of the VALUE parameters of a request or response, starting at byte
+/// `start`; a single value is returned as is, several as a struct
+pub(super) fn message_codec(odx: &Odx, message: Node, start: usize) -> Option<Codec> {
+    let params = params(message)
+        .filter(|param| byte_position(*param).is_none_or(|position| position >= start));
+    let mut codec = structure(odx, params, start, 0);
+    match codec.fields.as_slice() {
+        [] if codec.data_type == DataType::Struct => None,
+        [field] if field.byte_position == Some(0) && field.codec.bit_length.is_none() => {
+            codec.fields.pop().map(|field| field.codec)
+        }
+        _ => Some(codec),
+    }
+}
+
+/// Parameters of a request, response or STRUCTURE
+pub(super) fn params<'a, 'input: 'a>(
+    message: Node<'a, 'input>,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    child(message, "PARAMS")
+        .into_iter()
+        .flat_map(|params| children(params, "PARAM"))
+}
+
+/// Length in bytes of a parameter that is not a VALUE, when fixed and byte
+/// aligned
+pub(super) fn param_length(odx: &Odx, param: Node) -> Option<usize> {
+    let coded_type = child(param, "DIAG-CODED-TYPE").or_else(|| {
+        odx.resolve_child(param, "DOP-REF")
+            .and_then(|dop| child(dop, "DIAG-CODED-TYPE"))
+    });
+    let bits: usize = match (coded_type, xsi_type(param)) {
+        (Some(coded_type), _) => text(coded_type, "BIT-LENGTH")?.parse().ok()?,
+        (None, Some("RESERVED")) => text(param, "BIT-LENGTH")?.parse().ok()?,
+        (None, Some("MATCHING-REQUEST-PARAM")) => {
+            return text(param, "BYTE-LENGTH")?.parse().ok();
+        }
+        _ => return None,
+    };
+    bits.is_multiple_of(8).then_some(bits / 8)
+}
+
+pub(super) fn byte_position(param: Node) -> Option<usize> {
+    text(param, "BYTE-POSITION").and_then(|position| position.parse().ok())
+}
+
+/// Codec of a DOP; DOPs that cannot be described (END-OF-PDU-FIELDs, MUXes,
+/// ...) are kept as bytes
+fn codec(odx: &Odx, dop: Node, depth: usize) -> Codec {
+    match dop.tag_name().name() {
+        "DATA-OBJECT-PROP" => data_object_prop(odx, dop),
+        "STRUCTURE" if depth < MAX_DEPTH => structure(odx, params(dop), 0, depth + 1),
+        _ => Codec::new(DataType::Hex),
+    }
+}
+
+/// Struct of the VALUE parameters; positions are relative to `start`
+fn structure<'a, 'input: 'a>(
+    odx: &Odx<'a, 'input>,
+    params: impl Iterator<Item = Node<'a, 'input>>,
+    start: usize,
+    depth: usize,
+) -> Codec {
+    let mut structure = Codec::new(DataType::Struct);
+    // End of the previous parameter, while known
+    let mut end = Some(start);
+    for param in params {
+        let Some(position) = byte_position(param).or(end) else {
+            return Codec::new(DataType::Hex);
+        };
+
+        if xsi_type(param) != Some("VALUE") {
+            end = param_length(odx, param).map(|length| position + length);
+            continue;
+        }
+        let Some(dop) = odx.resolve_child(param, "DOP-REF") else {
+            return Codec::new(DataType::Hex);
+        };
+        let mut field = codec(odx, dop, depth);
+        if let Some(bit_position) = text(param, "BIT-POSITION").and_then(|bit| bit.parse().ok()) {
+            let integer = matches!(field.data_type, DataType::Number | DataType::Signed);
+            if bit_position > 0 && integer && field.bit_length.is_none() {
+                field.bit_length = field.length.take().map(|length| length as u32 * 8);
+            }
+            field.bit_position = bit_position;
+        }
+        end = field.byte_length().map(|length| position + length);
+        structure.fields.push(FieldDefinition {
+            name: short_name(param).unwrap_or_default().to_string(),
+            byte_position: Some(position.saturating_sub(start)),
+            codec: field,
+        });
+    }
+    structure
+}
+
+fn data_object_prop(odx: &Odx, dop: Node) -> Codec {
+    let Some(coded_type) = child(dop, "DIAG-CODED-TYPE") else {
+        return Codec::new(DataType::Hex);
+    };
+
+    let data_type = match coded_type.attribute("BASE-DATA-TYPE") {
+        Some("A_UINT32") => DataType::Number,
+        Some("A_INT32") => DataType::Signed,
+        Some("A_FLOAT32") | Some("A_FLOAT64") => DataType::Float,
+        Some("A_ASCIISTRING") | Some("A_UTF8STRING") => DataType::String,
+        _ => DataType::Hex,
+    };
+    let mut codec = Codec::new(data_type);
+    if let Some("false") | Some("0") = coded_type.attribute("IS-HIGHLOW-BYTE-ORDER") {
+        codec.byte_order = ByteOrder::Little;
+    }
+
+    match xsi_type(coded_type) {
+        Some("STANDARD-LENGTH-TYPE") => {
+            let bits: u32 = text(coded_type, "BIT-LENGTH")
+                .and_then(|bits| bits.parse().ok())
+                .unwrap_or(0);
+            if bits.is_multiple_of(8) {
+                codec.length = Some(bits as usize / 8).filter(|&length| length > 0);
+            } else if matches!(data_type, DataType::Number | DataType::Signed) {
+                codec.bit_length = Some(bits);
+            } else {
+                return Codec::new(DataType::Hex);
+            }
+        }
+        Some("MIN-MAX-LENGTH-TYPE") => {
+            let length = |name| text(coded_type, name).and_then(|l| l.parse::<usize>().ok());
+            if let (Some(min), Some(max)) = (length("MIN-LENGTH"), length("MAX-LENGTH")) {
+                codec.length = Some(min).filter(|&min| min > 0 && min == max);
+            }
+        }
+        _ => {}
+    }
+
+    if matches!(
+        data_type,
+        DataType::Number | DataType::Signed | DataType::Float
+    ) {
+        if let Some(method) = child(dop, "COMPU-METHOD") {
+            apply_compu_method(&mut codec, method);
+        }
+        codec.unit = odx.resolve_child(dop, "UNIT-REF").and_then(unit_name);
+    }
+    codec
+}
+
+/// Conversion of a LINEAR, RAT-FUNC or TEXTTABLE COMPU-METHOD; other
+/// categories leave raw values
+fn apply_compu_method(codec: &mut Codec, method: Node) {
+    let scales = || {
+        descendant(method, &["COMPU-INTERNAL-TO-PHYS", "COMPU-SCALES"])
+            .into_iter()
+            .flat_map(|scales| children(scales, "COMPU-SCALE"))
+    };
+    let coefficients = |scale: Node, name| -> Vec<f64> {
+        descendant(scale, &["COMPU-RATIONAL-COEFFS", name])
+            .into_iter()
+            .flat_map(|node| children(node, "V"))
+            .filter_map(|v| v.text()?.trim().parse().ok())
+            .collect()
+    };
+
+    match text(method, "CATEGORY") {
+        Some("LINEAR") | Some("RAT-FUNC") => {
+            let Some(scale) = scales().next() else {
+                return;
+            };
+            let numerator = coefficients(scale, "COMPU-NUMERATOR");
+            let denominator = coefficients(scale, "COMPU-DENOMINATOR");
+            match (numerator.as_slice(), denominator.as_slice()) {
+                ([offset, factor], []) => {
+                    codec.scale = *factor;
+                    codec.offset = *offset;
+                }
+                ([offset, factor], [divisor]) if *divisor != 0.0 => {
+                    codec.scale = factor / divisor;
+                    codec.offset = offset / divisor;
+                }
+                ([], _) => {}
+                _ => {
+                    codec.conversion = Some(Conversion::Rational {
+                        denominator: if denominator.is_empty() {
+                            vec![1.0]
+                        } else {
+                            denominator
+                        },
+                        numerator,
+                    });
+                }
+            }
+        }
+        Some("TEXTTABLE") => {
+            let limit =
+                |scale: Node, name| text(scale, name).and_then(|limit| limit.parse::<f64>().ok());
+            let entries: Vec<TextTableEntry> = scales()
+                .filter_map(|scale| {
+                    let lower = limit(scale, "LOWER-LIMIT")?;
+                    let upper = limit(scale, "UPPER-LIMIT").unwrap_or(lower);
+                    let text = descendant(scale, &["COMPU-CONST", "VT"])
+                        .and_then(|vt| vt.text())
+                        .map(str::trim)?;
+                    Some(TextTableEntry {
+                        value: lower as i64,
+                        max: (upper != lower).then_some(upper as i64),
+                        text: text.to_string(),
+                    })
+                })
+                .collect();
+            if !entries.is_empty() {
+                codec.conversion = Some(Conversion::TextTable { entries });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Display name of a UNIT
+fn unit_name(unit: Node) -> Option<String> {
+    text(unit, "DISPLAY-NAME")
+        .or_else(|| short_name(unit))
+        .map(str::to_string)
+}
@@ -0,0 +1,631 @@
+//! Definitions of a diagnostic layer and the layers it inherits from
+
+use super::dop::{byte_position, message_codec, param_length, params};
+use super::{child, children, descendant, short_name, text, xsi_type, Odx};
+use crate::models::uds::{DiagnosticSessionType, RoutineControlType, UdsServiceId};
+use crate::translation::{
+    Codec, DataAccess, DataDictionary, DataItemDefinition, DataType, DtcDefinition,
+    RoutineDefinition, SessionDefinition,
+};
+use roxmltree::Node;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{debug, warn};
+
+const DIAGNOSTIC_SESSION_CONTROL: u8 = UdsServiceId::DiagnosticSessionControl as u8;
+const SECURITY_ACCESS: u8 = UdsServiceId::SecurityAccess as u8;
+const READ_DATA_BY_IDENTIFIER: u8 = UdsServiceId::ReadDataByIdentifier as u8;
+const WRITE_DATA_BY_IDENTIFIER: u8 = UdsServiceId::WriteDataByIdentifier as u8;
+const ROUTINE_CONTROL: u8 = UdsServiceId::RoutineControl as u8;
+
+/// Words naming the service rather than the data item, routine or session
+const SERVICE_WORDS: &[&[&str]] = &[
+    &["read", "data", "by", "identifier"],
+    &["write", "data", "by", "identifier"],
+    &["rdbi"],
+    &["wdbi"],
+    &["read"],
+    &["write"],
+    &["routine", "control"],
+    &["request", "results"],
+    &["results"],
+    &["start"],
+    &["stop"],
+    &["routine"],
+    &["diagnostic", "session", "control"],
+    &["dsc"],
+    &["session"],
+];
+
+/// A DIAG-SERVICE and the bytes its request starts with
+struct Service<'a, 'input> {
+    node: Node<'a, 'input>,
+    /// SHORT-NAME
+    name: &'a str,
+    /// Constant request bytes by byte position (service ID, sub-function,
+    /// identifier, ...)
+    constants: BTreeMap<usize, (u64, usize)>,
+}
+
+impl Service<'_, '_> {
+    fn sid(&self) -> Option<u8> {
+        self.constant(0, 1).map(|sid| sid as u8)
+    }
+
+    /// Constant of `length` bytes at `position`
+    fn constant(&self, position: usize, length: usize) -> Option<u64> {
+        self.constants
+            .get(&position)
+            .filter(|(_, constant_length)| *constant_length == length)
+            .map(|(value, _)| *value)
+    }
+
+    /// SOVD ID derived from the SHORT-NAME
+    fn id(&self) -> String {
+        sovd_id(self.name)
+    }
+
+    /// LONG-NAME, or the SHORT-NAME
+    fn display_name(&self) -> String {
+        text(self.node, "LONG-NAME")
+            .unwrap_or(self.name)
+            .to_string()
+    }
+
+    fn description(&self) -> Option<String> {
+        child(self.node, "DESC").map(|desc| {
+            desc.descendants()
+                .filter(|node| node.is_text())
+                .filter_map(|node| node.text())
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+    }
+}
+
+/// Definitions of a variant, including inherited ones
+pub(super) fn dictionary(odx: &Odx, layer: Node) -> DataDictionary {
+    let services: Vec<Service> = diag_comms(odx, layer, &mut HashSet::new())
+        .into_iter()
+        .filter_map(|node| service(odx, node))
+        .collect();
+    let preconditions = Preconditions::new(odx, &services);
+
+    let mut dictionary = DataDictionary::default();
+    let mut items: BTreeMap<u16, DataItemDefinition> = BTreeMap::new();
+    let mut routines: BTreeMap<u16, (u64, RoutineDefinition)> = BTreeMap::new();
+
+    for service in &services {
+        let (session, security_level) = preconditions.of(odx, service.node);
+        match service.sid() {
+            Some(READ_DATA_BY_IDENTIFIER) => {
+                let Some(did) = service.constant(1, 2).map(|did| did as u16) else {
+                    continue;
+                };
+                let codec = response_codec(odx, service);
+                let item = items.entry(did).or_insert_with(|| data_item(service, did));
+                item.access = match item.access {
+                    DataAccess::Write => DataAccess::ReadWrite,
+                    access => access,
+                };
+                // The read service describes the item
+                item.id = service.id();
+                item.name = display_name(&service.display_name());
+                item.description = service.description().or(item.description.take());
+                item.codec = codec;
+                item.session = session;
+            }
+            Some(WRITE_DATA_BY_IDENTIFIER) => {
+                let Some(did) = service.constant(1, 2).map(|did| did as u16) else {
+                    continue;
+                };
+                let item = items.entry(did).or_insert_with(|| {
+                    let mut item = data_item(service, did);
+                    item.access = DataAccess::Write;
+                    item.codec = request_codec(odx, service);
+                    item
+                });
+                match item.access {
+                    // Write-only items are written in the session of the write service
+                    DataAccess::Write => item.session = session,
+                    _ => item.access = DataAccess::ReadWrite,
+                }
+                item.security_level = security_level;
+            }
+            Some(ROUTINE_CONTROL) => {
+                let (Some(control), Some(rid)) = (service.constant(1, 1), service.constant(2, 2))
+                else {
+                    continue;
+                };
+                let routine = RoutineDefinition {
+                    name: display_name(&service.display_name()),
+                    session,
+                    security_level,
+                    // Parameters follow service ID, sub-function and RID
+                    parameters: odx
+                        .resolve_child(service.node, "REQUEST-REF")
+                        .and_then(|request| message_codec(odx, request, 4)),
+                    description: service.description(),
+                    ..RoutineDefinition::new(&service.id(), rid as u16)
+                };
+                // The start service describes the routine
+                let start = RoutineControlType::StartRoutine as u64;
+                match routines.get(&(rid as u16)) {
+                    Some((existing, _)) if *existing == start || control != start => {}
+                    _ => {
+                        routines.insert(rid as u16, (control, routine));
+                    }
+                }
+            }
+            Some(DIAGNOSTIC_SESSION_CONTROL) => {
+                if let Some(id) = service.constant(1, 1) {
+                    dictionary.sessions.push(SessionDefinition {
+                        name: service.id(),
+                        id: id as u8,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    dictionary.items = items
+        .into_values()
+        .map(|mut item| {
+            item.codec = checked_codec(item.codec, "data item", &item.id);
+            item
+        })
+        .collect();
+    dictionary.routines = routines
+        .into_values()
+        .map(|(_, mut routine)| {
+            routine.parameters = routine
+                .parameters
+                .map(|codec| checked_codec(codec, "routine", &routine.id));
+            routine
+        })
+        .collect();
+    dictionary.dtcs = dtcs(odx, layer, &mut HashSet::new())
+        .into_values()
+        .collect();
+    debug!(
+        "ODX layer '{}': {} data items, {} routines, {} DTCs, {} sessions",
+        short_name(layer).unwrap_or_default(),
+        dictionary.items.len(),
+        dictionary.routines.len(),
+        dictionary.dtcs.len(),
+        dictionary.sessions.len()
+    );
+    dictionary
+}
+
+/// The codec, or a hex codec of the same length when it contradicts itself
+/// (an integer of more than 8 bytes, ...)
+fn checked_codec(codec: Codec, kind: &str, id: &str) -> Codec {
+    let Err(e) = codec.validate() else {
+        return codec;
+    };
+    warn!("ODX {} '{}': {}; its values are shown as hex", kind, id, e);
+    let mut hex = Codec::new(DataType::Hex);
+    hex.length = codec.byte_length().filter(|&length| length > 0);
+    hex
+}
+
+fn data_item(service: &Service, did: u16) -> DataItemDefinition {
+    let mut item = DataItemDefinition::new(&service.id(), did);
+    item.name = display_name(&service.display_name());
+    item.description = service.description();
+    if (0xF180..=0xF19F).contains(&did) {
+        item.category = "identData".to_string();
+    }
+    item
+}
+
+/// DIAG-SERVICEs of a layer: those of its parents, unless excluded by
+/// NOT-INHERITED-DIAG-COMMS, and its own, which override inherited services
+/// with the same SHORT-NAME
+fn diag_comms<'a, 'input>(
+    odx: &Odx<'a, 'input>,
+    layer: Node<'a, 'input>,
+    visited: &mut HashSet<&'a str>,
+) -> Vec<Node<'a, 'input>> {
+    if !visited.insert(layer.attribute("ID").unwrap_or_default()) {
+        return Vec::new();
+    }
+
+    let mut comms: Vec<Node> = Vec::new();
+    let add = |comms: &mut Vec<Node<'a, 'input>>, node: Node<'a, 'input>| {
+        let name = short_name(node);
+        match comms.iter_mut().find(|comm| short_name(**comm) == name) {
+            Some(comm) => *comm = node,
+            None => comms.push(node),
+        }
+    };
+
+    for parent_ref in descendant(layer, &["PARENT-REFS"])
+        .into_iter()
+        .flat_map(|refs| children(refs, "PARENT-REF"))
+    {
+        let Some(parent) = odx.resolve(parent_ref) else {
+            continue;
+        };
+        let excluded: HashSet<&str> = child(parent_ref, "NOT-INHERITED-DIAG-COMMS")
+            .into_iter()
+            .flat_map(|list| children(list, "NOT-INHERITED-DIAG-COMM"))
+            .filter_map(|comm| child(comm, "DIAG-COMM-SNREF")?.attribute("SHORT-NAME"))
+            .collect();
+        for comm in diag_comms(odx, parent, visited) {
+            if !short_name(comm).is_some_and(|name| excluded.contains(name)) {
+                add(&mut comms, comm);
+            }
+        }
+    }
+
+    for comm in child(layer, "DIAG-COMMS")
+        .into_iter()
+        .flat_map(|list| list.children().filter(Node::is_element))
+    {
+        let comm = match comm.tag_name().name() {
+            "DIAG-COMM-REF" => odx.resolve(comm),
+            "DIAG-SERVICE" => Some(comm),
+            // Jobs run on the tester and have no request of their own
+            _ => None,
+        };
+        if let Some(comm) = comm {
+            add(&mut comms, comm);
+        }
+    }
+    comms
+}
+
+/// DTCs of the DTC-DOPs of a layer and its parents
+fn dtcs<'a>(
+    odx: &Odx<'a, '_>,
+    layer: Node<'a, '_>,
+    visited: &mut HashSet<&'a str>,
+) -> BTreeMap<u32, DtcDefinition> {
+    let mut definitions = BTreeMap::new();
+    if !visited.insert(layer.attribute("ID").unwrap_or_default()) {
+        return definitions;
+    }
+
+    for parent in descendant(layer, &["PARENT-REFS"])
+        .into_iter()
+        .flat_map(|refs| children(refs, "PARENT-REF"))
+        .filter_map(|parent_ref| odx.resolve(parent_ref))
+    {
+        definitions.extend(dtcs(odx, parent, visited));
+    }
+
+    for dtc in descendant(layer, &["DIAG-DATA-DICTIONARY-SPEC", "DTC-DOPS"])
+        .into_iter()
+        .flat_map(|dops| children(dops, "DTC-DOP"))
+        .filter_map(|dop| child(dop, "DTCS"))
+        .flat_map(|list| list.children().filter(Node::is_element))
+    {
+        let dtc = match dtc.tag_name().name() {
+            "DTC-REF" => odx.resolve(dtc),
+            "DTC" => Some(dtc),
+            _ => None,
+        };
+        let Some(dtc) = dtc else {
+            continue;
+        };
+        let Some(code) = text(dtc, "TROUBLE-CODE").and_then(|code| code.parse::<u32>().ok()) else {
+            continue;
+        };
+        if code > 0xFF_FFFF {
+            continue;
+        }
+        definitions.insert(
+            code,
+            DtcDefinition {
+                code,
+                display_code: text(dtc, "DISPLAY-TROUBLE-CODE").map(str::to_string),
+                description: text(dtc, "TEXT")
+                    .or_else(|| short_name(dtc))
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+        );
+    }
+    definitions
+}
+
+/// Request constants of a DIAG-SERVICE
+fn service<'a, 'input>(
+    odx: &Odx<'a, 'input>,
+    node: Node<'a, 'input>,
+) -> Option<Service<'a, 'input>> {
+    let request = odx.resolve_child(node, "REQUEST-REF")?;
+    let mut constants = BTreeMap::new();
+    let mut position = 0;
+    for param in params(request) {
+        let length = param_length(odx, param);
+        position = byte_position(param).unwrap_or(position);
+        if xsi_type(param) == Some("CODED-CONST") {
+            let value = text(param, "CODED-VALUE").and_then(parse_coded_value);
+            if let (Some(value), Some(length)) = (value, length) {
+                constants.insert(position, (value, length));
+            }
+        }
+        position += length.unwrap_or(0);
+    }
+
+    Some(Service {
+        node,
+        name: short_name(node)?,
+        constants,
+    })
+}
+
+/// Coded values are decimal; hex values ("0x22") are accepted as well
+fn parse_coded_value(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(digits) => u64::from_str_radix(digits, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Codec of the value a WriteDataByIdentifier request carries
+fn request_codec(odx: &Odx, service: &Service) -> Codec {
+    odx.resolve_child(service.node, "REQUEST-REF")
+        .and_then(|request| message_codec(odx, request, 3))
+        .unwrap_or_else(|| Codec::new(DataType::Hex))
+}
+
+/// Codec of the value a ReadDataByIdentifier positive response carries
+fn response_codec(odx: &Odx, service: &Service) -> Codec {
+    child(service.node, "POS-RESPONSE-REFS")
+        .and_then(|refs| child(refs, "POS-RESPONSE-REF"))
+        .and_then(|reference| odx.resolve(reference))
+        .and_then(|response| message_codec(odx, response, 3))
+        .unwrap_or_else(|| Codec::new(DataType::Hex))
+}
+
+/// Session and security level the PRE-CONDITION-STATE-REFS of services
+/// require
+struct Preconditions {
+    /// (STATE-CHART ID, target state SHORT-NAME) -> request of the service
+    /// performing the transition (service ID, sub-function)
+    transitions: HashMap<(String, String), (u8, u8)>,
+}
+
+impl Preconditions {
+    fn new(odx: &Odx, services: &[Service]) -> Self {
+        let mut transitions = HashMap::new();
+        for service in services {
+            let (Some(sid), Some(sub_function)) = (service.sid(), service.constant(1, 1)) else {
+                continue;
+            };
+            for transition in child(service.node, "STATE-TRANSITION-REFS")
+                .into_iter()
+                .flat_map(|refs| children(refs, "STATE-TRANSITION-REF"))
+                .filter_map(|reference| odx.resolve(reference))
+            {
+                let Some(target) = child(transition, "TARGET-SNREF")
+                    .and_then(|target| target.attribute("SHORT-NAME"))
+                else {
+                    continue;
+                };
+                transitions.insert(
+                    (chart_id(transition), target.to_string()),
+                    (sid, sub_function as u8),
+                );
+            }
+        }
+        Self { transitions }
+    }
+
+    /// Session and security level the service requires
+    ///
+    /// Listed states are alternatives: no session is required when the
+    /// default session is allowed, and the lowest level when several are.
+    fn of(&self, odx: &Odx, service: Node) -> (Option<DiagnosticSessionType>, Option<u8>) {
+        let mut sessions = Vec::new();
+        let mut levels = Vec::new();
+        let mut locked = false;
+
+        for state in child(service, "PRE-CONDITION-STATE-REFS")
+            .into_iter()
+            .flat_map(|refs| children(refs, "PRE-CONDITION-STATE-REF"))
+            .filter_map(|reference| odx.resolve(reference))
+        {
+            let Some(name) = short_name(state) else {
+                continue;
+            };
+            let chart = state
+                .ancestors()
+                .find(|node| node.has_tag_name("STATE-CHART"));
+            let semantic = chart
+                .and_then(|chart| text(chart, "SEMANTIC").or_else(|| short_name(chart)))
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let transition = self
+                .transitions
+                .get(&(chart_id(state), name.to_string()))
+                .copied();
+
+            if semantic.contains("session") {
+                let session = match transition {
+                    Some((DIAGNOSTIC_SESSION_CONTROL, id)) => DiagnosticSessionType::from_u8(id),
+                    _ => session_from_name(name),
+                };
+                match session {
+                    Some(session) => sessions.push(session),
+                    None => debug!("ODX session state '{}' has no standard session", name),
+                }
+            } else if semantic.contains("security") {
+                let level = match transition {
+                    Some((SECURITY_ACCESS, sub_function)) => Some(sub_function.div_ceil(2)),
+                    _ => level_from_name(name),
+                };
+                match level {
+                    Some(level) => levels.push(level),
+                    None => locked = true,
+                }
+            }
+        }
+
+        let session = if sessions.contains(&DiagnosticSessionType::DefaultSession) {
+            None
+        } else {
+            sessions.first().copied()
+        };
+        let level = if locked {
+            None
+        } else {
+            levels.into_iter().min()
+        };
+        (session, level)
+    }
+}
+
+/// ID of the STATE-CHART an element belongs to
+fn chart_id(node: Node) -> String {
+    node.ancestors()
+        .find(|node| node.has_tag_name("STATE-CHART"))
+        .and_then(|chart| chart.attribute("ID").or_else(|| short_name(chart)))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn session_from_name(name: &str) -> Option<DiagnosticSessionType> {
+    let name = name.to_ascii_lowercase();
+    [
+        ("default", DiagnosticSessionType::DefaultSession),
+        ("programming", DiagnosticSessionType::ProgrammingSession),
+        ("extended", DiagnosticSessionType::ExtendedDiagnosticSession),
+        (
+            "safety",
+            DiagnosticSessionType::SafetySystemDiagnosticSession,
+        ),
+    ]
+    .into_iter()
+    .find(|(word, _)| name.contains(word))
+    .map(|(_, session)| session)
+}
+
+/// Security level from the number in a state name ("Unlocked_Level_3");
+/// `None` for locked states
+fn level_from_name(name: &str) -> Option<u8> {
+    let lower = name.to_ascii_lowercase();
+    if lower.contains("locked") && !lower.contains("unlocked") {
+        return None;
+    }
+    let digits: String = name
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits
+        .parse()
+        .ok()
+        .filter(|level| (1..=0x3F).contains(level))
+}
+
+/// snake_case SOVD ID of a SHORT-NAME, without the service name
+/// ("ReadVehicleSpeed" -> "vehicle_speed")
+fn sovd_id(short_name: &str) -> String {
+    strip_service_words(words(short_name)).join("_")
+}
+
+/// Display name without the service name ("Read Vehicle Speed" -> "Vehicle Speed")
+fn display_name(name: &str) -> String {
+    strip_service_words(name.split_whitespace().collect()).join(" ")
+}
+
+/// Remove service words from the start and end, keeping at least one word
+fn strip_service_words<T: AsRef<str>>(mut words: Vec<T>) -> Vec<T> {
+    let matches = |words: &[T], phrase: &[&str]| {
+        words
+            .iter()
+            .zip(phrase)
+            .all(|(word, expected)| word.as_ref().eq_ignore_ascii_case(expected))
+    };
+    loop {
+        let before = words.len();
+        for phrase in SERVICE_WORDS {
+            if words.len() > phrase.len() && matches(&words[..phrase.len()], phrase) {
+                words.drain(..phrase.len());
+            }
+            if words.len() > phrase.len() && matches(&words[words.len() - phrase.len()..], phrase) {
+                words.truncate(words.len() - phrase.len());
+            }
+        }
+        if words.len() == before {
+            return words;
+        }
+    }
+}
+
+/// Lowercase words of a CamelCase or snake_case name
+fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let previous = index.checked_sub(1).map(|index| chars[index]);
+        let next = chars.get(index + 1);
+        let boundary = c.is_ascii_uppercase()
+            && previous.is_some_and(|previous| {
+                previous.is_ascii_lowercase()
+                    || previous.is_ascii_digit()
+                    || (previous.is_ascii_uppercase()
+                        && next.is_some_and(|next| next.is_ascii_lowercase()))
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c.to_ascii_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_and_names_drop_the_service_words() {
+        let ids = [
+            ("ReadVehicleSpeed", "vehicle_speed"),
+            ("ReadDataByIdentifierOperatingMode", "operating_mode"),
+            ("RDBI_EngineRPM", "engine_rpm"),
+            ("ABSStatus_Read", "abs_status"),
+            ("StartFuelPump", "fuel_pump"),
+            ("Read", "read"),
+        ];
+        for (short_name, id) in ids {
+            assert_eq!(sovd_id(short_name), id, "{}", short_name);
+        }
+        assert_eq!(display_name("Read Vehicle Speed"), "Vehicle Speed");
+        assert_eq!(display_name("Fuel Pump Start"), "Fuel Pump");
+    }
+
+    #[test]
+    fn security_levels_come_from_state_names() {
+        assert_eq!(level_from_name("Unlocked_Level_3"), Some(3));
+        assert_eq!(level_from_name("SecurityLevel1"), Some(1));
+        assert_eq!(level_from_name("Locked"), None);
+        assert_eq!(level_from_name("Unlocked"), None);
+        assert_eq!(
+            session_from_name("ExtendedSession"),
+            Some(DiagnosticSessionType::ExtendedDiagnosticSession)
+        );
+        assert_eq!(session_from_name("Sleep"), None);
+    }
+}
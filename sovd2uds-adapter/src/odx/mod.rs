@@ -0,0 +1,726 @@
+//! Import of ODX 2.2 diagnostic descriptions (.odx files and PDX archives)
+//!
+//! The BASE-VARIANTs and ECU-VARIANTs of the DIAG-LAYER-CONTAINERs are
+//! turned into [`DataDictionary`]s: ReadDataByIdentifier and
+//! WriteDataByIdentifier services become data items, RoutineControl services
+//! routines, DiagnosticSessionControl services sessions and the DTC-DOPs DTCs.
+
+mod dop;
+mod layer;
+
+use crate::error::{Result, Sovd2UdsError};
+use crate::translation::DataDictionary;
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// Kind of an imported diagnostic layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    BaseVariant,
+    EcuVariant,
+}
+
+/// Definitions of a BASE-VARIANT or ECU-VARIANT, including those it inherits
+#[derive(Debug, Clone)]
+pub struct DiagLayer {
+    /// SHORT-NAME of the variant
+    pub name: String,
+    pub kind: LayerKind,
+    pub dictionary: DataDictionary,
+}
+
+/// Import the variants of ODX files and PDX archives
+///
+/// All files are imported together, so variants may inherit from layers in
+/// other files of the list.
+pub fn import(paths: &[String]) -> Result<Vec<DiagLayer>> {
+    let mut sources = Vec::new();
+    for path in paths {
+        sources.extend(read_sources(path)?);
+    }
+
+    let documents = sources
+        .iter()
+        .map(|(name, text)| Document::parse(text).map_err(|e| odx_error(name, e.to_string())))
+        .collect::<Result<Vec<_>>>()?;
+
+    let odx = Odx::new(&documents);
+    Ok(odx
+        .variants()
+        .map(|(node, kind)| DiagLayer {
+            name: short_name(node).unwrap_or_default().to_string(),
+            kind,
+            dictionary: layer::dictionary(&odx, node),
+        })
+        .collect())
+}
+
+/// ODX documents of a file, as (name, XML) pairs; PDX archives are read
+/// entry by entry
+fn read_sources(path: &str) -> Result<Vec<(String, String)>> {
+    let data = std::fs::read(path).map_err(|e| odx_error(path, e.to_string()))?;
+    if !data.starts_with(b"PK\x03\x04") {
+        let text = String::from_utf8(data).map_err(|e| odx_error(path, e.to_string()))?;
+        return Ok(vec![(path.to_string(), text)]);
+    }
+
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(data)).map_err(|e| odx_error(path, e.to_string()))?;
+    let mut sources = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| odx_error(path, e.to_string()))?;
+        let name = format!("{}/{}", path, entry.name());
+        let is_odx = entry
+            .name()
+            .rsplit_once('.')
+            .is_some_and(|(_, extension)| extension.to_ascii_lowercase().starts_with("odx"));
+        if !entry.is_file() || !is_odx {
+            continue;
+        }
+
+        let mut text = String::new();
+        entry
+            .read_to_string(&mut text)
+            .map_err(|e| odx_error(&name, e.to_string()))?;
+        sources.push((name, text));
+    }
+
+    if sources.is_empty() {
+        return Err(odx_error(path, "archive contains no ODX files".to_string()));
+    }
+    Ok(sources)
+}
+
+fn odx_error(path: &str, message: String) -> Sovd2UdsError {
+    Sovd2UdsError::Config(format!("Failed to import ODX '{}': {}", path, message))
+}
+
+/// Elements of all documents, indexed by ID
+struct Odx<'a, 'input> {
+    ids: HashMap<&'a str, Node<'a, 'input>>,
+    layers: Vec<Node<'a, 'input>>,
+}
+
+impl<'a, 'input> Odx<'a, 'input> {
+    fn new(documents: &'a [Document<'input>]) -> Self {
+        let mut ids = HashMap::new();
+        let mut layers = Vec::new();
+        for document in documents {
+            for node in document.descendants().filter(Node::is_element) {
+                if let Some(id) = node.attribute("ID") {
+                    ids.insert(id, node);
+                }
+                if is_layer(node) {
+                    layers.push(node);
+                }
+            }
+        }
+        Self { ids, layers }
+    }
+
+    /// BASE-VARIANTs and ECU-VARIANTs
+    fn variants(&self) -> impl Iterator<Item = (Node<'a, 'input>, LayerKind)> + '_ {
+        self.layers
+            .iter()
+            .filter_map(|node| match node.tag_name().name() {
+                "BASE-VARIANT" => Some((*node, LayerKind::BaseVariant)),
+                "ECU-VARIANT" => Some((*node, LayerKind::EcuVariant)),
+                _ => None,
+            })
+    }
+
+    /// Element referenced by the ID-REF of `reference`
+    fn resolve(&self, reference: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+        reference
+            .attribute("ID-REF")
+            .and_then(|id| self.ids.get(id))
+            .copied()
+    }
+
+    /// Element referenced by the ID-REF of the child `name` of `node`
+    fn resolve_child(&self, node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+        child(node, name).and_then(|reference| self.resolve(reference))
+    }
+}
+
+fn is_layer(node: Node) -> bool {
+    matches!(
+        node.tag_name().name(),
+        "BASE-VARIANT" | "ECU-VARIANT" | "PROTOCOL" | "FUNCTIONAL-GROUP" | "ECU-SHARED-DATA"
+    )
+}
+
+/// First child element called `name`
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+/// Child elements called `name`
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+/// Element reached by following child elements called `path`
+fn descendant<'a, 'input>(node: Node<'a, 'input>, path: &[&str]) -> Option<Node<'a, 'input>> {
+    path.iter().try_fold(node, |node, name| child(node, name))
+}
+
+/// Trimmed text of the child element called `name`
+fn text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn short_name<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    text(node, "SHORT-NAME")
+}
+
+/// Value of the xsi:type attribute
+fn xsi_type<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == "type")
+        .map(|attribute| attribute.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::uds::DiagnosticSessionType;
+    use crate::translation::dictionary::test_file;
+    use crate::translation::{DataAccess, DataItemDefinition, DataType};
+    use serde_json::json;
+    use std::io::Write;
+
+    /// A base variant with identification, a scaled value, a routine, a DTC
+    /// and session and security state charts, and an ECU variant replacing
+    /// one of its data items
+    const ODX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ODX xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" MODEL-VERSION="2.2.0">
+  <DIAG-LAYER-CONTAINER ID="DLC_Engine">
+    <SHORT-NAME>DLC_Engine</SHORT-NAME>
+    <BASE-VARIANTS>
+      <BASE-VARIANT ID="BV_Engine">
+        <SHORT-NAME>EngineBase</SHORT-NAME>
+        <DIAG-DATA-DICTIONARY-SPEC>
+          <DTC-DOPS>
+            <DTC-DOP ID="DOP_DTC">
+              <SHORT-NAME>DTCs</SHORT-NAME>
+              <DTCS>
+                <DTC ID="DTC_MAF">
+                  <SHORT-NAME>MafShortToGround</SHORT-NAME>
+                  <TROUBLE-CODE>65553</TROUBLE-CODE>
+                  <DISPLAY-TROUBLE-CODE>P0100-11</DISPLAY-TROUBLE-CODE>
+                  <TEXT>Mass air flow circuit short to ground</TEXT>
+                </DTC>
+              </DTCS>
+            </DTC-DOP>
+          </DTC-DOPS>
+          <DATA-OBJECT-PROPS>
+            <DATA-OBJECT-PROP ID="DOP_Speed">
+              <SHORT-NAME>Speed</SHORT-NAME>
+              <COMPU-METHOD>
+                <CATEGORY>LINEAR</CATEGORY>
+                <COMPU-INTERNAL-TO-PHYS>
+                  <COMPU-SCALES>
+                    <COMPU-SCALE>
+                      <COMPU-RATIONAL-COEFFS>
+                        <COMPU-NUMERATOR><V>0</V><V>0.01</V></COMPU-NUMERATOR>
+                      </COMPU-RATIONAL-COEFFS>
+                    </COMPU-SCALE>
+                  </COMPU-SCALES>
+                </COMPU-INTERNAL-TO-PHYS>
+              </COMPU-METHOD>
+              <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                <BIT-LENGTH>16</BIT-LENGTH>
+              </DIAG-CODED-TYPE>
+              <UNIT-REF ID-REF="UNIT_KMH"/>
+            </DATA-OBJECT-PROP>
+            <DATA-OBJECT-PROP ID="DOP_Vin">
+              <SHORT-NAME>Vin</SHORT-NAME>
+              <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_ASCIISTRING">
+                <BIT-LENGTH>136</BIT-LENGTH>
+              </DIAG-CODED-TYPE>
+            </DATA-OBJECT-PROP>
+            <DATA-OBJECT-PROP ID="DOP_Duty">
+              <SHORT-NAME>Duty</SHORT-NAME>
+              <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                <BIT-LENGTH>8</BIT-LENGTH>
+              </DIAG-CODED-TYPE>
+            </DATA-OBJECT-PROP>
+          </DATA-OBJECT-PROPS>
+          <UNIT-SPEC>
+            <UNITS>
+              <UNIT ID="UNIT_KMH">
+                <SHORT-NAME>KilometersPerHour</SHORT-NAME>
+                <DISPLAY-NAME>km/h</DISPLAY-NAME>
+              </UNIT>
+            </UNITS>
+          </UNIT-SPEC>
+        </DIAG-DATA-DICTIONARY-SPEC>
+        <DIAG-COMMS>
+          <DIAG-SERVICE ID="DS_ReadSpeed">
+            <SHORT-NAME>ReadVehicleSpeed</SHORT-NAME>
+            <LONG-NAME>Read Vehicle Speed</LONG-NAME>
+            <REQUEST-REF ID-REF="RQ_ReadSpeed"/>
+            <POS-RESPONSE-REFS><POS-RESPONSE-REF ID-REF="PR_ReadSpeed"/></POS-RESPONSE-REFS>
+          </DIAG-SERVICE>
+          <DIAG-SERVICE ID="DS_ReadVin">
+            <SHORT-NAME>ReadVIN</SHORT-NAME>
+            <DESC><p>Vehicle identification number</p></DESC>
+            <REQUEST-REF ID-REF="RQ_ReadVin"/>
+            <POS-RESPONSE-REFS><POS-RESPONSE-REF ID-REF="PR_ReadVin"/></POS-RESPONSE-REFS>
+          </DIAG-SERVICE>
+          <DIAG-SERVICE ID="DS_WriteVin">
+            <SHORT-NAME>WriteVIN</SHORT-NAME>
+            <REQUEST-REF ID-REF="RQ_WriteVin"/>
+            <PRE-CONDITION-STATE-REFS>
+              <PRE-CONDITION-STATE-REF ID-REF="ST_Extended"/>
+              <PRE-CONDITION-STATE-REF ID-REF="ST_Unlocked"/>
+            </PRE-CONDITION-STATE-REFS>
+          </DIAG-SERVICE>
+          <DIAG-SERVICE ID="DS_StartFuelPump">
+            <SHORT-NAME>StartFuelPump</SHORT-NAME>
+            <LONG-NAME>Fuel Pump</LONG-NAME>
+            <REQUEST-REF ID-REF="RQ_StartFuelPump"/>
+            <PRE-CONDITION-STATE-REFS>
+              <PRE-CONDITION-STATE-REF ID-REF="ST_Extended"/>
+            </PRE-CONDITION-STATE-REFS>
+          </DIAG-SERVICE>
+          <DIAG-SERVICE ID="DS_Extended">
+            <SHORT-NAME>DSC_Extended</SHORT-NAME>
+            <REQUEST-REF ID-REF="RQ_Extended"/>
+            <STATE-TRANSITION-REFS>
+              <STATE-TRANSITION-REF ID-REF="TR_DefaultToExtended"/>
+            </STATE-TRANSITION-REFS>
+          </DIAG-SERVICE>
+          <DIAG-SERVICE ID="DS_SendKey">
+            <SHORT-NAME>SendKeyLevel2</SHORT-NAME>
+            <REQUEST-REF ID-REF="RQ_SendKey"/>
+            <STATE-TRANSITION-REFS>
+              <STATE-TRANSITION-REF ID-REF="TR_Unlock"/>
+            </STATE-TRANSITION-REFS>
+          </DIAG-SERVICE>
+        </DIAG-COMMS>
+        <REQUESTS>
+          <REQUEST ID="RQ_ReadSpeed">
+            <SHORT-NAME>RQ_ReadSpeed</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>34</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>269</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>16</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+          <REQUEST ID="RQ_ReadVin">
+            <SHORT-NAME>RQ_ReadVin</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>0x22</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>61840</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>16</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+          <REQUEST ID="RQ_WriteVin">
+            <SHORT-NAME>RQ_WriteVin</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>46</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>61840</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>16</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="VALUE">
+                <SHORT-NAME>Vin</SHORT-NAME>
+                <BYTE-POSITION>3</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_Vin"/>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+          <REQUEST ID="RQ_StartFuelPump">
+            <SHORT-NAME>RQ_StartFuelPump</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>49</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>Control</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>1</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>RID</SHORT-NAME>
+                <BYTE-POSITION>2</BYTE-POSITION>
+                <CODED-VALUE>513</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>16</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="VALUE">
+                <SHORT-NAME>Duty</SHORT-NAME>
+                <BYTE-POSITION>4</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_Duty"/>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+          <REQUEST ID="RQ_Extended">
+            <SHORT-NAME>RQ_Extended</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>16</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>Session</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>3</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+          <REQUEST ID="RQ_SendKey">
+            <SHORT-NAME>RQ_SendKey</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>39</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SendKey</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>4</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+        </REQUESTS>
+        <POS-RESPONSES>
+          <POS-RESPONSE ID="PR_ReadSpeed">
+            <SHORT-NAME>PR_ReadSpeed</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>98</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="MATCHING-REQUEST-PARAM">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <REQUEST-BYTE-POS>1</REQUEST-BYTE-POS>
+                <BYTE-LENGTH>2</BYTE-LENGTH>
+              </PARAM>
+              <PARAM xsi:type="VALUE">
+                <SHORT-NAME>Speed</SHORT-NAME>
+                <BYTE-POSITION>3</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_Speed"/>
+              </PARAM>
+            </PARAMS>
+          </POS-RESPONSE>
+          <POS-RESPONSE ID="PR_ReadVin">
+            <SHORT-NAME>PR_ReadVin</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>98</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="MATCHING-REQUEST-PARAM">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <REQUEST-BYTE-POS>1</REQUEST-BYTE-POS>
+                <BYTE-LENGTH>2</BYTE-LENGTH>
+              </PARAM>
+              <PARAM xsi:type="VALUE">
+                <SHORT-NAME>Vin</SHORT-NAME>
+                <BYTE-POSITION>3</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_Vin"/>
+              </PARAM>
+            </PARAMS>
+          </POS-RESPONSE>
+        </POS-RESPONSES>
+        <STATE-CHARTS>
+          <STATE-CHART ID="SC_Session">
+            <SHORT-NAME>Session</SHORT-NAME>
+            <SEMANTIC>SESSION</SEMANTIC>
+            <STATE-TRANSITIONS>
+              <STATE-TRANSITION ID="TR_DefaultToExtended">
+                <SHORT-NAME>DefaultToExtended</SHORT-NAME>
+                <SOURCE-SNREF SHORT-NAME="Default"/>
+                <TARGET-SNREF SHORT-NAME="Extended"/>
+              </STATE-TRANSITION>
+            </STATE-TRANSITIONS>
+            <STATES>
+              <STATE ID="ST_Default"><SHORT-NAME>Default</SHORT-NAME></STATE>
+              <STATE ID="ST_Extended"><SHORT-NAME>Extended</SHORT-NAME></STATE>
+            </STATES>
+          </STATE-CHART>
+          <STATE-CHART ID="SC_Security">
+            <SHORT-NAME>SecurityAccess</SHORT-NAME>
+            <SEMANTIC>SECURITY</SEMANTIC>
+            <STATE-TRANSITIONS>
+              <STATE-TRANSITION ID="TR_Unlock">
+                <SHORT-NAME>Unlock</SHORT-NAME>
+                <SOURCE-SNREF SHORT-NAME="Locked"/>
+                <TARGET-SNREF SHORT-NAME="Unlocked"/>
+              </STATE-TRANSITION>
+            </STATE-TRANSITIONS>
+            <STATES>
+              <STATE ID="ST_Locked"><SHORT-NAME>Locked</SHORT-NAME></STATE>
+              <STATE ID="ST_Unlocked"><SHORT-NAME>Unlocked</SHORT-NAME></STATE>
+            </STATES>
+          </STATE-CHART>
+        </STATE-CHARTS>
+      </BASE-VARIANT>
+    </BASE-VARIANTS>
+    <ECU-VARIANTS>
+      <ECU-VARIANT ID="EV_Engine_V2">
+        <SHORT-NAME>Engine_V2</SHORT-NAME>
+        <DIAG-DATA-DICTIONARY-SPEC>
+          <DATA-OBJECT-PROPS>
+            <DATA-OBJECT-PROP ID="DOP_Mode">
+              <SHORT-NAME>Mode</SHORT-NAME>
+              <COMPU-METHOD>
+                <CATEGORY>TEXTTABLE</CATEGORY>
+                <COMPU-INTERNAL-TO-PHYS>
+                  <COMPU-SCALES>
+                    <COMPU-SCALE>
+                      <LOWER-LIMIT>0</LOWER-LIMIT>
+                      <COMPU-CONST><VT>Off</VT></COMPU-CONST>
+                    </COMPU-SCALE>
+                    <COMPU-SCALE>
+                      <LOWER-LIMIT>1</LOWER-LIMIT>
+                      <UPPER-LIMIT>3</UPPER-LIMIT>
+                      <COMPU-CONST><VT>Running</VT></COMPU-CONST>
+                    </COMPU-SCALE>
+                  </COMPU-SCALES>
+                </COMPU-INTERNAL-TO-PHYS>
+              </COMPU-METHOD>
+              <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32">
+                <BIT-LENGTH>8</BIT-LENGTH>
+              </DIAG-CODED-TYPE>
+            </DATA-OBJECT-PROP>
+          </DATA-OBJECT-PROPS>
+        </DIAG-DATA-DICTIONARY-SPEC>
+        <DIAG-COMMS>
+          <DIAG-SERVICE ID="DS_ReadMode">
+            <SHORT-NAME>ReadDataByIdentifierOperatingMode</SHORT-NAME>
+            <REQUEST-REF ID-REF="RQ_ReadMode"/>
+            <POS-RESPONSE-REFS><POS-RESPONSE-REF ID-REF="PR_ReadMode"/></POS-RESPONSE-REFS>
+          </DIAG-SERVICE>
+        </DIAG-COMMS>
+        <REQUESTS>
+          <REQUEST ID="RQ_ReadMode">
+            <SHORT-NAME>RQ_ReadMode</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>34</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <CODED-VALUE>272</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>16</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+            </PARAMS>
+          </REQUEST>
+        </REQUESTS>
+        <POS-RESPONSES>
+          <POS-RESPONSE ID="PR_ReadMode">
+            <SHORT-NAME>PR_ReadMode</SHORT-NAME>
+            <PARAMS>
+              <PARAM xsi:type="CODED-CONST">
+                <SHORT-NAME>SID</SHORT-NAME>
+                <BYTE-POSITION>0</BYTE-POSITION>
+                <CODED-VALUE>98</CODED-VALUE>
+                <DIAG-CODED-TYPE xsi:type="STANDARD-LENGTH-TYPE" BASE-DATA-TYPE="A_UINT32"><BIT-LENGTH>8</BIT-LENGTH></DIAG-CODED-TYPE>
+              </PARAM>
+              <PARAM xsi:type="MATCHING-REQUEST-PARAM">
+                <SHORT-NAME>DID</SHORT-NAME>
+                <BYTE-POSITION>1</BYTE-POSITION>
+                <REQUEST-BYTE-POS>1</REQUEST-BYTE-POS>
+                <BYTE-LENGTH>2</BYTE-LENGTH>
+              </PARAM>
+              <PARAM xsi:type="VALUE">
+                <SHORT-NAME>Mode</SHORT-NAME>
+                <BYTE-POSITION>3</BYTE-POSITION>
+                <DOP-REF ID-REF="DOP_Mode"/>
+              </PARAM>
+            </PARAMS>
+          </POS-RESPONSE>
+        </POS-RESPONSES>
+        <PARENT-REFS>
+          <PARENT-REF ID-REF="BV_Engine" xsi:type="BASE-VARIANT-REF">
+            <NOT-INHERITED-DIAG-COMMS>
+              <NOT-INHERITED-DIAG-COMM>
+                <DIAG-COMM-SNREF SHORT-NAME="ReadVehicleSpeed"/>
+              </NOT-INHERITED-DIAG-COMM>
+            </NOT-INHERITED-DIAG-COMMS>
+          </PARENT-REF>
+        </PARENT-REFS>
+      </ECU-VARIANT>
+    </ECU-VARIANTS>
+  </DIAG-LAYER-CONTAINER>
+</ODX>
+"#;
+
+    fn layer<'a>(layers: &'a [DiagLayer], name: &str) -> &'a DataDictionary {
+        &layers
+            .iter()
+            .find(|layer| layer.name == name)
+            .unwrap()
+            .dictionary
+    }
+
+    fn item<'a>(dictionary: &'a DataDictionary, id: &str) -> &'a DataItemDefinition {
+        dictionary.items.iter().find(|item| item.id == id).unwrap()
+    }
+
+    #[test]
+    fn services_become_data_items_routines_sessions_and_dtcs() {
+        let layers = import(&[test_file("engine.odx-d", ODX)]).unwrap();
+        let kinds: Vec<_> = layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("EngineBase", LayerKind::BaseVariant),
+                ("Engine_V2", LayerKind::EcuVariant)
+            ]
+        );
+        let base = layer(&layers, "EngineBase");
+
+        let speed = item(base, "vehicle_speed");
+        assert_eq!((speed.did, speed.name.as_str()), (0x010D, "Vehicle Speed"));
+        assert_eq!(speed.category, "currentData");
+        assert_eq!(speed.access, DataAccess::Read);
+        assert_eq!(speed.codec.unit.as_deref(), Some("km/h"));
+        assert_eq!(speed.codec.decode(&[0x27, 0x10]).unwrap(), json!(100.0));
+
+        // Read and write services of a DID make one item; the write service's
+        // preconditions apply to writes
+        let vin = item(base, "vin");
+        assert_eq!((vin.did, vin.category.as_str()), (0xF190, "identData"));
+        assert_eq!(vin.access, DataAccess::ReadWrite);
+        assert_eq!(vin.codec.data_type, DataType::String);
+        assert_eq!(vin.codec.length, Some(17));
+        assert_eq!(vin.session, None);
+        assert_eq!(vin.security_level, Some(2));
+        assert_eq!(
+            vin.description.as_deref(),
+            Some("Vehicle identification number")
+        );
+
+        let [routine] = base.routines.as_slice() else {
+            panic!("{:?}", base.routines);
+        };
+        assert_eq!((routine.id.as_str(), routine.rid), ("fuel_pump", 0x0201));
+        assert_eq!(
+            routine.session,
+            Some(DiagnosticSessionType::ExtendedDiagnosticSession)
+        );
+        let parameters = routine.parameters.as_ref().unwrap();
+        assert_eq!(parameters.encode(&json!(40)).unwrap(), [40]);
+
+        let sessions: Vec<_> = base
+            .sessions
+            .iter()
+            .map(|s| (s.name.as_str(), s.id))
+            .collect();
+        assert_eq!(sessions, [("extended", 0x03)]);
+
+        let [dtc] = base.dtcs.as_slice() else {
+            panic!("{:?}", base.dtcs);
+        };
+        assert_eq!(dtc.code, 0x010011);
+        assert_eq!(dtc.display_code.as_deref(), Some("P0100-11"));
+        assert_eq!(dtc.description, "Mass air flow circuit short to ground");
+    }
+
+    #[test]
+    fn variants_inherit_all_but_excluded_services() {
+        let layers = import(&[test_file("inherit.odx-d", ODX)]).unwrap();
+        let variant = layer(&layers, "Engine_V2");
+
+        let mut ids: Vec<_> = variant.items.iter().map(|item| item.id.as_str()).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["operating_mode", "vin"]);
+        let mode = item(variant, "operating_mode");
+        assert_eq!(mode.codec.decode(&[0x00]).unwrap(), json!("Off"));
+        assert_eq!(mode.codec.decode(&[0x02]).unwrap(), json!("Running"));
+
+        assert_eq!(variant.routines.len(), 1);
+        assert_eq!(variant.dtcs.len(), 1);
+    }
+
+    #[test]
+    fn pdx_archives_are_read_entry_by_entry() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        archive.start_file("index.xml", options).unwrap();
+        archive.write_all(b"<CATALOG/>").unwrap();
+        archive.start_file("engine.odx-d", options).unwrap();
+        archive.write_all(ODX.as_bytes()).unwrap();
+        let archive = archive.finish().unwrap().into_inner();
+
+        let layers = import(&[test_file("engine.pdx", archive)]).unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layer(&layers, "EngineBase").items.len(), 2);
+
+        let mut empty = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        empty.start_file("index.xml", options).unwrap();
+        let empty = empty.finish().unwrap().into_inner();
+        assert!(import(&[test_file("empty.pdx", empty)]).is_err());
+    }
+
+    #[test]
+    fn unreadable_sources_are_config_errors() {
+        let broken = test_file("broken.odx-d", "<ODX><DIAG-LAYER-CONTAINER>");
+        assert!(matches!(import(&[broken]), Err(Sovd2UdsError::Config(_))));
+        let missing = "/nonexistent/engine.odx-d".to_string();
+        assert!(matches!(import(&[missing]), Err(Sovd2UdsError::Config(_))));
+    }
+}
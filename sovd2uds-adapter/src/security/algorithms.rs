@@ -0,0 +1,139 @@
+use super::SeedKeyAlgorithm;
+use crate::error::{Result, Sovd2UdsError};
+use crate::translation::hex;
+use aes::{Aes128, Aes256};
+use cmac::{Cmac, Mac};
+
+fn parse_hex(name: &str, text: &str) -> Result<Vec<u8>> {
+    hex::decode(text).ok_or_else(|| {
+        Sovd2UdsError::Config(format!("Invalid hex for seed/key {}: '{}'", name, text))
+    })
+}
+
+/// Key = seed XOR mask, the mask repeated over the seed
+pub struct XorMask {
+    mask: Vec<u8>,
+}
+
+impl XorMask {
+    pub fn new(mask: Vec<u8>) -> Self {
+        Self { mask }
+    }
+
+    pub fn from_hex(mask: &str) -> Result<Self> {
+        let mask = parse_hex("mask", mask)?;
+        if mask.is_empty() {
+            return Err(Sovd2UdsError::Config(
+                "Seed/key XOR mask must not be empty".to_string(),
+            ));
+        }
+        Ok(Self::new(mask))
+    }
+}
+
+impl SeedKeyAlgorithm for XorMask {
+    fn compute_key(&self, _level: u8, seed: &[u8]) -> Result<Vec<u8>> {
+        Ok(seed
+            .iter()
+            .zip(self.mask.iter().cycle())
+            .map(|(s, m)| s ^ m)
+            .collect())
+    }
+
+    fn name(&self) -> &'static str {
+        "xor"
+    }
+}
+
+/// Key = CRC-32 (IEEE 802.3) over the seed followed by a secret, big-endian
+pub struct Crc32 {
+    secret: Vec<u8>,
+}
+
+impl Crc32 {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret }
+    }
+
+    pub fn from_hex(secret: &str) -> Result<Self> {
+        Ok(Self::new(parse_hex("secret", secret)?))
+    }
+
+    fn checksum(data: impl Iterator<Item = u8>) -> u32 {
+        let mut crc = 0xFFFF_FFFFu32;
+        for byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+}
+
+impl SeedKeyAlgorithm for Crc32 {
+    fn compute_key(&self, _level: u8, seed: &[u8]) -> Result<Vec<u8>> {
+        let crc = Self::checksum(seed.iter().chain(&self.secret).copied());
+        Ok(crc.to_be_bytes().to_vec())
+    }
+
+    fn name(&self) -> &'static str {
+        "crc32"
+    }
+}
+
+/// Key = AES-CMAC of the seed under a shared secret key (16 or 32 bytes),
+/// optionally truncated
+pub struct AesCmac {
+    key: Vec<u8>,
+    key_length: usize,
+}
+
+impl AesCmac {
+    pub fn new(key: Vec<u8>, key_length: Option<usize>) -> Result<Self> {
+        if key.len() != 16 && key.len() != 32 {
+            return Err(Sovd2UdsError::Config(format!(
+                "AES-CMAC key must be 16 or 32 bytes, got {}",
+                key.len()
+            )));
+        }
+        let key_length = key_length.unwrap_or(16);
+        if key_length == 0 || key_length > 16 {
+            return Err(Sovd2UdsError::Config(format!(
+                "AES-CMAC key_length must be 1-16 bytes, got {}",
+                key_length
+            )));
+        }
+        Ok(Self { key, key_length })
+    }
+
+    pub fn from_hex(key: &str, key_length: Option<usize>) -> Result<Self> {
+        Self::new(parse_hex("key", key)?, key_length)
+    }
+
+    fn mac<M: Mac + cmac::digest::KeyInit>(&self, seed: &[u8]) -> Vec<u8> {
+        let mut mac = <M as cmac::digest::KeyInit>::new_from_slice(&self.key)
+            .expect("AES key length checked on construction");
+        mac.update(seed);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+impl SeedKeyAlgorithm for AesCmac {
+    fn compute_key(&self, _level: u8, seed: &[u8]) -> Result<Vec<u8>> {
+        let mut tag = match self.key.len() {
+            16 => self.mac::<Cmac<Aes128>>(seed),
+            _ => self.mac::<Cmac<Aes256>>(seed),
+        };
+        tag.truncate(self.key_length);
+        Ok(tag)
+    }
+
+    fn name(&self) -> &'static str {
+        "aes_cmac"
+    }
+}
@@ -0,0 +1,225 @@
+use super::SeedKeyAlgorithm;
+use crate::error::{Result, Sovd2UdsError};
+use crate::translation::hex;
+use libloading::{Library, Symbol};
+use std::ffi::CString;
+use std::io::Read;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Largest key a seed-key library may return
+const MAX_KEY_LENGTH: usize = 64;
+
+/// `GenerateKeyEx` as exported by ASAM/Vector-style seed-key libraries
+type GenerateKeyEx = unsafe extern "system" fn(
+    seed: *const u8,
+    seed_length: c_uint,
+    security_level: c_uint,
+    variant: *const c_char,
+    key: *mut u8,
+    max_key_length: c_uint,
+    key_length: *mut c_uint,
+) -> c_int;
+
+/// Seed-key shared library (`.so`/`.dll`) exporting `GenerateKeyEx`
+///
+/// The library receives the requestSeed sub-function (`level * 2 - 1`) as
+/// security level, which is what these libraries are built against.
+pub struct SharedLibrary {
+    library: Library,
+    variant: CString,
+    path: String,
+}
+
+impl SharedLibrary {
+    pub fn load(path: &str, variant: &str) -> Result<Self> {
+        // SAFETY: loading runs the library's initialisers; the path comes
+        // from the adapter configuration, which is trusted
+        let library = unsafe { Library::new(path) }.map_err(|e| {
+            Sovd2UdsError::Config(format!("Cannot load seed-key library '{}': {}", path, e))
+        })?;
+        // SAFETY: only checks the symbol exists; the signature is the documented one
+        unsafe { library.get::<GenerateKeyEx>(b"GenerateKeyEx\0") }.map_err(|e| {
+            Sovd2UdsError::Config(format!("'{}' does not export GenerateKeyEx: {}", path, e))
+        })?;
+        let variant = CString::new(variant).map_err(|_| {
+            Sovd2UdsError::Config("Seed-key variant must not contain NUL bytes".to_string())
+        })?;
+
+        Ok(Self {
+            library,
+            variant,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl SeedKeyAlgorithm for SharedLibrary {
+    fn compute_key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>> {
+        // The library is passed the RequestSeed sub-function of the level
+        let request_seed_type = level
+            .checked_mul(2)
+            .and_then(|sub_function| sub_function.checked_sub(1))
+            .ok_or_else(|| {
+                Sovd2UdsError::InvalidRequest(format!("Invalid security level: {}", level))
+            })?;
+        let mut key = vec![0u8; MAX_KEY_LENGTH];
+        let mut key_length: c_uint = 0;
+
+        // SAFETY: the symbol was verified on load; all buffers outlive the
+        // call and their lengths are passed alongside
+        let result = unsafe {
+            let generate: Symbol<GenerateKeyEx> = self
+                .library
+                .get(b"GenerateKeyEx\0")
+                .map_err(|e| Sovd2UdsError::Internal(e.to_string()))?;
+            generate(
+                seed.as_ptr(),
+                seed.len() as c_uint,
+                request_seed_type as c_uint,
+                self.variant.as_ptr(),
+                key.as_mut_ptr(),
+                key.len() as c_uint,
+                &mut key_length,
+            )
+        };
+
+        if result != 0 {
+            return Err(Sovd2UdsError::Internal(format!(
+                "Seed-key library '{}' failed with result {}",
+                self.path, result
+            )));
+        }
+        key.truncate((key_length as usize).min(MAX_KEY_LENGTH));
+        Ok(key)
+    }
+
+    fn name(&self) -> &'static str {
+        "library"
+    }
+}
+
+/// External program computing the key
+///
+/// Runs `command... <level> <seed hex>` and reads the key as hex from
+/// standard output. A non-zero exit status or running longer than `timeout`
+/// milliseconds fails the security access.
+pub struct CommandHook {
+    command: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandHook {
+    pub fn new(command: Vec<String>, timeout: u32) -> Result<Self> {
+        if command.is_empty() {
+            return Err(Sovd2UdsError::Config(
+                "Seed-key command must not be empty".to_string(),
+            ));
+        }
+        Ok(Self {
+            command,
+            timeout: Duration::from_millis(timeout as u64),
+        })
+    }
+
+    fn failed(&self, reason: impl std::fmt::Display) -> Sovd2UdsError {
+        Sovd2UdsError::Internal(format!("Seed-key command '{}' {}", self.command[0], reason))
+    }
+}
+
+impl SeedKeyAlgorithm for CommandHook {
+    fn compute_key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .arg(level.to_string())
+            .arg(hex::encode(seed))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.failed(format!("could not start: {}", e)))?;
+
+        // Drain standard output while waiting, so a hook printing more than
+        // the pipe buffer does not block
+        let mut stdout = child.stdout.take();
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            if let Some(stdout) = stdout.as_mut() {
+                stdout.read_to_string(&mut output)?;
+            }
+            Ok::<_, std::io::Error>(output)
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(self.failed(format!("could not be waited for: {}", e)));
+                }
+            }
+            if started.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(self.failed("timed out"));
+            }
+            sleep(Duration::from_millis(10));
+        };
+        if !status.success() {
+            return Err(self.failed(format!("exited with {}", status)));
+        }
+
+        let output = reader
+            .join()
+            .map_err(|_| self.failed("output reader panicked"))?
+            .map_err(|e| self.failed(format!("output could not be read: {}", e)))?;
+        hex::decode(output.trim()).ok_or_else(|| self.failed("printed no hex key"))
+    }
+
+    fn name(&self) -> &'static str {
+        "command"
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn hook(script: &str, timeout: u32) -> CommandHook {
+        let command = ["sh", "-c", script, "hook"].map(String::from).to_vec();
+        CommandHook::new(command, timeout).unwrap()
+    }
+
+    #[test]
+    fn command_receives_level_and_seed() {
+        let hook = hook(r#"[ "$1" = 3 ] && [ "$2" = 1234ABCD ] && echo 0102"#, 2000);
+        assert_eq!(hook.compute_key(3, &[0x12, 0x34, 0xAB, 0xCD]).unwrap(), [0x01, 0x02]);
+    }
+
+    #[test]
+    fn output_larger_than_the_pipe_buffer_is_read() {
+        // 256 KiB of hex digits
+        let hook = hook("yes AB | tr -d '\\n' | head -c 262144", 5000);
+        let key = hook.compute_key(1, &[0x00]).unwrap();
+        assert!(!key.is_empty() && key.iter().all(|&b| b == 0xAB));
+    }
+
+    #[test]
+    fn failures_are_internal_errors() {
+        let cases = [
+            hook("exit 1", 2000),
+            hook("sleep 5", 100),
+            hook("echo not-hex", 2000),
+        ];
+        for hook in cases {
+            assert!(matches!(
+                hook.compute_key(1, &[0x00]),
+                Err(Sovd2UdsError::Internal(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Seed/key algorithms for SecurityAccess (0x27)
+
+pub mod algorithms;
+pub mod external;
+
+pub use algorithms::{AesCmac, Crc32, XorMask};
+pub use external::{CommandHook, SharedLibrary};
+
+use crate::config::{SecurityConfig, SeedKeyAlgorithmConfig};
+use crate::error::Result;
+use std::sync::Arc;
+
+/// Computes the key an ECU expects for a seed
+///
+/// `level` is the security level as configured in `security.security_level`;
+/// the ECU is asked for the seed with sub-function `level * 2 - 1`.
+pub trait SeedKeyAlgorithm: Send + Sync {
+    fn compute_key(&self, level: u8, seed: &[u8]) -> Result<Vec<u8>>;
+
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+}
+
+/// Build the algorithm described by a configuration entry
+pub fn build_algorithm(config: &SeedKeyAlgorithmConfig) -> Result<Arc<dyn SeedKeyAlgorithm>> {
+    Ok(match config {
+        SeedKeyAlgorithmConfig::Xor { mask } => Arc::new(XorMask::from_hex(mask)?),
+        SeedKeyAlgorithmConfig::Crc32 { secret } => Arc::new(Crc32::from_hex(secret)?),
+        SeedKeyAlgorithmConfig::AesCmac { key, key_length } => {
+            Arc::new(AesCmac::from_hex(key, *key_length)?)
+        }
+        SeedKeyAlgorithmConfig::Library { path, variant } => {
+            Arc::new(SharedLibrary::load(path, variant)?)
+        }
+        SeedKeyAlgorithmConfig::Command { command, timeout } => {
+            Arc::new(CommandHook::new(command.clone(), *timeout)?)
+        }
+    })
+}
+
+/// Seed/key algorithms of one component, by security level
+pub struct SeedKeyRegistry {
+    /// (level, algorithm, specificity); higher specificity wins
+    entries: Vec<(Option<u8>, Arc<dyn SeedKeyAlgorithm>, u8)>,
+    fallback: Arc<dyn SeedKeyAlgorithm>,
+}
+
+impl SeedKeyRegistry {
+    /// Collect the `security.algorithms` entries that apply to a component
+    ///
+    /// An entry naming the component beats one for all components, and an
+    /// entry naming the level beats one for all levels. Without a matching
+    /// entry the key is the seed XOR 0xAA.
+    pub fn for_component(config: &SecurityConfig, component_id: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for registration in &config.algorithms {
+            let specificity = match &registration.component {
+                Some(component) if component != component_id => continue,
+                Some(_) => 2,
+                None => 0,
+            } + registration.level.is_some() as u8;
+            entries.push((
+                registration.level,
+                build_algorithm(&registration.algorithm)?,
+                specificity,
+            ));
+        }
+
+        Ok(Self {
+            entries,
+            fallback: Arc::new(XorMask::new(vec![0xAA])),
+        })
+    }
+
+    /// Algorithm for a security level
+    pub fn algorithm(&self, level: u8) -> Arc<dyn SeedKeyAlgorithm> {
+        self.entries
+            .iter()
+            .filter(|(entry_level, _, _)| entry_level.is_none_or(|l| l == level))
+            .max_by_key(|(_, _, specificity)| *specificity)
+            .map(|(_, algorithm, _)| Arc::clone(algorithm))
+            .unwrap_or_else(|| Arc::clone(&self.fallback))
+    }
+}
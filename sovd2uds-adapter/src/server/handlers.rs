@@ -1,17 +1,20 @@
 use crate::config::Config;
-use crate::error::Sovd2UdsError;
+use super::middleware::{concurrency_limit, request_deadline};
+use crate::error::{Sovd2UdsError, UdsNegativeResponseCode};
 use crate::models::*;
-use crate::translation::SovdUdsTranslator;
-use crate::uds::UdsClientPool;
+use crate::translation::{hex, SovdUdsTranslator};
+use crate::uds::{SessionState, UdsClient, UdsClientPool};
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::Deserialize;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
 use tracing::{error, info};
 
 /// Application state shared across handlers
@@ -26,11 +29,23 @@ pub struct AppState {
 #[derive(Debug, Deserialize)]
 pub struct DataQuery {
     categories: Option<String>,
+    /// Read the items' values as well, with multi-DID requests
+    #[serde(default)]
+    include_values: bool,
+}
+
+/// Query parameters for the DTC query endpoint
+#[derive(Debug, Deserialize)]
+pub struct DtcQuery {
+    /// Status filter, e.g. "confirmed,pending"
+    status: Option<String>,
+    /// Comma-separated DTCs to report, e.g. "P0100-11,0x030000"
+    dtcs: Option<String>,
 }
 
 /// Create the API router
 pub fn create_router(state: AppState) -> Router {
-    Router::new()
+    let mut api = Router::new()
         .route("/api/v1/components", get(get_components))
         .route(
             "/api/v1/components/:component_id/data",
@@ -38,7 +53,7 @@ pub fn create_router(state: AppState) -> Router {
         )
         .route(
             "/api/v1/components/:component_id/data/:data_id",
-            get(get_data_item_value),
+            get(get_data_item_value).put(write_data_item_value),
         )
         .route(
             "/api/v1/components/:component_id/actuators/control",
@@ -46,13 +61,36 @@ pub fn create_router(state: AppState) -> Router {
         )
         .route(
             "/api/v1/components/:component_id/dtcs",
-            post(manage_dtcs),
+            get(get_dtcs).post(manage_dtcs),
         )
         .route(
             "/api/v1/components/:component_id/services",
             post(execute_service),
         )
-        .route("/health", get(health_check))
+        .route(
+            "/api/v1/components/:component_id/session",
+            get(get_session_state),
+        )
+        .route(
+            "/api/v1/components/:component_id/locks",
+            get(get_security_access)
+                .post(unlock_security)
+                .delete(lock_security),
+        );
+
+    let max_concurrent_requests = state.config.performance.max_concurrent_requests;
+    if max_concurrent_requests > 0 {
+        api = api.layer(middleware::from_fn_with_state(
+            Arc::new(Semaphore::new(max_concurrent_requests)),
+            concurrency_limit,
+        ));
+    }
+
+    api.route("/health", get(health_check))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_deadline,
+        ))
         .with_state(state)
 }
 
@@ -89,9 +127,46 @@ async fn get_component_data(
         .as_ref()
         .map(|s| s.split(',').map(|c| c.trim().to_string()).collect::<Vec<_>>());
 
-    let items = state.translator.get_component_data_items(categories.as_deref());
+    let items = state
+        .translator
+        .get_component_data_items(&component_id, categories.as_deref())?;
+    if !query.include_values {
+        return Ok(Json(DataItemsResponse {
+            items,
+            values: None,
+            errors: None,
+        }));
+    }
 
-    Ok(Json(DataItemsResponse { items }))
+    let client = state.client_pool.get_client(&component_id).await?;
+    let item_ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+    let results = state
+        .translator
+        .read_data_items(
+            &client,
+            &item_ids,
+            state.config.uds.max_dids_per_request,
+            state.config.uds.max_response_length,
+        )
+        .await;
+
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for (id, result) in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(e) => errors.push(DataItemError {
+                id,
+                error: error_response(&e).1,
+            }),
+        }
+    }
+
+    Ok(Json(DataItemsResponse {
+        items,
+        values: Some(values),
+        errors: Some(errors),
+    }))
 }
 
 /// Get specific data item value
@@ -113,6 +188,37 @@ async fn get_data_item_value(
     Ok(Json(value))
 }
 
+/// Write a data item value and return the value read back
+async fn write_data_item_value(
+    State(state): State<AppState>,
+    Path((component_id, data_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<DataItemWriteRequest>,
+) -> Result<Json<DataItemValue>, AppError> {
+    info!(
+        "Writing data item '{}' for component '{}'",
+        data_id, component_id
+    );
+
+    // Writing unlocks the item's security level or the configured one, which needs the token
+    if state.config.security.require_security_access
+        || state
+            .translator
+            .write_security_level(&component_id, &data_id)?
+            .is_some()
+    {
+        authorize(&state.config, &headers)?;
+    }
+    let client = state.client_pool.get_client(&component_id).await?;
+
+    let value = state
+        .translator
+        .write_data_item(&client, &data_id, &request.data)
+        .await?;
+
+    Ok(Json(value))
+}
+
 /// Control actuator
 async fn control_actuator(
     State(state): State<AppState>,
@@ -133,6 +239,37 @@ async fn control_actuator(
     Ok(Json(response))
 }
 
+/// Read the DTCs of a component, optionally filtered by status
+async fn get_dtcs(
+    State(state): State<AppState>,
+    Path(component_id): Path<String>,
+    Query(query): Query<DtcQuery>,
+) -> Result<Json<DtcManagementResponse>, AppError> {
+    info!(
+        "Reading DTCs for component '{}' (status filter: {:?})",
+        component_id, query.status
+    );
+
+    let request = DtcManagementRequest {
+        action: "read".to_string(),
+        status: query.status,
+        dtcs: query
+            .dtcs
+            .map(|dtcs| {
+                dtcs.split(',')
+                    .map(str::trim)
+                    .filter(|dtc| !dtc.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+        ..Default::default()
+    };
+    let client = state.client_pool.get_client(&component_id).await?;
+    let response = state.translator.manage_dtcs(&client, &request).await?;
+
+    Ok(Json(response))
+}
+
 /// Manage DTCs
 async fn manage_dtcs(
     State(state): State<AppState>,
@@ -153,10 +290,151 @@ async fn manage_dtcs(
     Ok(Json(response))
 }
 
+/// Get the diagnostic session state of a component
+async fn get_session_state(
+    State(state): State<AppState>,
+    Path(component_id): Path<String>,
+) -> Result<Json<SessionStateResponse>, AppError> {
+    info!("Getting session state for component: {}", component_id);
+
+    // Without an open connection the ECU is reported in its default session
+    let (session, keep_alive) = match connected_client(&state, &component_id).await? {
+        Some(client) => (client.session_state(), client.keep_alive_active()),
+        None => (SessionState::new(), false),
+    };
+
+    Ok(Json(SessionStateResponse {
+        session: state.translator.session_name(&component_id, session.session),
+        component_id,
+        session_id: format!("0x{:02X}", session.session),
+        security_level: session.security_level,
+        session_since: session.session_since,
+        security_since: session.security_since,
+        last_activity: session.last_activity,
+        keep_alive,
+    }))
+}
+
+/// Get the security access state of a component
+async fn get_security_access(
+    State(state): State<AppState>,
+    Path(component_id): Path<String>,
+) -> Result<Json<SecurityAccessResponse>, AppError> {
+    info!("Getting security access state for component: {}", component_id);
+
+    // Without an open connection no security level is unlocked
+    let status = match connected_client(&state, &component_id).await? {
+        Some(client) => security_access_state(component_id, &client),
+        None => SecurityAccessResponse {
+            component_id,
+            unlocked: false,
+            level: None,
+            unlocked_since: None,
+            remaining_attempts: state.config.security.max_attempts,
+            retry_after_ms: None,
+        },
+    };
+
+    Ok(Json(status))
+}
+
+/// Client of a registered component if a connection is already open; status
+/// reads use it so they never connect to the ECU
+async fn connected_client(
+    state: &AppState,
+    component_id: &str,
+) -> Result<Option<Arc<UdsClient>>, Sovd2UdsError> {
+    if state.config.get_component(component_id).is_none() {
+        return Err(Sovd2UdsError::ComponentNotFound(component_id.to_string()));
+    }
+    Ok(state.client_pool.existing_client(component_id).await)
+}
+
+/// Unlock a security level
+async fn unlock_security(
+    State(state): State<AppState>,
+    Path(component_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<SecurityAccessRequest>,
+) -> Result<Json<SecurityAccessResponse>, AppError> {
+    info!(
+        "Unlocking security level {} on component '{}'",
+        request.level, component_id
+    );
+
+    authorize(&state.config, &headers)?;
+    let level = security_level(request.level)?;
+    let client = state.client_pool.get_client(&component_id).await?;
+    client.unlock_security(level).await?;
+
+    Ok(Json(security_access_state(component_id, &client)))
+}
+
+/// Release security access
+async fn lock_security(
+    State(state): State<AppState>,
+    Path(component_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<SecurityAccessResponse>, AppError> {
+    info!("Releasing security access on component '{}'", component_id);
+
+    authorize(&state.config, &headers)?;
+    let client = state.client_pool.get_client(&component_id).await?;
+    client.lock_security().await?;
+
+    Ok(Json(security_access_state(component_id, &client)))
+}
+
+fn security_access_state(component_id: String, client: &UdsClient) -> SecurityAccessResponse {
+    let session = client.session_state();
+    SecurityAccessResponse {
+        component_id,
+        unlocked: session.security_level.is_some(),
+        level: session.security_level,
+        unlocked_since: session.security_since,
+        remaining_attempts: client.remaining_security_attempts(),
+        retry_after_ms: session
+            .retry_after()
+            .map(|left| left.num_milliseconds().max(0) as u64),
+    }
+}
+
+/// Security level from a request; levels map to sub-functions 0x01-0x7E
+fn security_level(level: u8) -> Result<u8, Sovd2UdsError> {
+    if security_levels::is_valid(level) {
+        Ok(level)
+    } else {
+        Err(Sovd2UdsError::InvalidRequest(format!(
+            "Invalid security level: {}",
+            level
+        )))
+    }
+}
+
+/// Check the bearer token required for security access and other state-changing
+/// operations, if one is configured
+fn authorize(config: &Config, headers: &HeaderMap) -> Result<(), Sovd2UdsError> {
+    let Some(token) = config.security.api_token.as_deref() else {
+        return Ok(());
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if presented == Some(token) {
+        Ok(())
+    } else {
+        Err(Sovd2UdsError::Unauthorized(
+            "A valid bearer token is required for this operation".to_string(),
+        ))
+    }
+}
+
 /// Execute generic service
 async fn execute_service(
     State(state): State<AppState>,
     Path(component_id): Path<String>,
+    headers: HeaderMap,
     Json(request): Json<ServiceRequest>,
 ) -> Result<Json<ServiceResponse>, AppError> {
     info!(
@@ -180,17 +458,13 @@ async fn execute_service(
                     Sovd2UdsError::InvalidRequest("Missing session_type parameter".to_string())
                 })? as u8;
 
-            let session_type_enum = match session_type {
-                0x01 => DiagnosticSessionType::DefaultSession,
-                0x02 => DiagnosticSessionType::ProgrammingSession,
-                0x03 => DiagnosticSessionType::ExtendedDiagnosticSession,
-                0x04 => DiagnosticSessionType::SafetySystemDiagnosticSession,
-                _ => {
-                    return Err(AppError::from(Sovd2UdsError::InvalidRequest(
-                        format!("Invalid session type: 0x{:02X}", session_type),
-                    )))
-                }
-            };
+            let session_type_enum =
+                DiagnosticSessionType::from_u8(session_type).ok_or_else(|| {
+                    Sovd2UdsError::InvalidRequest(format!(
+                        "Invalid session type: 0x{:02X}",
+                        session_type
+                    ))
+                })?;
 
             client.diagnostic_session_control(session_type_enum).await?;
 
@@ -235,6 +509,93 @@ async fn execute_service(
                 timestamp: Some(chrono::Utc::now()),
             }
         }
+        "security_access" => {
+            authorize(&state.config, &headers)?;
+            let level = security_level(narrow(required_u64(&request, "level")?, "level")?)?;
+
+            client.unlock_security(level).await?;
+
+            let status = security_access_state(component_id, &client);
+            service_success(
+                &request,
+                Some(serde_json::to_value(status).map_err(Sovd2UdsError::from)?),
+                "Security access granted",
+            )
+        }
+        "read_memory" => {
+            let address = required_u64(&request, "address")?;
+            let size = required_u64(&request, "size")?;
+
+            let data = client
+                .read_memory_by_address(MemoryRegion::new(address, size))
+                .await?;
+
+            service_success(
+                &request,
+                Some(serde_json::json!({ "data": hex::encode(&data) })),
+                "Memory read successfully",
+            )
+        }
+        "write_memory" => {
+            authorize(&state.config, &headers)?;
+            let address = required_u64(&request, "address")?;
+            let data = required_hex(&request, "data")?;
+
+            client.write_memory_by_address(address, &data).await?;
+
+            service_success(&request, None, "Memory written successfully")
+        }
+        "control_dtc_setting" => {
+            authorize(&state.config, &headers)?;
+            let setting = match required_str(&request, "setting")? {
+                "on" => DtcSettingType::On,
+                "off" => DtcSettingType::Off,
+                other => {
+                    return Err(AppError::from(Sovd2UdsError::InvalidRequest(format!(
+                        "Invalid DTC setting: {}",
+                        other
+                    ))))
+                }
+            };
+
+            client.control_dtc_setting(setting).await?;
+
+            service_success(&request, None, "DTC setting changed")
+        }
+        "communication_control" => {
+            authorize(&state.config, &headers)?;
+            let control_type = match required_u64(&request, "control_type")? {
+                0x00 => CommunicationControlType::EnableRxAndTx,
+                0x01 => CommunicationControlType::EnableRxAndDisableTx,
+                0x02 => CommunicationControlType::DisableRxAndEnableTx,
+                0x03 => CommunicationControlType::DisableRxAndTx,
+                other => {
+                    return Err(AppError::from(Sovd2UdsError::InvalidRequest(format!(
+                        "Invalid control type: 0x{:02X}",
+                        other
+                    ))))
+                }
+            };
+            let communication_type = parameter(&request, "communication_type")
+                .and_then(|v| v.as_u64())
+                .map(|value| narrow(value, "communication_type"))
+                .transpose()?
+                .unwrap_or(communication_types::NORMAL);
+
+            client
+                .communication_control(control_type, communication_type)
+                .await?;
+
+            service_success(&request, None, "Communication control executed")
+        }
+        "dynamically_define_did" => {
+            authorize(&state.config, &headers)?;
+            let definition = parse_dynamic_did_definition(&request)?;
+
+            client.dynamically_define_data_identifier(&definition).await?;
+
+            service_success(&request, None, "Dynamic data identifier updated")
+        }
         _ => {
             return Err(AppError::from(Sovd2UdsError::InvalidRequest(format!(
                 "Unknown service type: {}",
@@ -246,6 +607,100 @@ async fn execute_service(
     Ok(Json(response))
 }
 
+/// Successful service response
+fn service_success(
+    request: &ServiceRequest,
+    results: Option<serde_json::Value>,
+    message: &str,
+) -> ServiceResponse {
+    ServiceResponse {
+        service_type: request.service_type.clone(),
+        status: "success".to_string(),
+        results,
+        message: Some(message.to_string()),
+        timestamp: Some(chrono::Utc::now()),
+    }
+}
+
+fn parameter<'a>(request: &'a ServiceRequest, name: &str) -> Option<&'a serde_json::Value> {
+    request.parameters.as_ref().and_then(|p| p.get(name))
+}
+
+fn missing_parameter(name: &str) -> Sovd2UdsError {
+    Sovd2UdsError::InvalidRequest(format!("Missing {} parameter", name))
+}
+
+fn required_u64(request: &ServiceRequest, name: &str) -> Result<u64, Sovd2UdsError> {
+    parameter(request, name)
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| missing_parameter(name))
+}
+
+/// Convert a numeric parameter to a narrower type, rejecting values that do not fit
+fn narrow<T: TryFrom<u64>>(value: u64, name: &str) -> Result<T, Sovd2UdsError> {
+    T::try_from(value).map_err(|_| {
+        Sovd2UdsError::InvalidRequest(format!("Parameter {} is out of range: {}", name, value))
+    })
+}
+
+fn required_str<'a>(request: &'a ServiceRequest, name: &str) -> Result<&'a str, Sovd2UdsError> {
+    parameter(request, name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| missing_parameter(name))
+}
+
+fn required_hex(request: &ServiceRequest, name: &str) -> Result<Vec<u8>, Sovd2UdsError> {
+    let text = required_str(request, name)?;
+    hex::decode(text).ok_or_else(|| {
+        Sovd2UdsError::InvalidRequest(format!("Parameter {} is not valid hex: {}", name, text))
+    })
+}
+
+/// Parse `{"clear": true, "did"?}`, `{"did", "sources": [{"did", "position", "size"}]}`
+/// or `{"did", "regions": [{"address", "size"}]}`
+fn parse_dynamic_did_definition(
+    request: &ServiceRequest,
+) -> Result<DynamicDidDefinition, Sovd2UdsError> {
+    let field = |value: &serde_json::Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| missing_parameter(name))
+    };
+
+    if parameter(request, "clear").and_then(|v| v.as_bool()) == Some(true) {
+        let did = parameter(request, "did")
+            .and_then(|v| v.as_u64())
+            .map(|did| narrow(did, "did"))
+            .transpose()?;
+        return Ok(DynamicDidDefinition::Clear { did });
+    }
+
+    let did = narrow(required_u64(request, "did")?, "did")?;
+    if let Some(sources) = parameter(request, "sources").and_then(|v| v.as_array()) {
+        let sources = sources
+            .iter()
+            .map(|source| {
+                Ok(DidSource {
+                    did: narrow(field(source, "did")?, "did")?,
+                    position: narrow(field(source, "position")?, "position")?,
+                    size: narrow(field(source, "size")?, "size")?,
+                })
+            })
+            .collect::<Result<Vec<_>, Sovd2UdsError>>()?;
+        return Ok(DynamicDidDefinition::ByIdentifier { did, sources });
+    }
+    if let Some(regions) = parameter(request, "regions").and_then(|v| v.as_array()) {
+        let regions = regions
+            .iter()
+            .map(|region| Ok(MemoryRegion::new(field(region, "address")?, field(region, "size")?)))
+            .collect::<Result<Vec<_>, Sovd2UdsError>>()?;
+        return Ok(DynamicDidDefinition::ByMemoryAddress { did, regions });
+    }
+
+    Err(missing_parameter("sources or regions"))
+}
+
 /// Error wrapper for axum responses
 pub struct AppError(Sovd2UdsError);
 
@@ -255,24 +710,385 @@ impl From<Sovd2UdsError> for AppError {
     }
 }
 
+/// Seconds a client should wait before repeating a request the ECU or adapter was too busy for
+const BUSY_RETRY_AFTER_SECS: u64 = 1;
+
+/// HTTP status for a negative response to `service`
+fn nrc_status(service: u8, nrc: u8) -> StatusCode {
+    match UdsNegativeResponseCode::from_u8(nrc) {
+        Some(
+            UdsNegativeResponseCode::SecurityAccessDenied
+            | UdsNegativeResponseCode::InvalidKey
+            | UdsNegativeResponseCode::ExceedNumberOfAttempts
+            | UdsNegativeResponseCode::RequiredTimeDelayNotExpired,
+        ) => StatusCode::FORBIDDEN,
+        // Unknown identifier for lookups, invalid parameter otherwise
+        Some(UdsNegativeResponseCode::RequestOutOfRange) => match service {
+            0x14 | 0x19 | 0x22 | 0x24 | 0x2F | 0x31 => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_REQUEST,
+        },
+        Some(
+            UdsNegativeResponseCode::ConditionsNotCorrect
+            | UdsNegativeResponseCode::RequestSequenceError
+            | UdsNegativeResponseCode::SubFunctionNotSupportedInActiveSession
+            | UdsNegativeResponseCode::ServiceNotSupportedInActiveSession,
+        ) => StatusCode::CONFLICT,
+        Some(UdsNegativeResponseCode::BusyRepeatRequest) => StatusCode::SERVICE_UNAVAILABLE,
+        Some(
+            UdsNegativeResponseCode::ServiceNotSupported
+            | UdsNegativeResponseCode::SubFunctionNotSupported,
+        ) => StatusCode::NOT_IMPLEMENTED,
+        Some(UdsNegativeResponseCode::IncorrectMessageLengthOrInvalidFormat) => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// HTTP status and SOVD error body for an error
+fn error_response(error: &Sovd2UdsError) -> (StatusCode, ErrorResponse) {
+    let status = match error {
+        Sovd2UdsError::ComponentNotFound(_) => StatusCode::NOT_FOUND,
+        Sovd2UdsError::DataItemNotFound(_) => StatusCode::NOT_FOUND,
+        Sovd2UdsError::ActuatorNotFound(_) => StatusCode::NOT_FOUND,
+        Sovd2UdsError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        Sovd2UdsError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        Sovd2UdsError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+        Sovd2UdsError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+        Sovd2UdsError::UdsProtocol { service, nrc, .. } => nrc_status(*service, *nrc),
+        Sovd2UdsError::SecurityAccessDenied { .. } => StatusCode::FORBIDDEN,
+        Sovd2UdsError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let (nrc, nrc_description, service) = match error {
+        Sovd2UdsError::UdsProtocol {
+            service,
+            nrc,
+            description,
+        } => (
+            Some(format!("0x{:02X}", nrc)),
+            Some(description.clone()),
+            Some(format!("0x{:02X}", service)),
+        ),
+        Sovd2UdsError::SecurityAccessDenied {
+            nrc, description, ..
+        } => (
+            Some(format!("0x{:02X}", nrc)),
+            Some(description.clone()),
+            Some(format!("0x{:02X}", UdsServiceId::SecurityAccess as u8)),
+        ),
+        _ => (None, None, None),
+    };
+    let (remaining_attempts, retry_after_ms) = match error {
+        Sovd2UdsError::SecurityAccessDenied {
+            remaining_attempts,
+            retry_after,
+            ..
+        } => (Some(*remaining_attempts), *retry_after),
+        _ => (None, None),
+    };
+
+    let body = ErrorResponse {
+        error: error.to_string(),
+        code: status.as_u16(),
+        details: Some(format!("{:?}", error)),
+        nrc,
+        nrc_description,
+        service,
+        remaining_attempts,
+        retry_after_ms,
+    };
+    (status, body)
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self.0 {
-            Sovd2UdsError::ComponentNotFound(_) => (StatusCode::NOT_FOUND, self.0.to_string()),
-            Sovd2UdsError::DataItemNotFound(_) => (StatusCode::NOT_FOUND, self.0.to_string()),
-            Sovd2UdsError::InvalidRequest(_) => (StatusCode::BAD_REQUEST, self.0.to_string()),
-            Sovd2UdsError::Timeout(_) => (StatusCode::REQUEST_TIMEOUT, self.0.to_string()),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()),
+        let (status, body) = error_response(&self.0);
+
+        error!("Request error: {}", body.error);
+
+        let retry_after_ms = body.retry_after_ms;
+        let body = Json(body);
+
+        if let Some(retry_after_ms) = retry_after_ms {
+            let retry_after = [(header::RETRY_AFTER, retry_after_ms.div_ceil(1000).to_string())];
+            return (status, retry_after, body).into_response();
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            return (status, [(header::WWW_AUTHENTICATE, "Bearer")], body).into_response();
+        }
+
+        if status == StatusCode::SERVICE_UNAVAILABLE || status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = [(header::RETRY_AFTER, BUSY_RETRY_AFTER_SECS.to_string())];
+            return (status, retry_after, body).into_response();
+        }
+
+        (status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SeedKeyAlgorithmConfig, SeedKeyRegistration, TransportKind};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use tower::Service;
+
+    /// Router whose components are served by the ECU simulator
+    fn app(mut config: Config) -> Router {
+        config.uds.transport = Some(TransportKind::Simulator);
+        let config = Arc::new(config);
+        create_router(AppState {
+            translator: Arc::new(SovdUdsTranslator::from_config(&config).unwrap()),
+            client_pool: Arc::new(UdsClientPool::new(Arc::clone(&config))),
+            config,
+        })
+    }
+
+    fn request(method: &str, uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn call(app: &mut Router, request: Request<Body>) -> (StatusCode, HeaderMap, Value) {
+        let response = app.call(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        (status, headers, body)
+    }
+
+    #[test]
+    fn negative_responses_map_to_http_statuses() {
+        let cases = [
+            (0x2E, 0x33, StatusCode::FORBIDDEN),
+            (0x27, 0x35, StatusCode::FORBIDDEN),
+            (0x27, 0x37, StatusCode::FORBIDDEN),
+            (0x22, 0x31, StatusCode::NOT_FOUND),
+            (0x31, 0x31, StatusCode::NOT_FOUND),
+            (0x2E, 0x31, StatusCode::BAD_REQUEST),
+            (0x31, 0x22, StatusCode::CONFLICT),
+            (0x2E, 0x7F, StatusCode::CONFLICT),
+            (0x22, 0x21, StatusCode::SERVICE_UNAVAILABLE),
+            (0x85, 0x12, StatusCode::NOT_IMPLEMENTED),
+            (0x22, 0x13, StatusCode::BAD_REQUEST),
+            (0x22, 0x10, StatusCode::BAD_GATEWAY),
+            (0x22, 0xF0, StatusCode::BAD_GATEWAY),
+        ];
+        for (service, nrc, status) in cases {
+            let (actual, _) = error_response(&Sovd2UdsError::negative_response(service, nrc));
+            assert_eq!(actual, status, "service 0x{:02X}, NRC 0x{:02X}", service, nrc);
+        }
+    }
+
+    #[test]
+    fn negative_response_body_names_the_nrc_and_service() {
+        let (status, body) = error_response(&Sovd2UdsError::negative_response(0x2E, 0x33));
+        assert_eq!(body.code, status.as_u16());
+        assert_eq!(body.nrc.as_deref(), Some("0x33"));
+        assert_eq!(body.service.as_deref(), Some("0x2E"));
+        assert_eq!(body.nrc_description.as_deref(), Some("Security access denied"));
+    }
+
+    #[test]
+    fn busy_ecu_asks_to_retry_later() {
+        let response = AppError(Sovd2UdsError::negative_response(0x22, 0x21)).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        let error = Sovd2UdsError::SecurityAccessDenied {
+            level: 1,
+            nrc: 0x37,
+            description: "Required time delay not expired".to_string(),
+            remaining_attempts: 0,
+            retry_after: Some(2500),
         };
+        let response = AppError(error).into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+    }
+
+    #[tokio::test]
+    async fn ecu_negative_responses_reach_the_client() {
+        let mut config = Config::default();
+        config.simulator.ecu.dids.retain(|did| did.id != 0xF191);
+        let mut app = app(config);
 
-        error!("Request error: {}", error_message);
+        let uri = "/api/v1/components/engine/data/ecu_hardware_version";
+        let (status, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["nrc"], "0x31");
+        assert_eq!(body["service"], "0x22");
 
-        let body = Json(ErrorResponse {
-            error: error_message.clone(),
-            code: status.as_u16(),
-            details: Some(format!("{:?}", self.0)),
+        let read = json!({
+            "service_type": "read_memory",
+            "parameters": { "address": 0x2000, "size": 4 }
         });
+        let uri = "/api/v1/components/engine/services";
+        let (status, _, body) = call(&mut app, request("POST", uri, read)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["nrc"], "0x31");
+        assert_eq!(body["service"], "0x23");
+    }
 
-        (status, body).into_response()
+    fn with_token(mut request: Request<Body>) -> Request<Body> {
+        let token = "Bearer secret".parse().unwrap();
+        request.headers_mut().insert(header::AUTHORIZATION, token);
+        request
+    }
+
+    async fn enter_extended_session(app: &mut Router) {
+        let session = json!({
+            "service_type": "session_control",
+            "parameters": { "session_type": 0x03 }
+        });
+        let uri = "/api/v1/components/engine/services";
+        let (status, _, _) = call(app, request("POST", uri, session)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn locks_unlock_report_and_release_security_access() {
+        let mut config = Config::default();
+        config.security.api_token = Some("secret".to_string());
+        let mut app = app(config);
+        let uri = "/api/v1/components/engine/locks";
+
+        let (status, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["unlocked"], false);
+        assert_eq!(body["remaining_attempts"], 3);
+
+        let unlock = || request("POST", uri, json!({ "level": 1 }));
+        let (status, headers, _) = call(&mut app, unlock()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers[header::WWW_AUTHENTICATE], "Bearer");
+
+        enter_extended_session(&mut app).await;
+        let (status, _, body) = call(&mut app, with_token(unlock())).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["unlocked"], true);
+        assert_eq!(body["level"], 1);
+
+        let (_, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(body["unlocked"], true);
+
+        let (status, _, body) = call(&mut app, with_token(request("DELETE", uri, Value::Null))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["unlocked"], false);
+
+        let uri = "/api/v1/components/gearbox/locks";
+        let (status, _, _) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn rejected_keys_report_remaining_attempts_and_lockout() {
+        let mut config = Config::default();
+        config.security.lockout_delay = 2000;
+        config.simulator.ecu.security_delay = 2000;
+        // The ECU expects keys masked with 0xAA
+        config.security.algorithms.push(SeedKeyRegistration {
+            component: None,
+            level: None,
+            algorithm: SeedKeyAlgorithmConfig::Xor {
+                mask: "55".to_string(),
+            },
+        });
+        let mut app = app(config);
+        enter_extended_session(&mut app).await;
+        let uri = "/api/v1/components/engine/locks";
+        let unlock = || request("POST", uri, json!({ "level": 1 }));
+
+        for (nrc, remaining_attempts) in [("0x35", 2), ("0x35", 1), ("0x36", 0)] {
+            let (status, _, body) = call(&mut app, unlock()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(body["nrc"], nrc);
+            assert_eq!(body["remaining_attempts"], remaining_attempts);
+        }
+
+        let (status, headers, body) = call(&mut app, unlock()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["nrc"], "0x37");
+        assert_eq!(headers[header::RETRY_AFTER], "2");
+        assert!(body["retry_after_ms"].as_u64().unwrap() <= 2000);
+
+        let (_, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(body["remaining_attempts"], 0);
+        assert!(body["retry_after_ms"].as_u64().is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_components_and_items_are_not_found() {
+        let mut config = Config::default();
+        let items = "items: [{ id: oil_temperature, did: 0x0110, data_type: number, length: 1 }]";
+        let path = crate::translation::dictionary::test_file("engine-catalog.yaml", items);
+        config.components.get_mut("engine").unwrap().dictionaries.push(path);
+        let mut app = app(config);
+
+        let uri = "/api/v1/components/engine/data";
+        let (status, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|item| item["id"].as_str())
+            .collect();
+        assert!(ids.contains(&"oil_temperature") && ids.contains(&"vin"), "{:?}", ids);
+
+        for uri in [
+            "/api/v1/components/gearbox/data",
+            "/api/v1/components/gearbox/data/vin",
+            "/api/v1/components/abs/data/oil_temperature",
+        ] {
+            let (status, _, _) = call(&mut app, request("GET", uri, Value::Null)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn dtcs_are_filtered_by_status() {
+        let mut config = Config::default();
+        config.simulator.ecu.dtc_status_availability_mask = 0x2F;
+        let mut app = app(config);
+
+        let codes = |body: &Value| -> Vec<String> {
+            body["results"]["dtcs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|dtc| dtc["code"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let uri = "/api/v1/components/engine/dtcs?status=confirmed";
+        let (status, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(codes(&body), ["P0100-11"]);
+        let dtc = &body["results"]["dtcs"][0];
+        assert_eq!(dtc["status"]["confirmedDTC"], true);
+        assert_eq!(dtc["status"]["pendingDTC"], false);
+        let availability = &body["results"]["status_availability_mask"];
+        assert_eq!(availability["mask"], "0x2F");
+
+        let uri = "/api/v1/components/engine/dtcs?status=pending";
+        let (_, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(codes(&body), ["P0300-00"]);
+
+        let uri = "/api/v1/components/engine/dtcs";
+        let (_, _, body) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(codes(&body).len(), 2);
+
+        let uri = "/api/v1/components/engine/dtcs?status=sleepy";
+        let (status, _, _) = call(&mut app, request("GET", uri, Value::Null)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 }
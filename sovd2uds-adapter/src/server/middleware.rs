@@ -0,0 +1,89 @@
+use super::handlers::{AppError, AppState};
+use crate::error::Sovd2UdsError;
+use crate::uds::deadline;
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Header overriding the request deadline, in milliseconds
+pub const TIMEOUT_HEADER: &str = "x-request-timeout";
+
+/// Bound every API call by a deadline
+///
+/// The deadline is the `timeout` query parameter or `X-Request-Timeout`
+/// header (milliseconds), else `server.request_timeout` (seconds, 0 disables
+/// it). The handler is not cancelled: UDS requests made by it stop waiting at
+/// the deadline and fail with `Timeout`, answered with 504 Gateway Timeout,
+/// while cleanup such as restoring a temporarily entered session still runs.
+pub async fn request_deadline(
+    State(state): State<AppState>,
+    Query(query): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limit = match requested_timeout(&query, request.headers()) {
+        Ok(Some(limit)) => limit,
+        Ok(None) if state.config.server.request_timeout == 0 => return next.run(request).await,
+        Ok(None) => Duration::from_secs(state.config.server.request_timeout),
+        Err(e) => return AppError::from(e).into_response(),
+    };
+
+    let path = request.uri().path().to_string();
+    let deadline = Instant::now() + limit;
+    let response = deadline::with_deadline(deadline, next.run(request)).await;
+    if response.status() == StatusCode::GATEWAY_TIMEOUT {
+        warn!("{} exceeded its {} ms deadline", path, limit.as_millis());
+    }
+    response
+}
+
+/// Reject requests beyond `performance.max_concurrent_requests` with 429
+pub async fn concurrency_limit(
+    State(permits): State<Arc<Semaphore>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match permits.try_acquire() {
+        Ok(_permit) => next.run(request).await,
+        Err(_) => {
+            warn!(
+                "Rejecting {}: too many concurrent requests",
+                request.uri().path()
+            );
+            AppError::from(Sovd2UdsError::TooManyRequests(
+                "Maximum number of concurrent requests reached".to_string(),
+            ))
+            .into_response()
+        }
+    }
+}
+
+/// Per-request timeout from the query string or header
+fn requested_timeout(
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+) -> Result<Option<Duration>, Sovd2UdsError> {
+    let value = match (query.get("timeout"), headers.get(TIMEOUT_HEADER)) {
+        (Some(value), _) => value.as_str(),
+        (None, Some(value)) => value.to_str().map_err(|_| invalid_timeout())?,
+        (None, None) => return Ok(None),
+    };
+
+    match value.trim().parse::<u64>() {
+        Ok(millis) if millis > 0 => Ok(Some(Duration::from_millis(millis))),
+        _ => Err(invalid_timeout()),
+    }
+}
+
+fn invalid_timeout() -> Sovd2UdsError {
+    Sovd2UdsError::InvalidRequest("timeout must be a positive number of milliseconds".to_string())
+}
@@ -1,3 +1,4 @@
 pub mod handlers;
+pub mod middleware;
 
 pub use handlers::{create_router, AppState};
@@ -0,0 +1,1010 @@
+use crate::config::SimulatedEcuConfig;
+use crate::error::{Result, Sovd2UdsError, UdsNegativeResponseCode as Nrc};
+use crate::models::uds::{DiagnosticSessionType, UdsRequest};
+use crate::security::{self, SeedKeyAlgorithm};
+use crate::translation::hex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// A DID as stored in the virtual ECU
+struct Did {
+    value: Vec<u8>,
+    writable: bool,
+    security_level: Option<u8>,
+}
+
+/// A routine and its latest state
+struct Routine {
+    result: Vec<u8>,
+    security_level: Option<u8>,
+    duration: Duration,
+    running: bool,
+    started: bool,
+}
+
+/// A stored DTC and its records
+struct Dtc {
+    code: u32,
+    status: u8,
+    /// Snapshot record numbers and the DIDs they hold
+    snapshots: Vec<(u8, Vec<u16>)>,
+    extended_data: BTreeMap<u8, Vec<u8>>,
+    fault_detection_counter: i8,
+    severity: u8,
+    permanent: bool,
+}
+
+/// Part of a dynamically defined DID
+enum DynamicSource {
+    Did {
+        did: u16,
+        position: usize,
+        size: usize,
+    },
+    Memory {
+        address: usize,
+        size: usize,
+    },
+}
+
+/// Mutable state of the virtual ECU
+struct EcuState {
+    session: u8,
+    /// Unlocked security level: (requestSeed sub-function + 1) / 2
+    unlocked_level: Option<u8>,
+    /// Security level and seed of an outstanding requestSeed
+    pending_seed: Option<(u8, Vec<u8>)>,
+    failed_attempts: u8,
+    locked_until: Option<Instant>,
+    last_activity: Instant,
+    dids: BTreeMap<u16, Did>,
+    dtcs: Vec<Dtc>,
+    routines: HashMap<u16, Routine>,
+    dynamic_dids: HashMap<u16, Vec<DynamicSource>>,
+    memory: Vec<u8>,
+    dtc_setting_enabled: bool,
+    communication_control: u8,
+}
+
+/// Simulated ECU answering raw UDS requests
+///
+/// Implements the session and security access state machines, DID
+/// read/write (including dynamically defined DIDs), memory access, DTC
+/// reporting, clearing and setting control, communication control and
+/// routine control on top of a `SimulatedEcuConfig`. Keys are checked with
+/// the configured seed/key algorithm (seed XOR 0xAA by default, matching the
+/// adapter's fallback).
+pub struct VirtualEcu {
+    config: SimulatedEcuConfig,
+    seed_key: Arc<dyn SeedKeyAlgorithm>,
+    state: Mutex<EcuState>,
+}
+
+type Response = std::result::Result<Vec<u8>, Nrc>;
+
+/// DTC severity bits reported by WWH-OBD requests
+const WWH_OBD_SEVERITY_AVAILABILITY: u8 = 0xE0;
+/// DTCFormatIdentifier of WWH-OBD responses (SAE J2012-DA DTC format 04)
+const WWH_OBD_DTC_FORMAT: u8 = 0x04;
+
+impl VirtualEcu {
+    /// Build a virtual ECU from its configuration
+    pub fn new(config: SimulatedEcuConfig) -> Result<Self> {
+        let mut dids = BTreeMap::new();
+        for did in &config.dids {
+            let value = match (&did.ascii, &did.hex) {
+                (Some(text), None) => text.as_bytes().to_vec(),
+                (None, Some(hex)) => parse_hex(hex)?,
+                _ => {
+                    return Err(Sovd2UdsError::Config(format!(
+                        "Simulated DID 0x{:04X} needs exactly one of 'ascii' or 'hex'",
+                        did.id
+                    )))
+                }
+            };
+            dids.insert(
+                did.id,
+                Did {
+                    value,
+                    writable: did.writable,
+                    security_level: did.security_level,
+                },
+            );
+        }
+
+        let mut routines = HashMap::new();
+        for routine in &config.routines {
+            let result = match &routine.result {
+                Some(hex) => parse_hex(hex)?,
+                None => Vec::new(),
+            };
+            routines.insert(
+                routine.id,
+                Routine {
+                    result,
+                    security_level: routine.security_level,
+                    duration: Duration::from_millis(routine.duration as u64),
+                    running: false,
+                    started: false,
+                },
+            );
+        }
+
+        let mut dtcs = Vec::new();
+        for dtc in &config.dtcs {
+            let mut extended_data = BTreeMap::new();
+            for record in &dtc.extended_data {
+                extended_data.insert(record.record, parse_hex(&record.hex)?);
+            }
+            dtcs.push(Dtc {
+                code: dtc.code & 0xFF_FFFF,
+                status: dtc.status,
+                snapshots: dtc
+                    .snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.record, snapshot.dids.clone()))
+                    .collect(),
+                extended_data,
+                fault_detection_counter: dtc.fault_detection_counter,
+                severity: dtc.severity,
+                permanent: dtc.permanent,
+            });
+        }
+
+        Ok(Self {
+            seed_key: security::build_algorithm(&config.seed_key)?,
+            state: Mutex::new(EcuState {
+                session: DiagnosticSessionType::DefaultSession as u8,
+                unlocked_level: None,
+                pending_seed: None,
+                failed_attempts: 0,
+                locked_until: None,
+                last_activity: Instant::now(),
+                dids,
+                dtcs,
+                routines,
+                dynamic_dids: HashMap::new(),
+                memory: vec![0; config.memory_size as usize],
+                dtc_setting_enabled: true,
+                communication_control: 0x00,
+            }),
+            config,
+        })
+    }
+
+    /// Delay applied before every response
+    pub fn response_delay(&self) -> Duration {
+        Duration::from_millis(self.config.response_delay as u64)
+    }
+
+    /// P2* server time, the longest gap between response pending messages
+    pub fn p2_star(&self) -> Duration {
+        Duration::from_millis(self.config.p2_star as u64)
+    }
+
+    /// Time the ECU needs before it can answer a request positively
+    ///
+    /// Only starting a routine with a configured duration takes time; the
+    /// tester sees response pending (NRC 0x78) until it completes.
+    pub fn processing_time(&self, request: &[u8]) -> Duration {
+        match request {
+            [0x31, control_type, high, low, ..] if control_type & 0x7F == 0x01 => {
+                let state = self.state.lock().unwrap();
+                state
+                    .routines
+                    .get(&u16::from_be_bytes([*high, *low]))
+                    .map_or(Duration::ZERO, |routine| routine.duration)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Active diagnostic session
+    pub fn session(&self) -> u8 {
+        let mut state = self.state.lock().unwrap();
+        self.expire_session(&mut state);
+        state.session
+    }
+
+    /// Handle a raw request; `None` when the positive response is suppressed
+    pub fn handle(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let request = UdsRequest::from_bytes(request)?;
+        let (sid, params) = (request.service_id, &request.data[..]);
+        let mut state = self.state.lock().unwrap();
+        self.expire_session(&mut state);
+        state.last_activity = Instant::now();
+
+        let suppress = request.suppress_positive_response();
+        let result = match sid {
+            0x10 => self.session_control(&mut state, params),
+            0x11 => self.ecu_reset(&mut state, params),
+            0x27 => self.security_access(&mut state, params),
+            0x3E => self.tester_present(params),
+            0x22 => self.read_data(&state, params),
+            0x2E => self.write_data(&mut state, params),
+            0x19 => self.read_dtc_information(&state, params),
+            0x14 => self.clear_dtcs(&mut state, params),
+            0x31 => self.routine_control(&mut state, params),
+            0x23 => self.read_memory(&state, params),
+            0x3D => self.write_memory(&mut state, params),
+            0x2C => self.define_dynamic_did(&mut state, params),
+            0x85 => self.control_dtc_setting(&mut state, params),
+            0x28 => self.communication_control(&mut state, params),
+            _ => Err(Nrc::ServiceNotSupported),
+        };
+
+        match result {
+            Ok(_) if suppress => None,
+            Ok(data) => {
+                let mut response = vec![sid + 0x40];
+                response.extend_from_slice(&data);
+                Some(response)
+            }
+            Err(nrc) => {
+                debug!("Virtual ECU rejects 0x{:02X}: {}", sid, nrc.description());
+                Some(vec![0x7F, sid, nrc as u8])
+            }
+        }
+    }
+
+    /// Fall back to the default session once S3 has elapsed
+    fn expire_session(&self, state: &mut EcuState) {
+        let s3 = Duration::from_millis(self.config.s3_timeout as u64);
+        if state.session != DiagnosticSessionType::DefaultSession as u8
+            && state.last_activity.elapsed() > s3
+        {
+            debug!("Virtual ECU S3 timeout, returning to default session");
+            enter_session(state, DiagnosticSessionType::DefaultSession as u8);
+        }
+    }
+
+    fn session_control(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let [sub_function] = params else {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        };
+        let session = sub_function & 0x7F;
+        if !(0x01..=0x04).contains(&session) {
+            return Err(Nrc::SubFunctionNotSupported);
+        }
+        enter_session(state, session);
+
+        let mut response = vec![session];
+        response.extend_from_slice(&self.config.p2.to_be_bytes());
+        response.extend_from_slice(&((self.config.p2_star / 10) as u16).to_be_bytes());
+        Ok(response)
+    }
+
+    fn ecu_reset(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let [sub_function] = params else {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        };
+        let reset_type = sub_function & 0x7F;
+        if !(0x01..=0x03).contains(&reset_type) {
+            return Err(Nrc::SubFunctionNotSupported);
+        }
+        enter_session(state, DiagnosticSessionType::DefaultSession as u8);
+        for routine in state.routines.values_mut() {
+            routine.running = false;
+        }
+        Ok(vec![reset_type])
+    }
+
+    fn security_access(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let (&sub_function, key) = params
+            .split_first()
+            .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+        let access_type = sub_function & 0x7F;
+        let level = access_type.div_ceil(2);
+        if state.session == DiagnosticSessionType::DefaultSession as u8 {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+
+        if access_type % 2 == 1 {
+            if !self.config.security_levels.contains(&access_type) {
+                return Err(Nrc::SubFunctionNotSupported);
+            }
+            if !key.is_empty() {
+                return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+            }
+            if state
+                .locked_until
+                .is_some_and(|until| Instant::now() < until)
+            {
+                return Err(Nrc::RequiredTimeDelayNotExpired);
+            }
+            if state.unlocked_level == Some(level) {
+                // Already unlocked: zero seed
+                return Ok(vec![access_type, 0x00, 0x00, 0x00, 0x00]);
+            }
+            let seed = generate_seed();
+            state.pending_seed = Some((level, seed.clone()));
+            let mut response = vec![access_type];
+            response.extend_from_slice(&seed);
+            return Ok(response);
+        }
+
+        let seed = match state.pending_seed.take() {
+            Some((pending_level, seed)) if pending_level == level => seed,
+            _ => return Err(Nrc::RequestSequenceError),
+        };
+        let expected = self
+            .seed_key
+            .compute_key(level, &seed)
+            .map_err(|_| Nrc::GeneralReject)?;
+        if key != expected.as_slice() {
+            state.failed_attempts += 1;
+            if state.failed_attempts >= self.config.max_key_attempts {
+                state.failed_attempts = 0;
+                state.locked_until =
+                    Some(Instant::now() + Duration::from_millis(self.config.security_delay as u64));
+                return Err(Nrc::ExceedNumberOfAttempts);
+            }
+            return Err(Nrc::InvalidKey);
+        }
+
+        state.failed_attempts = 0;
+        state.unlocked_level = Some(level);
+        Ok(vec![access_type])
+    }
+
+    fn tester_present(&self, params: &[u8]) -> Response {
+        match params {
+            [sub_function] if sub_function & 0x7F == 0x00 => Ok(vec![0x00]),
+            [_] => Err(Nrc::SubFunctionNotSupported),
+            _ => Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        }
+    }
+
+    fn read_data(&self, state: &EcuState, params: &[u8]) -> Response {
+        if params.is_empty() || !params.len().is_multiple_of(2) {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        let mut response = Vec::new();
+        for chunk in params.chunks(2) {
+            let id = u16::from_be_bytes([chunk[0], chunk[1]]);
+            // Unsupported DIDs are skipped; the request fails only if none is supported
+            if let Some(did) = state.dids.get(&id) {
+                response.extend_from_slice(chunk);
+                response.extend_from_slice(&did.value);
+            } else if let Some(sources) = state.dynamic_dids.get(&id) {
+                response.extend_from_slice(chunk);
+                for source in sources {
+                    response.extend_from_slice(dynamic_source_value(state, source));
+                }
+            }
+        }
+
+        if response.is_empty() {
+            return Err(Nrc::RequestOutOfRange);
+        }
+        Ok(response)
+    }
+
+    fn write_data(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        if params.len() < 3 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let id = u16::from_be_bytes([params[0], params[1]]);
+        let unlocked_level = state.unlocked_level;
+        let in_default_session = state.session == DiagnosticSessionType::DefaultSession as u8;
+
+        let did = state
+            .dids
+            .get_mut(&id)
+            .filter(|did| did.writable)
+            .ok_or(Nrc::RequestOutOfRange)?;
+        if in_default_session {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+        if did.security_level.is_some() && did.security_level != unlocked_level {
+            return Err(Nrc::SecurityAccessDenied);
+        }
+        if params.len() - 2 != did.value.len() {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        did.value = params[2..].to_vec();
+        Ok(params[..2].to_vec())
+    }
+
+    fn read_dtc_information(&self, state: &EcuState, params: &[u8]) -> Response {
+        let (&sub_function, rest) = params
+            .split_first()
+            .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+        let sub_function = sub_function & 0x7F;
+        let availability = self.config.dtc_status_availability_mask;
+
+        match sub_function {
+            // reportNumberOfDTCByStatusMask
+            0x01 => {
+                let [mask] = rest else {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                };
+                let count = state
+                    .dtcs
+                    .iter()
+                    .filter(|dtc| dtc.status & availability & mask != 0)
+                    .count() as u16;
+                let mut response = vec![sub_function, availability, 0x01];
+                response.extend_from_slice(&count.to_be_bytes());
+                Ok(response)
+            }
+            // reportDTCByStatusMask
+            0x02 => {
+                let [mask] = rest else {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                };
+                let mut response = vec![sub_function, availability];
+                for dtc in &state.dtcs {
+                    if dtc.status & availability & mask != 0 {
+                        response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                        response.push(dtc.status & availability);
+                    }
+                }
+                Ok(response)
+            }
+            // reportDTCSnapshotIdentification
+            0x03 => {
+                if !rest.is_empty() {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                }
+                let mut response = vec![sub_function];
+                for dtc in &state.dtcs {
+                    for (record, _) in &dtc.snapshots {
+                        response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                        response.push(*record);
+                    }
+                }
+                Ok(response)
+            }
+            // reportDTCSnapshotRecordByDTCNumber
+            0x04 => {
+                let (dtc, record) = dtc_record(state, rest)?;
+                let mut response = vec![sub_function];
+                response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                response.push(dtc.status & availability);
+                let mut found = false;
+                for (number, dids) in &dtc.snapshots {
+                    if record != 0xFF && record != *number {
+                        continue;
+                    }
+                    found = true;
+                    response.push(*number);
+                    response.push(dids.len() as u8);
+                    for did in dids {
+                        response.extend_from_slice(&did.to_be_bytes());
+                        if let Some(stored) = state.dids.get(did) {
+                            response.extend_from_slice(&stored.value);
+                        }
+                    }
+                }
+                if !found && record != 0xFF {
+                    return Err(Nrc::RequestOutOfRange);
+                }
+                Ok(response)
+            }
+            // reportDTCExtDataRecordByDTCNumber
+            0x06 => {
+                let (dtc, record) = dtc_record(state, rest)?;
+                let mut response = vec![sub_function];
+                response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                response.push(dtc.status & availability);
+                // 0xFF requests all records, 0xFE all OBD records
+                let all = record >= 0xFE;
+                if !all && !dtc.extended_data.contains_key(&record) {
+                    return Err(Nrc::RequestOutOfRange);
+                }
+                for (number, data) in &dtc.extended_data {
+                    if all || record == *number {
+                        response.push(*number);
+                        response.extend_from_slice(data);
+                    }
+                }
+                Ok(response)
+            }
+            // reportSupportedDTC
+            0x0A => {
+                if !rest.is_empty() {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                }
+                let mut response = vec![sub_function, availability];
+                for dtc in &state.dtcs {
+                    response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                    response.push(dtc.status & availability);
+                }
+                Ok(response)
+            }
+            // reportDTCFaultDetectionCounter: prefailed DTCs only
+            0x14 => {
+                if !rest.is_empty() {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                }
+                let mut response = vec![sub_function];
+                for dtc in &state.dtcs {
+                    if (1..127).contains(&dtc.fault_detection_counter) {
+                        response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                        response.push(dtc.fault_detection_counter as u8);
+                    }
+                }
+                Ok(response)
+            }
+            // reportWWHOBDDTCByMaskRecord
+            0x42 => {
+                let [group, status_mask, severity_mask] = rest else {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                };
+                let mut response = vec![
+                    sub_function,
+                    *group,
+                    availability,
+                    WWH_OBD_SEVERITY_AVAILABILITY,
+                    WWH_OBD_DTC_FORMAT,
+                ];
+                for dtc in &state.dtcs {
+                    let severity = dtc.severity & severity_mask;
+                    if dtc.status & availability & status_mask != 0 && severity != 0 {
+                        response.push(dtc.severity);
+                        response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                        response.push(dtc.status & availability);
+                    }
+                }
+                Ok(response)
+            }
+            // reportWWHOBDDTCWithPermanentStatus
+            0x55 => {
+                let [group] = rest else {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                };
+                let mut response = vec![sub_function, *group, availability, WWH_OBD_DTC_FORMAT];
+                for dtc in state.dtcs.iter().filter(|dtc| dtc.permanent) {
+                    response.extend_from_slice(&dtc.code.to_be_bytes()[1..]);
+                    response.push(dtc.status & availability);
+                }
+                Ok(response)
+            }
+            _ => Err(Nrc::SubFunctionNotSupported),
+        }
+    }
+
+    fn clear_dtcs(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let [high, middle, low] = params else {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        };
+        let group = u32::from_be_bytes([0, *high, *middle, *low]);
+
+        if group == 0xFF_FFFF {
+            state.dtcs.clear();
+        } else if state.dtcs.iter().any(|dtc| dtc.code == group) {
+            state.dtcs.retain(|dtc| dtc.code != group);
+        } else {
+            return Err(Nrc::RequestOutOfRange);
+        }
+        Ok(Vec::new())
+    }
+
+    fn routine_control(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        if params.len() < 3 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let control_type = params[0] & 0x7F;
+        let id = u16::from_be_bytes([params[1], params[2]]);
+        let unlocked_level = state.unlocked_level;
+        let in_default_session = state.session == DiagnosticSessionType::DefaultSession as u8;
+
+        let routine = state.routines.get_mut(&id).ok_or(Nrc::RequestOutOfRange)?;
+        if in_default_session {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+        if routine.security_level.is_some() && routine.security_level != unlocked_level {
+            return Err(Nrc::SecurityAccessDenied);
+        }
+
+        let mut response = params[..3].to_vec();
+        response[0] = control_type;
+        match control_type {
+            0x01 => {
+                routine.running = true;
+                routine.started = true;
+                response.extend_from_slice(&routine.result);
+            }
+            0x02 => {
+                if !routine.running {
+                    return Err(Nrc::RequestSequenceError);
+                }
+                routine.running = false;
+            }
+            0x03 => {
+                if !routine.started {
+                    return Err(Nrc::RequestSequenceError);
+                }
+                response.extend_from_slice(&routine.result);
+            }
+            _ => return Err(Nrc::SubFunctionNotSupported),
+        }
+        Ok(response)
+    }
+
+    fn read_memory(&self, state: &EcuState, params: &[u8]) -> Response {
+        let (address, size, rest) = parse_memory_region(params)?;
+        if !rest.is_empty() {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let range = memory_range(state, address, size)?;
+        Ok(state.memory[range].to_vec())
+    }
+
+    fn write_memory(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        if state.session == DiagnosticSessionType::DefaultSession as u8 {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+        let (address, size, data) = parse_memory_region(params)?;
+        if data.len() != size {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let range = memory_range(state, address, size)?;
+        state.memory[range].copy_from_slice(data);
+        Ok(params[..params.len() - data.len()].to_vec())
+    }
+
+    fn define_dynamic_did(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let (&sub_function, rest) = params
+            .split_first()
+            .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+        let sub_function = sub_function & 0x7F;
+
+        if sub_function == 0x03 {
+            match rest {
+                [] => state.dynamic_dids.clear(),
+                [high, low] => {
+                    state
+                        .dynamic_dids
+                        .remove(&u16::from_be_bytes([*high, *low]))
+                        .ok_or(Nrc::RequestOutOfRange)?;
+                }
+                _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+            }
+            let mut response = vec![sub_function];
+            response.extend_from_slice(rest);
+            return Ok(response);
+        }
+
+        if rest.len() < 2 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let did = u16::from_be_bytes([rest[0], rest[1]]);
+        if state.dids.contains_key(&did) {
+            return Err(Nrc::RequestOutOfRange);
+        }
+
+        let mut sources = Vec::new();
+        match sub_function {
+            // defineByIdentifier
+            0x01 => {
+                let definitions = &rest[2..];
+                if definitions.is_empty() || !definitions.len().is_multiple_of(4) {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                }
+                for definition in definitions.chunks(4) {
+                    let source = u16::from_be_bytes([definition[0], definition[1]]);
+                    let (position, size) = (definition[2] as usize, definition[3] as usize);
+                    let value = &state.dids.get(&source).ok_or(Nrc::RequestOutOfRange)?.value;
+                    if position == 0 || position - 1 + size > value.len() {
+                        return Err(Nrc::RequestOutOfRange);
+                    }
+                    sources.push(DynamicSource::Did {
+                        did: source,
+                        position,
+                        size,
+                    });
+                }
+            }
+            // defineByMemoryAddress
+            0x02 => {
+                let (&format, mut regions) = rest[2..]
+                    .split_first()
+                    .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+                let entry_length = (format >> 4) as usize + (format & 0x0F) as usize;
+                if regions.is_empty() || !regions.len().is_multiple_of(entry_length) {
+                    return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+                }
+                while !regions.is_empty() {
+                    let mut entry = vec![format];
+                    entry.extend_from_slice(&regions[..entry_length]);
+                    let (address, size, _) = parse_memory_region(&entry)?;
+                    memory_range(state, address, size)?;
+                    sources.push(DynamicSource::Memory { address, size });
+                    regions = &regions[entry_length..];
+                }
+            }
+            _ => return Err(Nrc::SubFunctionNotSupported),
+        }
+
+        state.dynamic_dids.entry(did).or_default().extend(sources);
+        Ok(vec![sub_function, rest[0], rest[1]])
+    }
+
+    fn control_dtc_setting(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let (&sub_function, _record) = params
+            .split_first()
+            .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+        if state.session == DiagnosticSessionType::DefaultSession as u8 {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+        let setting = sub_function & 0x7F;
+        state.dtc_setting_enabled = match setting {
+            0x01 => true,
+            0x02 => false,
+            _ => return Err(Nrc::SubFunctionNotSupported),
+        };
+        Ok(vec![setting])
+    }
+
+    fn communication_control(&self, state: &mut EcuState, params: &[u8]) -> Response {
+        let [sub_function, communication_type] = params else {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        };
+        if state.session == DiagnosticSessionType::DefaultSession as u8 {
+            return Err(Nrc::ServiceNotSupportedInActiveSession);
+        }
+        let control_type = sub_function & 0x7F;
+        if control_type > 0x03 {
+            return Err(Nrc::SubFunctionNotSupported);
+        }
+        if !(0x01..=0x03).contains(&(communication_type & 0x0F)) {
+            return Err(Nrc::RequestOutOfRange);
+        }
+        state.communication_control = control_type;
+        Ok(vec![control_type])
+    }
+}
+
+/// Parse addressAndLengthFormatIdentifier, memoryAddress and memorySize,
+/// returning the remaining bytes
+fn parse_memory_region(params: &[u8]) -> std::result::Result<(usize, usize, &[u8]), Nrc> {
+    let (&format, rest) = params
+        .split_first()
+        .ok_or(Nrc::IncorrectMessageLengthOrInvalidFormat)?;
+    let address_length = (format & 0x0F) as usize;
+    let size_length = (format >> 4) as usize;
+    if address_length == 0 || size_length == 0 || address_length > 8 || size_length > 8 {
+        return Err(Nrc::RequestOutOfRange);
+    }
+    if rest.len() < address_length + size_length {
+        return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+    }
+
+    let read = |bytes: &[u8]| bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+    let address = read(&rest[..address_length]);
+    let size = read(&rest[address_length..address_length + size_length]);
+    Ok((
+        address as usize,
+        size as usize,
+        &rest[address_length + size_length..],
+    ))
+}
+
+fn memory_range(
+    state: &EcuState,
+    address: usize,
+    size: usize,
+) -> std::result::Result<std::ops::Range<usize>, Nrc> {
+    match address.checked_add(size) {
+        Some(end) if size > 0 && end <= state.memory.len() => Ok(address..end),
+        _ => Err(Nrc::RequestOutOfRange),
+    }
+}
+
+fn dynamic_source_value<'a>(state: &'a EcuState, source: &DynamicSource) -> &'a [u8] {
+    match source {
+        DynamicSource::Did {
+            did,
+            position,
+            size,
+        } => state
+            .dids
+            .get(did)
+            .and_then(|did| did.value.get(position - 1..position - 1 + size))
+            .unwrap_or_default(),
+        DynamicSource::Memory { address, size } => &state.memory[*address..address + size],
+    }
+}
+
+/// Stored DTC and record number addressed by a DTC record request
+fn dtc_record<'a>(
+    state: &'a EcuState,
+    params: &[u8],
+) -> std::result::Result<(&'a Dtc, u8), Nrc> {
+    let [high, middle, low, record] = params else {
+        return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+    };
+    let code = u32::from_be_bytes([0, *high, *middle, *low]);
+    let dtc = state
+        .dtcs
+        .iter()
+        .find(|dtc| dtc.code == code)
+        .ok_or(Nrc::RequestOutOfRange)?;
+    Ok((dtc, *record))
+}
+
+/// Switch session; any session change relocks security access
+fn enter_session(state: &mut EcuState, session: u8) {
+    state.session = session;
+    state.unlocked_level = None;
+    state.pending_seed = None;
+    if session == DiagnosticSessionType::DefaultSession as u8 {
+        state.dtc_setting_enabled = true;
+        state.communication_control = 0x00;
+    }
+}
+
+fn generate_seed() -> Vec<u8> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0x1234_5678);
+    // Never hand out the all-zero "already unlocked" seed
+    (nanos | 0x0100_0000).to_be_bytes().to_vec()
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    hex::decode(text).ok_or_else(|| Sovd2UdsError::Config(format!("Invalid hex value '{}'", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ecu(configure: impl FnOnce(&mut SimulatedEcuConfig)) -> VirtualEcu {
+        let mut config = SimulatedEcuConfig::default();
+        configure(&mut config);
+        VirtualEcu::new(config).unwrap()
+    }
+
+    fn request(ecu: &VirtualEcu, bytes: &[u8]) -> Vec<u8> {
+        ecu.handle(bytes).expect("a response")
+    }
+
+    /// Request a seed for `level` and answer it with the default key (seed XOR 0xAA)
+    fn unlock(ecu: &VirtualEcu, level: u8) -> Vec<u8> {
+        let response = request(ecu, &[0x27, level * 2 - 1]);
+        assert_eq!(response[..2], [0x67, level * 2 - 1]);
+        let key: Vec<u8> = response[2..].iter().map(|b| b ^ 0xAA).collect();
+        request(ecu, &[&[0x27, level * 2][..], &key].concat())
+    }
+
+    fn write_vin(ecu: &VirtualEcu) -> Vec<u8> {
+        request(
+            ecu,
+            &[&[0x2E, 0xF1, 0x90][..], b"WVWZZZ1JZXW000002"].concat(),
+        )
+    }
+
+    #[test]
+    fn session_transitions() {
+        let ecu = ecu(|_| {});
+        assert_eq!(ecu.session(), 0x01);
+
+        // P2 50 ms and P2* 5000 ms (in 10 ms units)
+        assert_eq!(
+            request(&ecu, &[0x10, 0x03]),
+            [0x50, 0x03, 0x00, 0x32, 0x01, 0xF4]
+        );
+        assert_eq!(ecu.session(), 0x03);
+        assert_eq!(request(&ecu, &[0x10, 0x05]), [0x7F, 0x10, 0x12]);
+        assert_eq!(request(&ecu, &[0x10]), [0x7F, 0x10, 0x13]);
+        assert_eq!(ecu.session(), 0x03);
+
+        assert_eq!(ecu.handle(&[0x10, 0x82]), None);
+        assert_eq!(ecu.session(), 0x02);
+
+        assert_eq!(request(&ecu, &[0x11, 0x01]), [0x51, 0x01]);
+        assert_eq!(ecu.session(), 0x01);
+    }
+
+    #[test]
+    fn session_falls_back_after_s3() {
+        let ecu = ecu(|config| config.s3_timeout = 20);
+        request(&ecu, &[0x10, 0x03]);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(ecu.session(), 0x01);
+
+        // TesterPresent keeps the session
+        request(&ecu, &[0x10, 0x03]);
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(10));
+            assert_eq!(request(&ecu, &[0x3E, 0x00]), [0x7E, 0x00]);
+        }
+        assert_eq!(ecu.session(), 0x03);
+    }
+
+    #[test]
+    fn unlock_and_relock_on_session_change() {
+        let ecu = ecu(|_| {});
+        assert_eq!(request(&ecu, &[0x27, 0x01]), [0x7F, 0x27, 0x7F]);
+
+        request(&ecu, &[0x10, 0x03]);
+        assert_eq!(write_vin(&ecu), [0x7F, 0x2E, 0x33]);
+        // Only level 1 is configured, and a key needs a seed first
+        assert_eq!(request(&ecu, &[0x27, 0x03]), [0x7F, 0x27, 0x12]);
+        assert_eq!(request(&ecu, &[0x27, 0x02, 0x00]), [0x7F, 0x27, 0x24]);
+
+        assert_eq!(unlock(&ecu, 1), [0x67, 0x02]);
+        assert_eq!(write_vin(&ecu), [0x6E, 0xF1, 0x90]);
+        // Unlocked levels answer with a zero seed
+        assert_eq!(request(&ecu, &[0x27, 0x01]), [0x67, 0x01, 0, 0, 0, 0]);
+
+        // Re-entering even the active session locks again
+        request(&ecu, &[0x10, 0x03]);
+        assert_eq!(write_vin(&ecu), [0x7F, 0x2E, 0x33]);
+        assert_eq!(unlock(&ecu, 1), [0x67, 0x02]);
+        request(&ecu, &[0x11, 0x01]);
+        request(&ecu, &[0x10, 0x03]);
+        assert_eq!(write_vin(&ecu), [0x7F, 0x2E, 0x33]);
+    }
+
+    #[test]
+    fn higher_levels_use_their_own_sub_functions() {
+        let ecu = ecu(|config| config.security_levels = vec![0x01, 0x05]);
+        request(&ecu, &[0x10, 0x03]);
+        assert_eq!(unlock(&ecu, 3), [0x67, 0x06]);
+        // Level 3 does not grant level 1
+        assert_eq!(write_vin(&ecu), [0x7F, 0x2E, 0x33]);
+        assert_eq!(unlock(&ecu, 1), [0x67, 0x02]);
+        assert_eq!(write_vin(&ecu), [0x6E, 0xF1, 0x90]);
+    }
+
+    #[test]
+    fn invalid_keys_lock_security_access_out() {
+        let ecu = ecu(|config| config.security_delay = 30);
+        request(&ecu, &[0x10, 0x03]);
+
+        let invalid_key = |ecu: &VirtualEcu| {
+            request(ecu, &[0x27, 0x01]);
+            request(ecu, &[0x27, 0x02, 0x00, 0x00, 0x00, 0x00])
+        };
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x35]);
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x35]);
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x36]);
+        assert_eq!(request(&ecu, &[0x27, 0x01]), [0x7F, 0x27, 0x37]);
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x35]);
+        // A valid key resets the counter
+        assert_eq!(unlock(&ecu, 1), [0x67, 0x02]);
+        request(&ecu, &[0x10, 0x03]);
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x35]);
+        assert_eq!(invalid_key(&ecu), [0x7F, 0x27, 0x35]);
+    }
+
+    #[test]
+    fn dtcs_are_reported_and_cleared() {
+        let ecu = ecu(|_| {});
+        assert_eq!(
+            request(&ecu, &[0x19, 0x02, 0xFF]),
+            [0x59, 0x02, 0xFF, 0x01, 0x00, 0x11, 0x09, 0x03, 0x00, 0x00, 0x24]
+        );
+        // Only the first DTC is confirmed (bit 3)
+        assert_eq!(
+            request(&ecu, &[0x19, 0x01, 0x08]),
+            [0x59, 0x01, 0xFF, 0x01, 0x00, 0x01]
+        );
+        assert_eq!(
+            request(&ecu, &[0x19, 0x02, 0x08]),
+            [0x59, 0x02, 0xFF, 0x01, 0x00, 0x11, 0x09]
+        );
+        assert_eq!(request(&ecu, &[0x19, 0x02]), [0x7F, 0x19, 0x13]);
+
+        assert_eq!(request(&ecu, &[0x14, 0x03, 0x00, 0x00]), [0x54]);
+        assert_eq!(
+            request(&ecu, &[0x19, 0x02, 0xFF]),
+            [0x59, 0x02, 0xFF, 0x01, 0x00, 0x11, 0x09]
+        );
+        assert_eq!(request(&ecu, &[0x14, 0x12, 0x34, 0x56]), [0x7F, 0x14, 0x31]);
+
+        assert_eq!(request(&ecu, &[0x14, 0xFF, 0xFF, 0xFF]), [0x54]);
+        assert_eq!(request(&ecu, &[0x19, 0x02, 0xFF]), [0x59, 0x02, 0xFF]);
+    }
+}
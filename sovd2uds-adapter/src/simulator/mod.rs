@@ -0,0 +1,19 @@
+pub mod ecu;
+pub mod transport;
+
+pub use ecu::VirtualEcu;
+pub use transport::SimulatorTransport;
+
+/// Client of component `component_id` whose ECU is simulated as `config`
+/// describes
+#[cfg(test)]
+pub(crate) async fn test_client(
+    mut config: crate::config::Config,
+    component_id: &str,
+) -> std::sync::Arc<crate::uds::UdsClient> {
+    config.uds.transport = Some(crate::config::TransportKind::Simulator);
+    crate::uds::UdsClientPool::new(std::sync::Arc::new(config))
+        .get_client(component_id)
+        .await
+        .unwrap()
+}
@@ -0,0 +1,112 @@
+use super::VirtualEcu;
+use crate::error::{Result, Sovd2UdsError, UdsNegativeResponseCode};
+use crate::uds::UdsTransport;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::{sleep, Instant};
+
+/// A response tagged with the exchange (request) it answers
+type TaggedResponse = (u64, Vec<u8>);
+
+/// UDS transport delivering requests to an in-process virtual ECU
+pub struct SimulatorTransport {
+    ecu: VirtualEcu,
+    /// Incremented on every request; responses to earlier requests are stale
+    exchange: AtomicU64,
+    responses_tx: mpsc::UnboundedSender<TaggedResponse>,
+    responses_rx: Mutex<mpsc::UnboundedReceiver<TaggedResponse>>,
+}
+
+impl SimulatorTransport {
+    pub fn new(ecu: VirtualEcu) -> Self {
+        let (responses_tx, responses_rx) = mpsc::unbounded_channel();
+        Self {
+            ecu,
+            exchange: AtomicU64::new(0),
+            responses_tx,
+            responses_rx: Mutex::new(responses_rx),
+        }
+    }
+
+    /// The simulated ECU behind this transport
+    pub fn ecu(&self) -> &VirtualEcu {
+        &self.ecu
+    }
+}
+
+#[async_trait]
+impl UdsTransport for SimulatorTransport {
+    async fn connect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn send(&self, request: &[u8]) -> Result<()> {
+        let exchange = self.exchange.fetch_add(1, Ordering::SeqCst) + 1;
+        let Some(response) = self.ecu.handle(request) else {
+            return Ok(());
+        };
+
+        let processing_time = self.ecu.processing_time(request);
+        if response[0] == 0x7F || processing_time.is_zero() {
+            let _ = self.responses_tx.send((exchange, response));
+            return Ok(());
+        }
+
+        // Keep the tester waiting with response pending until the ECU is done
+        let pending = vec![
+            0x7F,
+            request[0],
+            UdsNegativeResponseCode::RequestCorrectlyReceivedResponsePending as u8,
+        ];
+        let interval = (self.ecu.p2_star() / 2).max(Duration::from_millis(1));
+        let done = Instant::now() + processing_time;
+        let responses = self.responses_tx.clone();
+        tokio::spawn(async move {
+            while Instant::now() < done {
+                if responses.send((exchange, pending.clone())).is_err() {
+                    return;
+                }
+                sleep(interval.min(done - Instant::now())).await;
+            }
+            let _ = responses.send((exchange, response));
+        });
+        Ok(())
+    }
+
+    async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+        let delay = self.ecu.response_delay();
+        if delay >= timeout {
+            // The response arrives after the tester gave up and is discarded as stale
+            sleep(timeout).await;
+            return Err(Sovd2UdsError::Timeout(
+                "No response from virtual ECU".to_string(),
+            ));
+        }
+        sleep(delay).await;
+
+        let current = self.exchange.load(Ordering::SeqCst);
+        let mut responses = self.responses_rx.lock().await;
+        let deadline = Instant::now() + (timeout - delay);
+        loop {
+            match tokio::time::timeout_at(deadline, responses.recv()).await {
+                Ok(Some((exchange, response))) if exchange == current => return Ok(response),
+                Ok(Some(_)) => continue,
+                _ => {
+                    return Err(Sovd2UdsError::Timeout(
+                        "No response from virtual ECU".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "simulator"
+    }
+}
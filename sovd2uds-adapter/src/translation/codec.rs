@@ -0,0 +1,967 @@
+//! Coding of values in UDS data: integers, floats, conversions to physical
+//! values, bit fields, BCD and structs
+
+use super::hex;
+use crate::error::{Result, Sovd2UdsError};
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Byte order of numeric values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+/// Type of a coded value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    /// ASCII / UTF-8 text
+    #[default]
+    String,
+    /// Unsigned integer
+    #[serde(alias = "unsigned", alias = "uint")]
+    Number,
+    /// Two's complement integer
+    #[serde(alias = "int")]
+    Signed,
+    /// IEEE 754 float of 4 or 8 bytes
+    Float,
+    Boolean,
+    /// Bytes as a hex string
+    Hex,
+    /// Packed BCD digits, as a string of digits
+    Bcd,
+    /// BCD date, YYMMDD (3 bytes) or YYYYMMDD (4 bytes), as "YYYY-MM-DD"
+    BcdDate,
+    /// Fields, as a JSON object
+    Struct,
+}
+
+impl DataType {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Signed => "signed",
+            Self::Float => "float",
+            Self::Boolean => "boolean",
+            Self::Hex => "hex",
+            Self::Bcd => "bcd",
+            Self::BcdDate => "bcd_date",
+            Self::Struct => "struct",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Number | Self::Signed | Self::Float | Self::Boolean
+        )
+    }
+}
+
+/// Conversion of raw numbers to physical values, besides `scale` and `offset`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Conversion {
+    /// physical = (n0 + n1*raw + n2*raw^2 + ...) / (d0 + d1*raw + ...)
+    Rational {
+        numerator: Vec<f64>,
+        #[serde(default = "default_denominator")]
+        denominator: Vec<f64>,
+    },
+    /// Raw values or ranges of raw values shown as texts
+    TextTable { entries: Vec<TextTableEntry> },
+}
+
+fn default_denominator() -> Vec<f64> {
+    vec![1.0]
+}
+
+/// Text of the raw values `value..=max`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextTableEntry {
+    pub value: i64,
+    #[serde(default)]
+    pub max: Option<i64>,
+    pub text: String,
+}
+
+impl TextTableEntry {
+    fn contains(&self, raw: f64) -> bool {
+        (self.value as f64..=self.max.unwrap_or(self.value) as f64).contains(&raw)
+    }
+}
+
+/// A field of a struct value
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldDefinition {
+    pub name: String,
+    /// Offset of the field in the struct; fields follow each other when unset
+    #[serde(default)]
+    pub byte_position: Option<usize>,
+    #[serde(flatten)]
+    pub codec: Codec,
+}
+
+/// How a value is coded in UDS data
+///
+/// Used for data items, struct fields and routine parameters; the fields are
+/// part of the enclosing definition in dictionaries.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Codec {
+    #[serde(default)]
+    pub data_type: DataType,
+    /// Length of the value in bytes, when fixed
+    #[serde(default)]
+    pub length: Option<usize>,
+    /// Bit fields: first bit of the value, counted from the least significant
+    /// bit of its bytes
+    #[serde(default)]
+    pub bit_position: u32,
+    /// Bit fields: number of bits of the value
+    #[serde(default)]
+    pub bit_length: Option<u32>,
+    /// Byte order of numbers
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+    /// Physical value = raw * scale + offset (numbers only)
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    /// Rational or text table conversion, replacing `scale` and `offset`
+    #[serde(default)]
+    pub conversion: Option<Conversion>,
+    /// Unit of the physical value, e.g. "km/h"
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Fields of struct values
+    #[serde(default)]
+    pub fields: Vec<FieldDefinition>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Self::new(DataType::default())
+    }
+}
+
+/// Raw number read from or written to UDS data
+#[derive(Debug, Clone, Copy)]
+enum Raw {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+}
+
+impl Raw {
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Unsigned(raw) => raw as f64,
+            Self::Signed(raw) => raw as f64,
+            Self::Float(raw) => raw,
+        }
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            Self::Unsigned(raw) => Value::from(raw),
+            Self::Signed(raw) => Value::from(raw),
+            Self::Float(raw) => Value::from(raw),
+        }
+    }
+}
+
+impl Codec {
+    /// Unscaled value of a type, without fixed length
+    pub fn new(data_type: DataType) -> Self {
+        Self {
+            data_type,
+            length: None,
+            bit_position: 0,
+            bit_length: None,
+            byte_order: ByteOrder::default(),
+            scale: default_scale(),
+            offset: 0.0,
+            conversion: None,
+            unit: None,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Whether numbers are scaled to physical values
+    pub fn is_scaled(&self) -> bool {
+        self.scale != 1.0 || self.offset != 0.0
+    }
+
+    /// Bytes a value takes, when fixed
+    pub fn byte_length(&self) -> Option<usize> {
+        if let Some(length) = self.length {
+            return Some(length);
+        }
+        if let Some(bits) = self.bit_length {
+            return Some((self.bit_position + bits).div_ceil(8) as usize);
+        }
+        match self.data_type {
+            DataType::Boolean => Some(1),
+            DataType::Struct => self.fields.iter().try_fold(0, |end, field| {
+                let start = field.byte_position.unwrap_or(end);
+                Some(end.max(start + field.codec.byte_length()?))
+            }),
+            _ => None,
+        }
+    }
+
+    /// Check the definition for contradictions
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.length == Some(0) {
+            return Err("length is 0".to_string());
+        }
+        if let Some(bits) = self.bit_length {
+            if !self.data_type.is_numeric() || self.data_type == DataType::Float {
+                return Err("bit_length requires an integer or boolean type".to_string());
+            }
+            if bits == 0 || self.bit_position + bits > 64 {
+                return Err("bit fields must lie within 64 bits".to_string());
+            }
+        }
+        match self.data_type {
+            DataType::Float if !matches!(self.length, Some(4) | Some(8)) => {
+                return Err("float values need length 4 or 8".to_string());
+            }
+            DataType::Number | DataType::Signed if self.length.is_some_and(|l| l > 8) => {
+                return Err("integers are at most 8 bytes".to_string());
+            }
+            DataType::BcdDate if !matches!(self.length, None | Some(3) | Some(4)) => {
+                return Err("BCD dates need length 3 or 4".to_string());
+            }
+            DataType::Struct if self.fields.is_empty() => {
+                return Err("struct values need fields".to_string());
+            }
+            _ => {}
+        }
+        if self.conversion.is_some() && !self.data_type.is_numeric() {
+            return Err("conversions require a numeric type".to_string());
+        }
+        if let Some(Conversion::Rational { denominator, .. }) = &self.conversion {
+            if denominator.is_empty() {
+                return Err("rational conversions need a denominator".to_string());
+            }
+        }
+
+        let last = self.fields.len().saturating_sub(1);
+        for (index, field) in self.fields.iter().enumerate() {
+            field
+                .codec
+                .validate()
+                .map_err(|e| format!("field '{}': {}", field.name, e))?;
+            if index != last && field.codec.byte_length().is_none() {
+                return Err(format!("field '{}' needs a fixed length", field.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode UDS data to a JSON value
+    pub fn decode(&self, data: &[u8]) -> Result<Value> {
+        match self.data_type {
+            DataType::String => Ok(Value::String(
+                String::from_utf8(data.to_vec()).unwrap_or_else(|_| hex::encode(data)),
+            )),
+            DataType::Hex => Ok(Value::String(hex::encode(data))),
+            DataType::Bcd => Ok(Value::String(decode_bcd(data)?)),
+            DataType::BcdDate => decode_bcd_date(data).map(Value::String),
+            DataType::Struct => self.decode_struct(data),
+            DataType::Boolean => Ok(Value::Bool(self.read_bits(data)? != 0)),
+            // Integers of unexpected lengths are kept as bytes
+            DataType::Number | DataType::Signed if data.is_empty() || data.len() > 8 => {
+                Ok(Value::String(hex::encode(data)))
+            }
+            DataType::Number | DataType::Signed | DataType::Float => {
+                let raw = self.read_raw(data)?;
+                Ok(self.physical(raw))
+            }
+        }
+    }
+
+    /// Encode a JSON value to UDS data
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>> {
+        let data = match self.data_type {
+            DataType::String => self.expect(value.as_str(), value)?.as_bytes().to_vec(),
+            DataType::Hex => {
+                let text = self.expect(value.as_str(), value)?;
+                self.expect(hex::decode(text), value)?
+            }
+            DataType::Bcd => encode_bcd(self.expect(value.as_str(), value)?, self.length)?,
+            DataType::BcdDate => encode_bcd_date(self.expect(value.as_str(), value)?, self.length)?,
+            DataType::Struct => self.encode_struct(value)?,
+            DataType::Number | DataType::Signed | DataType::Float | DataType::Boolean => {
+                let size = self.number_size(value)?;
+                let bits = self.raw_bits(value, size)?;
+                write_uint(bits << self.bit_position, size, self.byte_order)
+            }
+        };
+
+        match self.length {
+            Some(length) if data.len() != length => Err(Sovd2UdsError::InvalidRequest(format!(
+                "Expected {} bytes, got {}",
+                length,
+                data.len()
+            ))),
+            _ => Ok(data),
+        }
+    }
+
+    fn expect<T>(&self, value: Option<T>, json: &Value) -> Result<T> {
+        value.ok_or_else(|| {
+            Sovd2UdsError::InvalidRequest(format!(
+                "Expected a {} value, got {}",
+                self.data_type.name(),
+                json
+            ))
+        })
+    }
+
+    /// Unsigned integer of the value's bits
+    fn read_bits(&self, data: &[u8]) -> Result<u64> {
+        if data.is_empty() || data.len() > 8 {
+            return Err(Sovd2UdsError::Translation(format!(
+                "Cannot decode {} bytes as a {}",
+                data.len(),
+                self.data_type.name()
+            )));
+        }
+        let raw = read_uint(data, self.byte_order);
+        Ok(match self.bit_length {
+            Some(bits) => (raw >> self.bit_position) & mask(bits),
+            None => raw,
+        })
+    }
+
+    /// Number of bits of the value in `size` bytes
+    fn width(&self, size: usize) -> u32 {
+        self.bit_length.unwrap_or(size as u32 * 8)
+    }
+
+    fn read_raw(&self, data: &[u8]) -> Result<Raw> {
+        let bits = self.read_bits(data)?;
+        let width = self.width(data.len());
+        Ok(match self.data_type {
+            DataType::Signed if width < 64 && bits >> (width - 1) & 1 == 1 => {
+                Raw::Signed((bits | !mask(width)) as i64)
+            }
+            DataType::Signed => Raw::Signed(bits as i64),
+            DataType::Float => match width {
+                // Through the shortest text of the f32, so 123.45 stays 123.45
+                32 => Raw::Float(
+                    f32::from_bits(bits as u32)
+                        .to_string()
+                        .parse()
+                        .unwrap_or(f64::NAN),
+                ),
+                64 => Raw::Float(f64::from_bits(bits)),
+                _ => {
+                    return Err(Sovd2UdsError::Translation(format!(
+                        "Cannot decode {} bytes as a float",
+                        data.len()
+                    )))
+                }
+            },
+            _ => Raw::Unsigned(bits),
+        })
+    }
+
+    /// Physical value of a raw number
+    fn physical(&self, raw: Raw) -> Value {
+        match &self.conversion {
+            Some(Conversion::TextTable { entries }) => entries
+                .iter()
+                .find(|entry| entry.contains(raw.as_f64()))
+                .map(|entry| Value::String(entry.text.clone()))
+                .unwrap_or_else(|| raw.to_json()),
+            Some(Conversion::Rational {
+                numerator,
+                denominator,
+            }) => {
+                let x = raw.as_f64();
+                Value::from(polynomial(numerator, x) / polynomial(denominator, x))
+            }
+            None if self.is_scaled() => Value::from(raw.as_f64() * self.scale + self.offset),
+            None => raw.to_json(),
+        }
+    }
+
+    /// Raw number of a physical value, before rounding
+    fn internal(&self, value: &Value) -> Result<f64> {
+        let physical = |value: &Value| self.expect(value.as_f64(), value);
+        match &self.conversion {
+            Some(Conversion::TextTable { entries }) => match value.as_str() {
+                Some(text) => entries
+                    .iter()
+                    .find(|entry| entry.text == text)
+                    .map(|entry| entry.value as f64)
+                    .ok_or_else(|| {
+                        let texts: Vec<&str> =
+                            entries.iter().map(|entry| entry.text.as_str()).collect();
+                        Sovd2UdsError::InvalidRequest(format!(
+                            "Unknown value '{}', expected one of: {}",
+                            text,
+                            texts.join(", ")
+                        ))
+                    }),
+                None => physical(value),
+            },
+            Some(Conversion::Rational {
+                numerator,
+                denominator,
+            }) => {
+                let p = physical(value)?;
+                // p * (d0 + d1*x) = n0 + n1*x
+                let (n0, n1, d0, d1) = match (numerator.as_slice(), denominator.as_slice()) {
+                    ([n0], [d0]) => (*n0, 0.0, *d0, 0.0),
+                    ([n0, n1], [d0]) => (*n0, *n1, *d0, 0.0),
+                    ([n0], [d0, d1]) => (*n0, 0.0, *d0, *d1),
+                    ([n0, n1], [d0, d1]) => (*n0, *n1, *d0, *d1),
+                    _ => {
+                        return Err(Sovd2UdsError::InvalidRequest(
+                            "Values of this conversion cannot be written".to_string(),
+                        ))
+                    }
+                };
+                let divisor = n1 - p * d1;
+                if divisor == 0.0 {
+                    return Err(Sovd2UdsError::InvalidRequest(format!(
+                        "Value {} cannot be converted",
+                        p
+                    )));
+                }
+                Ok((p * d0 - n0) / divisor)
+            }
+            None => Ok((physical(value)? - self.offset) / self.scale),
+        }
+    }
+
+    /// Bytes a number is written as
+    fn number_size(&self, value: &Value) -> Result<usize> {
+        if let Some(length) = self.byte_length() {
+            return Ok(length);
+        }
+        // Without a declared length, the smallest of 1, 2 or 4 bytes (as read)
+        let raw = self.internal(value)?.abs();
+        Ok(match raw {
+            r if r <= 0xFF as f64 && self.data_type != DataType::Signed => 1,
+            r if r < 0x80 as f64 => 1,
+            r if r <= 0xFFFF as f64 && self.data_type != DataType::Signed => 2,
+            r if r < 0x8000 as f64 => 2,
+            _ => 4,
+        })
+    }
+
+    /// Bits of a number as written in `size` bytes
+    fn raw_bits(&self, value: &Value, size: usize) -> Result<u64> {
+        let width = self.width(size);
+        let out_of_range = || {
+            Sovd2UdsError::InvalidRequest(format!("Value {} does not fit in {} bits", value, width))
+        };
+
+        match self.data_type {
+            DataType::Boolean => Ok(self.expect(value.as_bool(), value)? as u64),
+            DataType::Float => {
+                let raw = self.internal(value)?;
+                match width {
+                    32 => Ok((raw as f32).to_bits() as u64),
+                    64 => Ok(raw.to_bits()),
+                    _ => Err(out_of_range()),
+                }
+            }
+            _ => {
+                // Integers without conversion are taken as they are
+                let exact = (self.conversion.is_none() && !self.is_scaled())
+                    .then(|| {
+                        value
+                            .as_i64()
+                            .map(i128::from)
+                            .or(value.as_u64().map(i128::from))
+                    })
+                    .flatten();
+                let raw = match exact {
+                    Some(raw) => raw,
+                    None => {
+                        let raw = self.internal(value)?.round();
+                        if !raw.is_finite() {
+                            return Err(out_of_range());
+                        }
+                        raw as i128
+                    }
+                };
+                let (min, max) = match self.data_type {
+                    DataType::Signed => (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1),
+                    _ => (0, (1i128 << width) - 1),
+                };
+                if !(min..=max).contains(&raw) {
+                    return Err(out_of_range());
+                }
+                Ok(raw as u64 & mask(width))
+            }
+        }
+    }
+
+    fn decode_struct(&self, data: &[u8]) -> Result<Value> {
+        let mut object = serde_json::Map::new();
+        let mut end = 0;
+        for field in &self.fields {
+            let start = field.byte_position.unwrap_or(end);
+            let length = field
+                .codec
+                .byte_length()
+                .unwrap_or(data.len().saturating_sub(start));
+            let bytes = data.get(start..start + length).ok_or_else(|| {
+                Sovd2UdsError::Translation(format!(
+                    "Field '{}' exceeds the {} bytes of data",
+                    field.name,
+                    data.len()
+                ))
+            })?;
+            object.insert(field.name.clone(), field.codec.decode(bytes)?);
+            end = end.max(start + length);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn encode_struct(&self, value: &Value) -> Result<Vec<u8>> {
+        let object = self.expect(value.as_object(), value)?;
+        if let Some(name) = object
+            .keys()
+            .find(|name| !self.fields.iter().any(|field| &field.name == *name))
+        {
+            return Err(Sovd2UdsError::InvalidRequest(format!(
+                "Unknown field '{}'",
+                name
+            )));
+        }
+
+        let mut data = Vec::new();
+        let mut end = 0;
+        for field in &self.fields {
+            let value = object.get(&field.name).ok_or_else(|| {
+                Sovd2UdsError::InvalidRequest(format!("Missing field '{}'", field.name))
+            })?;
+            let start = field.byte_position.unwrap_or(end);
+            let bytes = field.codec.encode(value)?;
+            if data.len() < start + bytes.len() {
+                data.resize(start + bytes.len(), 0);
+            }
+            let target = &mut data[start..start + bytes.len()];
+            if field.codec.bit_length.is_some() {
+                // Bit fields share their bytes with other fields
+                let merged = read_uint(target, field.codec.byte_order)
+                    | read_uint(&bytes, field.codec.byte_order);
+                target.copy_from_slice(&write_uint(merged, bytes.len(), field.codec.byte_order));
+            } else {
+                target.copy_from_slice(&bytes);
+            }
+            end = end.max(start + bytes.len());
+        }
+        Ok(data)
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, coefficient| sum * x + coefficient)
+}
+
+/// Unsigned integer of up to 8 bytes
+fn read_uint(data: &[u8], byte_order: ByteOrder) -> u64 {
+    let mut bytes = [0u8; 8];
+    match byte_order {
+        ByteOrder::Big => {
+            bytes[8 - data.len()..].copy_from_slice(data);
+            u64::from_be_bytes(bytes)
+        }
+        ByteOrder::Little => {
+            bytes[..data.len()].copy_from_slice(data);
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+/// The `size` low bytes of an integer
+fn write_uint(value: u64, size: usize, byte_order: ByteOrder) -> Vec<u8> {
+    match byte_order {
+        ByteOrder::Big => value.to_be_bytes()[8 - size..].to_vec(),
+        ByteOrder::Little => value.to_le_bytes()[..size].to_vec(),
+    }
+}
+
+/// Digits of packed BCD; a trailing 0xF nibble pads an odd number of digits
+fn decode_bcd(data: &[u8]) -> Result<String> {
+    let mut digits = String::with_capacity(data.len() * 2);
+    let nibbles = data.iter().flat_map(|byte| [byte >> 4, byte & 0x0F]);
+    for (index, nibble) in nibbles.enumerate() {
+        match nibble {
+            0..=9 => digits.push(char::from(b'0' + nibble)),
+            0x0F if index == data.len() * 2 - 1 => {}
+            _ => {
+                return Err(Sovd2UdsError::Translation(format!(
+                    "Invalid BCD data {}",
+                    hex::encode(data)
+                )))
+            }
+        }
+    }
+    Ok(digits)
+}
+
+/// Packed BCD of a string of digits, padded with leading zeros to `length`
+fn encode_bcd(digits: &str, length: Option<usize>) -> Result<Vec<u8>> {
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Sovd2UdsError::InvalidRequest(format!(
+            "Expected BCD digits, got '{}'",
+            digits
+        )));
+    }
+    let size = length.unwrap_or(digits.len().div_ceil(2));
+    if digits.len() > size * 2 {
+        return Err(Sovd2UdsError::InvalidRequest(format!(
+            "'{}' does not fit in {} bytes",
+            digits, size
+        )));
+    }
+    let padded = format!("{:0>width$}", digits, width = size * 2);
+    Ok(padded
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| (pair[0] - b'0') << 4 | (pair[1] - b'0'))
+        .collect())
+}
+
+/// "YYYY-MM-DD" of a BCD date (YYMMDD or YYYYMMDD)
+fn decode_bcd_date(data: &[u8]) -> Result<String> {
+    let invalid = || Sovd2UdsError::Translation(format!("Invalid BCD date {}", hex::encode(data)));
+    let digits = decode_bcd(data)?;
+    let (year, month_day) = match digits.len() {
+        6 => (format!("20{}", &digits[..2]), &digits[2..]),
+        8 => (digits[..4].to_string(), &digits[4..]),
+        _ => return Err(invalid()),
+    };
+    let text = format!("{}-{}-{}", year, &month_day[..2], &month_day[2..]);
+    NaiveDate::parse_from_str(&text, "%Y-%m-%d").map_err(|_| invalid())?;
+    Ok(text)
+}
+
+/// BCD date of "YYYY-MM-DD"; 3 bytes (YYMMDD) or, by default, 4 (YYYYMMDD)
+fn encode_bcd_date(text: &str, length: Option<usize>) -> Result<Vec<u8>> {
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+        Sovd2UdsError::InvalidRequest(format!("Expected a date (YYYY-MM-DD), got '{}'", text))
+    })?;
+    let digits = match length {
+        Some(3) if (2000..=2099).contains(&date.year()) => date.format("%y%m%d").to_string(),
+        Some(3) => {
+            return Err(Sovd2UdsError::InvalidRequest(format!(
+                "Year of '{}' does not fit in 2 digits",
+                text
+            )))
+        }
+        _ => date.format("%Y%m%d").to_string(),
+    };
+    encode_bcd(&digits, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn codec(definition: Value) -> Codec {
+        serde_json::from_value(definition).unwrap()
+    }
+
+    /// Decode `data` to `value` and encode `value` back to `data`
+    fn assert_round_trip(codec: &Codec, data: &[u8], value: Value) {
+        assert_eq!(codec.decode(data).unwrap(), value, "decoding {:02X?}", data);
+        assert_eq!(codec.encode(&value).unwrap(), data, "encoding {}", value);
+    }
+
+    fn assert_invalid_request(result: Result<Vec<u8>>) {
+        assert!(
+            matches!(result, Err(Sovd2UdsError::InvalidRequest(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn signed_integers_of_every_width() {
+        let cases: [(Value, &[u8], i64); 6] = [
+            (json!({"data_type": "signed", "length": 1}), &[0xFF], -1),
+            (json!({"data_type": "signed", "length": 1}), &[0x7F], 127),
+            (
+                json!({"data_type": "signed", "length": 2}),
+                &[0x80, 0x00],
+                -32768,
+            ),
+            (
+                json!({"data_type": "signed", "length": 4}),
+                &[0xFF, 0xFF, 0xFF, 0xFE],
+                -2,
+            ),
+            (
+                json!({"data_type": "signed", "length": 8}),
+                &[0x80, 0, 0, 0, 0, 0, 0, 0],
+                i64::MIN,
+            ),
+            (
+                json!({"data_type": "signed", "length": 2, "byte_order": "little"}),
+                &[0xFE, 0xFF],
+                -2,
+            ),
+        ];
+        for (definition, data, value) in cases {
+            assert_round_trip(&codec(definition), data, json!(value));
+        }
+    }
+
+    #[test]
+    fn bit_fields() {
+        let cases: [(Value, &[u8], Value); 4] = [
+            (
+                json!({"data_type": "number", "bit_position": 4, "bit_length": 3}),
+                &[0x50],
+                json!(5),
+            ),
+            (
+                json!({"data_type": "signed", "bit_position": 0, "bit_length": 4}),
+                &[0x0F],
+                json!(-1),
+            ),
+            (
+                json!({"data_type": "boolean", "bit_position": 7, "bit_length": 1}),
+                &[0x80],
+                json!(true),
+            ),
+            (
+                json!({"data_type": "number", "length": 2, "bit_position": 4, "bit_length": 8}),
+                &[0x0A, 0xB0],
+                json!(0xAB),
+            ),
+        ];
+        for (definition, data, value) in cases {
+            assert_round_trip(&codec(definition), data, value);
+        }
+
+        // Bits outside the field are ignored when decoding
+        let field = codec(json!({"data_type": "number", "bit_position": 4, "bit_length": 3}));
+        assert_eq!(field.decode(&[0xDF]).unwrap(), json!(5));
+    }
+
+    #[test]
+    fn scaled_and_rational_conversions_are_inverted() {
+        let scaled =
+            codec(json!({"data_type": "number", "length": 2, "scale": 0.01, "offset": -40}));
+        assert_round_trip(&scaled, &[0x10, 0x68], json!(2.0));
+
+        // physical = x / (1 + 0.5x)
+        let rational = codec(json!({
+            "data_type": "number",
+            "length": 1,
+            "conversion": {"type": "rational", "numerator": [0, 1], "denominator": [1, 0.5]}
+        }));
+        assert_round_trip(&rational, &[0x02], json!(1.0));
+        assert_round_trip(&rational, &[0x06], json!(1.5));
+    }
+
+    #[test]
+    fn text_tables() {
+        let gear = codec(json!({
+            "data_type": "number",
+            "length": 1,
+            "conversion": {"type": "text_table", "entries": [
+                {"value": 0, "text": "Park"},
+                {"value": 2, "max": 8, "text": "Drive"}
+            ]}
+        }));
+        assert_round_trip(&gear, &[0x00], json!("Park"));
+        assert_round_trip(&gear, &[0x02], json!("Drive"));
+        assert_eq!(gear.decode(&[0x05]).unwrap(), json!("Drive"));
+        // Raw values without text are shown and written as numbers
+        assert_round_trip(&gear, &[0x01], json!(1));
+        assert_invalid_request(gear.encode(&json!("Reverse")));
+    }
+
+    #[test]
+    fn bcd_values() {
+        let cases: [(Value, &[u8], &str); 4] = [
+            (json!({"data_type": "bcd"}), &[0x12, 0x34], "1234"),
+            (
+                json!({"data_type": "bcd", "length": 3}),
+                &[0x00, 0x12, 0x34],
+                "001234",
+            ),
+            (
+                json!({"data_type": "bcd_date"}),
+                &[0x20, 0x24, 0x02, 0x29],
+                "2024-02-29",
+            ),
+            (
+                json!({"data_type": "bcd_date", "length": 3}),
+                &[0x24, 0x02, 0x29],
+                "2024-02-29",
+            ),
+        ];
+        for (definition, data, value) in cases {
+            assert_round_trip(&codec(definition), data, json!(value));
+        }
+        // A trailing 0xF nibble pads an odd number of digits
+        assert_eq!(
+            codec(json!({"data_type": "bcd"}))
+                .decode(&[0x12, 0x3F])
+                .unwrap(),
+            json!("123")
+        );
+    }
+
+    #[test]
+    fn struct_fields_share_bit_field_bytes() {
+        let status = codec(json!({
+            "data_type": "struct",
+            "fields": [
+                {"name": "low", "data_type": "number", "byte_position": 0, "bit_length": 4},
+                {
+                    "name": "high",
+                    "data_type": "number",
+                    "byte_position": 0,
+                    "bit_position": 4,
+                    "bit_length": 4
+                },
+                {"name": "counter", "data_type": "number", "byte_position": 1, "length": 2},
+                {"name": "name", "data_type": "string"}
+            ]
+        }));
+        assert_eq!(status.byte_length(), None);
+        assert_round_trip(
+            &status,
+            &[0xA3, 0x01, 0x02, b'O', b'K'],
+            json!({"low": 3, "high": 10, "counter": 258, "name": "OK"}),
+        );
+    }
+
+    #[test]
+    fn f32_values_keep_their_shortest_text() {
+        let float = codec(json!({"data_type": "float", "length": 4}));
+        let data = 123.45f32.to_be_bytes();
+        assert_round_trip(&float, &data, json!(123.45));
+
+        let double = codec(json!({"data_type": "float", "length": 8, "byte_order": "little"}));
+        assert_round_trip(&double, &0.1f64.to_le_bytes(), json!(0.1));
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let cases = [
+            (json!({"data_type": "number", "length": 1}), json!(256)),
+            (json!({"data_type": "number", "length": 1}), json!(-1)),
+            (json!({"data_type": "signed", "length": 1}), json!(-129)),
+            (json!({"data_type": "signed", "length": 1}), json!(128)),
+            (
+                json!({"data_type": "number", "bit_position": 4, "bit_length": 3}),
+                json!(8),
+            ),
+            (
+                json!({"data_type": "number", "length": 1, "scale": 0.5}),
+                json!(128),
+            ),
+            (json!({"data_type": "string", "length": 3}), json!("ABCD")),
+        ];
+        for (definition, value) in cases {
+            assert_invalid_request(codec(definition).encode(&value));
+        }
+    }
+
+    #[test]
+    fn invalid_bcd_is_rejected() {
+        let bcd = codec(json!({"data_type": "bcd"}));
+        assert!(matches!(
+            bcd.decode(&[0x1A]),
+            Err(Sovd2UdsError::Translation(_))
+        ));
+        assert!(matches!(
+            bcd.decode(&[0xF1]),
+            Err(Sovd2UdsError::Translation(_))
+        ));
+        assert_invalid_request(bcd.encode(&json!("12a4")));
+        assert_invalid_request(
+            codec(json!({"data_type": "bcd", "length": 1})).encode(&json!("123")),
+        );
+
+        let date = codec(json!({"data_type": "bcd_date"}));
+        // Month 13 and February 30th
+        assert!(date.decode(&[0x20, 0x24, 0x13, 0x01]).is_err());
+        assert!(date.decode(&[0x20, 0x24, 0x02, 0x30]).is_err());
+        assert_invalid_request(date.encode(&json!("29.02.2024")));
+        let short_date = codec(json!({"data_type": "bcd_date", "length": 3}));
+        assert_invalid_request(short_date.encode(&json!("1999-12-31")));
+    }
+
+    #[test]
+    fn unknown_and_missing_struct_fields_are_rejected() {
+        let pair = codec(json!({
+            "data_type": "struct",
+            "fields": [
+                {"name": "a", "data_type": "number", "length": 1},
+                {"name": "b", "data_type": "number", "length": 1}
+            ]
+        }));
+        assert_invalid_request(pair.encode(&json!({"a": 1, "b": 2, "c": 3})));
+        assert_invalid_request(pair.encode(&json!({"a": 1})));
+        assert_invalid_request(pair.encode(&json!([1, 2])));
+    }
+
+    #[test]
+    fn contradicting_definitions_fail_validation() {
+        let invalid = [
+            json!({"data_type": "number", "length": 9}),
+            json!({"data_type": "number", "length": 0}),
+            json!({"data_type": "float", "length": 2}),
+            json!({"data_type": "string", "bit_length": 4}),
+            json!({"data_type": "number", "bit_position": 60, "bit_length": 8}),
+            json!({"data_type": "bcd_date", "length": 2}),
+            json!({"data_type": "struct"}),
+            json!({"data_type": "hex", "conversion": {"type": "rational", "numerator": [1]}}),
+            json!({"data_type": "struct", "fields": [
+                {"name": "text", "data_type": "string"},
+                {"name": "number", "data_type": "number", "length": 1}
+            ]}),
+        ];
+        for definition in invalid {
+            assert!(
+                codec(definition.clone()).validate().is_err(),
+                "{}",
+                definition
+            );
+        }
+        assert!(codec(json!({"data_type": "number", "length": 8}))
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,493 @@
+use super::codec::Codec;
+use crate::error::{Result, Sovd2UdsError};
+use crate::models::uds::{data_identifiers, security_levels, DiagnosticSessionType};
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
+
+/// Whether a data item may be read and/or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataAccess {
+    #[default]
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl DataAccess {
+    pub fn readable(self) -> bool {
+        matches!(self, Self::Read | Self::ReadWrite)
+    }
+
+    pub fn writable(self) -> bool {
+        matches!(self, Self::Write | Self::ReadWrite)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::ReadWrite => "read_write",
+        }
+    }
+}
+
+/// A SOVD data item and the DID it is read from and written to
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataItemDefinition {
+    /// SOVD data item ID, e.g. "vin"
+    pub id: String,
+    /// Display name; the ID when empty
+    #[serde(default)]
+    pub name: String,
+    /// Data identifier, as a number or a hex string such as "0xF190"
+    #[serde(deserialize_with = "deserialize_identifier")]
+    pub did: u16,
+    /// SOVD category (identData, currentData, storedData, sysInfo, ...)
+    #[serde(default = "default_category")]
+    pub category: String,
+    /// Type, length, scaling and unit of the value
+    #[serde(flatten)]
+    pub codec: Codec,
+    #[serde(default)]
+    pub access: DataAccess,
+    /// Session the item is read and written in ("extended", ...); reads use
+    /// the active session and writes the extended session when unset
+    #[serde(default, deserialize_with = "deserialize_session")]
+    pub session: Option<DiagnosticSessionType>,
+    /// Security level that must be unlocked to write the item
+    #[serde(default)]
+    pub security_level: Option<u8>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl DataItemDefinition {
+    /// Read-only definition with the default type, category and scaling
+    pub fn new(id: &str, did: u16) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            did,
+            category: default_category(),
+            codec: Codec::default(),
+            access: DataAccess::default(),
+            session: None,
+            security_level: None,
+            description: None,
+        }
+    }
+
+    /// Identification string as defined by ISO 14229-1
+    fn ident(id: &str, did: u16, name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            category: "identData".to_string(),
+            description: Some(description.to_string()),
+            ..Self::new(id, did)
+        }
+    }
+}
+
+fn check_security_level(level: Option<u8>) -> std::result::Result<(), String> {
+    match level {
+        Some(level) if !security_levels::is_valid(level) => Err(format!(
+            "security_level {} is outside 1-{}",
+            level,
+            security_levels::MAX
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn default_category() -> String {
+    "currentData".to_string()
+}
+
+/// A routine, controlled through the SOVD actuator API
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutineDefinition {
+    /// SOVD actuator ID, e.g. "fuel_pump"
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// Routine identifier, as a number or a hex string such as "0x0201"
+    #[serde(deserialize_with = "deserialize_identifier")]
+    pub rid: u16,
+    /// Session the routine must be run in
+    #[serde(default, deserialize_with = "deserialize_session")]
+    pub session: Option<DiagnosticSessionType>,
+    /// Security level that must be unlocked to run the routine
+    #[serde(default)]
+    pub security_level: Option<u8>,
+    /// Coding of the start/stop parameters (the actuator `value`); without
+    /// one, numbers take 1, 2 or 4 bytes and strings are sent as text
+    #[serde(default)]
+    pub parameters: Option<Codec>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl RoutineDefinition {
+    pub fn new(id: &str, rid: u16) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            rid,
+            session: None,
+            security_level: None,
+            parameters: None,
+            description: None,
+        }
+    }
+}
+
+/// A DTC an ECU may report
+#[derive(Debug, Clone, Deserialize)]
+pub struct DtcDefinition {
+    /// 24-bit DTC number, as a number, a hex string such as "0x010011" or a
+    /// code such as "P0100-11"
+    #[serde(deserialize_with = "deserialize_dtc")]
+    pub code: u32,
+    /// Code as shown to users, e.g. "P0100-11"
+    #[serde(default)]
+    pub display_code: Option<String>,
+    pub description: String,
+}
+
+/// Meaning of a DTC failure type byte (FTB)
+#[derive(Debug, Clone, Deserialize)]
+pub struct FailureTypeDefinition {
+    /// Third byte of the DTC
+    pub value: u8,
+    pub description: String,
+}
+
+/// A diagnostic session an ECU supports
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionDefinition {
+    /// Name reported for the session, e.g. "extended"
+    pub name: String,
+    /// DiagnosticSessionControl sub-function
+    pub id: u8,
+}
+
+/// Accept identifiers as numbers (TOML/YAML hex literals included) or hex strings
+fn deserialize_identifier<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u16, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Did {
+        Number(u16),
+        Text(String),
+    }
+
+    match Did::deserialize(deserializer)? {
+        Did::Number(did) => Ok(did),
+        Did::Text(text) => {
+            let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+            u16::from_str_radix(digits, 16)
+                .map_err(|_| serde::de::Error::custom(format!("invalid DID '{}'", text)))
+        }
+    }
+}
+
+fn deserialize_dtc<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Dtc {
+        Number(u32),
+        Text(String),
+    }
+
+    let code = match Dtc::deserialize(deserializer)? {
+        Dtc::Number(code) => code,
+        Dtc::Text(text) => super::dtc::parse_dtc(&text)
+            .map_err(|_| serde::de::Error::custom(format!("invalid DTC '{}'", text)))?,
+    };
+    if code > 0xFF_FFFF {
+        return Err(serde::de::Error::custom(format!(
+            "DTC 0x{:X} is longer than 3 bytes",
+            code
+        )));
+    }
+    Ok(code)
+}
+
+fn deserialize_session<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<DiagnosticSessionType>, D::Error> {
+    let Some(name) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    DiagnosticSessionType::from_name(&name)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown session '{}'", name)))
+}
+
+/// Data item, routine, DTC, failure type and session definitions, as loaded
+/// from a dictionary file or imported from ODX
+///
+/// ```yaml
+/// items:
+///   - id: vehicle_speed
+///     name: Vehicle Speed
+///     did: 0x010D
+///     data_type: number
+///     length: 2
+///     scale: 0.01
+///     unit: km/h
+/// routines:
+///   - id: fuel_pump
+///     rid: 0x0201
+///     session: extended
+/// dtcs:
+///   - code: 0x010011
+///     display_code: P0100-11
+///     description: Mass air flow circuit short to ground
+///   - code: P0100              # failure type 00: all failure types
+///     description: Mass or volume air flow sensor A circuit
+/// failure_types:
+///   - value: 0x11
+///     description: Circuit short to ground
+/// sessions:
+///   - name: end_of_line
+///     id: 0x40
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DataDictionary {
+    #[serde(default)]
+    pub items: Vec<DataItemDefinition>,
+    #[serde(default)]
+    pub routines: Vec<RoutineDefinition>,
+    #[serde(default)]
+    pub dtcs: Vec<DtcDefinition>,
+    #[serde(default)]
+    pub failure_types: Vec<FailureTypeDefinition>,
+    #[serde(default)]
+    pub sessions: Vec<SessionDefinition>,
+}
+
+impl DataDictionary {
+    /// Load a dictionary; the format (YAML, TOML or JSON) follows the file extension
+    pub fn load(path: &str) -> Result<Self> {
+        let dictionary_error = |e: config::ConfigError| {
+            Sovd2UdsError::Config(format!("Failed to load data dictionary '{}': {}", path, e))
+        };
+
+        let dictionary: Self = config::Config::builder()
+            .add_source(config::File::from(Path::new(path)))
+            .build()
+            .map_err(dictionary_error)?
+            .try_deserialize()
+            .map_err(dictionary_error)?;
+
+        let invalid = |kind: &str, id: &str, e: String| {
+            Sovd2UdsError::Config(format!("{} '{}' in '{}': {}", kind, id, path, e))
+        };
+        for item in &dictionary.items {
+            item.codec
+                .validate()
+                .map_err(|e| invalid("Data item", &item.id, e))?;
+            check_security_level(item.security_level)
+                .map_err(|e| invalid("Data item", &item.id, e))?;
+        }
+        for routine in &dictionary.routines {
+            check_security_level(routine.security_level)
+                .map_err(|e| invalid("Routine", &routine.id, e))?;
+            if let Some(parameters) = &routine.parameters {
+                parameters
+                    .validate()
+                    .map_err(|e| invalid("Routine", &routine.id, e))?;
+            }
+        }
+        Ok(dictionary)
+    }
+
+    /// Identification DIDs every ECU is expected to support, the actuator
+    /// routines of the built-in simulator and the ISO 15031-6 failure types
+    ///
+    /// The identification DIDs are read-only; a loaded dictionary that
+    /// redefines an item with write access makes it writable.
+    pub fn builtin() -> Self {
+        let vin = DataItemDefinition {
+            codec: Codec {
+                length: Some(17),
+                ..Codec::default()
+            },
+            ..DataItemDefinition::ident(
+                "vin",
+                data_identifiers::VIN,
+                "Vehicle Identification Number",
+                "Unique vehicle identification number",
+            )
+        };
+
+        Self {
+            items: vec![
+                vin,
+                DataItemDefinition::ident(
+                    "ecu_serial_number",
+                    data_identifiers::ECU_SERIAL_NUMBER,
+                    "ECU Serial Number",
+                    "ECU serial number",
+                ),
+                DataItemDefinition::ident(
+                    "ecu_hardware_version",
+                    data_identifiers::ECU_HARDWARE_VERSION,
+                    "ECU Hardware Version",
+                    "ECU hardware version",
+                ),
+                DataItemDefinition::ident(
+                    "ecu_software_version",
+                    data_identifiers::ECU_SOFTWARE_VERSION,
+                    "ECU Software Version",
+                    "ECU software version",
+                ),
+                DataItemDefinition::ident(
+                    "manufacturing_date",
+                    data_identifiers::ECU_MANUFACTURING_DATE,
+                    "Manufacturing Date",
+                    "ECU manufacturing date",
+                ),
+                DataItemDefinition::ident(
+                    "system_supplier_id",
+                    data_identifiers::SYSTEM_SUPPLIER_ID,
+                    "System Supplier ID",
+                    "System supplier identifier",
+                ),
+            ],
+            // Routines are not allowed in the default session
+            routines: [
+                ("fuel_pump", 0x0201),
+                ("cooling_fan", 0x0202),
+                ("throttle", 0x0203),
+            ]
+            .into_iter()
+            .map(|(id, rid)| RoutineDefinition {
+                session: Some(DiagnosticSessionType::ExtendedDiagnosticSession),
+                ..RoutineDefinition::new(id, rid)
+            })
+            .collect(),
+            dtcs: Vec::new(),
+            failure_types: super::dtc::FAILURE_TYPES
+                .iter()
+                .map(|(value, description)| FailureTypeDefinition {
+                    value: *value,
+                    description: description.to_string(),
+                })
+                .collect(),
+            sessions: Vec::new(),
+        }
+    }
+}
+
+/// Path of a file named `name` holding `contents`, in a temporary directory
+/// of this test process
+#[cfg(test)]
+pub(crate) fn test_file(name: &str, contents: impl AsRef<[u8]>) -> String {
+    let directory = std::env::temp_dir().join(format!("sovd2uds-test-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translation::DataType;
+
+    const YAML: &str = r#"
+items:
+  - id: vehicle_speed
+    name: Vehicle Speed
+    did: 0x010D
+    data_type: number
+    length: 2
+    scale: 0.01
+    unit: km/h
+  - id: coding
+    did: "0xF1A0"
+    data_type: hex
+    access: read_write
+    session: programming
+    security_level: 2
+routines:
+  - id: fuel_pump
+    rid: 0x0201
+    session: extended
+dtcs:
+  - code: P0100-11
+    description: Mass air flow circuit short to ground
+failure_types:
+  - value: 0x11
+    description: Circuit short to ground
+sessions:
+  - name: end_of_line
+    id: 0x40
+"#;
+
+    #[test]
+    fn dictionaries_load_in_every_format() {
+        let toml = r#"
+[[items]]
+id = "vehicle_speed"
+did = 0x010D
+data_type = "number"
+length = 2
+scale = 0.01
+unit = "km/h"
+"#;
+        let json = r#"{ "items": [{ "id": "vehicle_speed", "did": "0x010D",
+            "data_type": "number", "length": 2, "scale": 0.01, "unit": "km/h" }] }"#;
+        for (name, contents) in [("speed.yaml", YAML), ("speed.toml", toml), ("speed.json", json)] {
+            let dictionary = DataDictionary::load(&test_file(name, contents)).unwrap();
+            let speed = &dictionary.items[0];
+            assert_eq!(speed.id, "vehicle_speed", "{}", name);
+            assert_eq!(speed.did, 0x010D, "{}", name);
+            assert_eq!(speed.category, "currentData", "{}", name);
+            assert_eq!(speed.codec.data_type, DataType::Number, "{}", name);
+            assert_eq!(speed.codec.length, Some(2), "{}", name);
+            assert_eq!(speed.codec.unit.as_deref(), Some("km/h"), "{}", name);
+            assert_eq!(speed.access, DataAccess::Read, "{}", name);
+        }
+    }
+
+    #[test]
+    fn dictionary_holds_access_sessions_routines_and_dtcs() {
+        let dictionary = DataDictionary::load(&test_file("full.yaml", YAML)).unwrap();
+
+        let coding = &dictionary.items[1];
+        assert_eq!(coding.did, 0xF1A0);
+        assert!(coding.access.readable() && coding.access.writable());
+        assert_eq!(coding.session, Some(DiagnosticSessionType::ProgrammingSession));
+        assert_eq!(coding.security_level, Some(2));
+
+        let routine = &dictionary.routines[0];
+        assert_eq!((routine.id.as_str(), routine.rid), ("fuel_pump", 0x0201));
+        assert_eq!(routine.session, Some(DiagnosticSessionType::ExtendedDiagnosticSession));
+        assert_eq!(dictionary.dtcs[0].code, 0x010011);
+        assert_eq!(dictionary.failure_types[0].value, 0x11);
+        assert_eq!(dictionary.sessions[0].id, 0x40);
+    }
+
+    #[test]
+    fn invalid_definitions_are_rejected() {
+        let invalid = [
+            ("level.yaml", "items: [{ id: a, did: 0x0100, security_level: 0 }]"),
+            ("session.yaml", "items: [{ id: a, did: 0x0100, session: sleepy }]"),
+            ("did.yaml", "items: [{ id: a, did: \"0xG100\" }]"),
+            ("dtc.yaml", "dtcs: [{ code: 0x1000000, description: too long }]"),
+            ("missing.yaml", "items: [{ id: a }]"),
+        ];
+        for (name, contents) in invalid {
+            let result = DataDictionary::load(&test_file(name, contents));
+            assert!(matches!(result, Err(Sovd2UdsError::Config(_))), "{}", name);
+        }
+        assert!(DataDictionary::load("/nonexistent/dictionary.yaml").is_err());
+    }
+}
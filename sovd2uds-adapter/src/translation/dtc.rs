@@ -0,0 +1,849 @@
+//! Parsing of ReadDTCInformation (0x19) responses and DTC status bytes
+//!
+//! Responses are parsed without their sub-function echo, which the client
+//! strips. Truncated or malformed responses are reported as translation
+//! errors.
+
+use crate::error::{Result, Sovd2UdsError};
+use crate::models::DtcStatusMask;
+
+/// Short names of status bits accepted by status filters, besides the
+/// ISO 14229-1 names
+const STATUS_ALIASES: [(&str, u8); 10] = [
+    ("failed", DtcStatusMask::TEST_FAILED),
+    (
+        "failedThisOperationCycle",
+        DtcStatusMask::TEST_FAILED_THIS_OPERATION_CYCLE,
+    ),
+    ("pending", DtcStatusMask::PENDING_DTC),
+    ("confirmed", DtcStatusMask::CONFIRMED_DTC),
+    (
+        "notCompletedSinceLastClear",
+        DtcStatusMask::TEST_NOT_COMPLETED_SINCE_LAST_CLEAR,
+    ),
+    (
+        "failedSinceLastClear",
+        DtcStatusMask::TEST_FAILED_SINCE_LAST_CLEAR,
+    ),
+    (
+        "notCompletedThisOperationCycle",
+        DtcStatusMask::TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE,
+    ),
+    (
+        "warningIndicator",
+        DtcStatusMask::WARNING_INDICATOR_REQUESTED,
+    ),
+    ("mil", DtcStatusMask::WARNING_INDICATOR_REQUESTED),
+    ("all", 0xFF),
+];
+
+/// A DTC and its status byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DtcStatusRecord {
+    /// 24-bit DTC number
+    pub code: u32,
+    pub status: u8,
+}
+
+/// Response to reportDTCByStatusMask (0x02) or reportSupportedDTC (0x0A)
+#[derive(Debug, Clone)]
+pub struct DtcList {
+    /// Status bits the ECU supports
+    pub availability_mask: u8,
+    pub dtcs: Vec<DtcStatusRecord>,
+}
+
+/// Response to reportNumberOfDTCByStatusMask (0x01)
+#[derive(Debug, Clone, Copy)]
+pub struct DtcCount {
+    pub availability_mask: u8,
+    /// DTCFormatIdentifier
+    pub format: u8,
+    pub count: u16,
+}
+
+/// A snapshot record (freeze frame) and the DID values it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotRecord {
+    pub number: u8,
+    pub values: Vec<(u16, Vec<u8>)>,
+}
+
+/// An extended data record of a DTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedDataRecord {
+    pub number: u8,
+    pub data: Vec<u8>,
+}
+
+/// Records of a DTC reported by DTC number (0x04, 0x06)
+#[derive(Debug, Clone)]
+pub struct DtcRecords<T> {
+    pub dtc: DtcStatusRecord,
+    pub records: Vec<T>,
+}
+
+/// A DTC reported with its WWH-OBD severity (0x42)
+#[derive(Debug, Clone, Copy)]
+pub struct SeverityRecord {
+    pub severity: u8,
+    pub dtc: DtcStatusRecord,
+}
+
+/// Response to reportWWHOBDDTCByMaskRecord (0x42)
+#[derive(Debug, Clone)]
+pub struct WwhObdDtcList {
+    pub functional_group: u8,
+    pub availability_mask: u8,
+    pub severity_availability_mask: u8,
+    pub format: u8,
+    pub dtcs: Vec<SeverityRecord>,
+}
+
+/// Response to reportWWHOBDDTCWithPermanentStatus (0x55)
+#[derive(Debug, Clone)]
+pub struct PermanentDtcList {
+    pub functional_group: u8,
+    pub availability_mask: u8,
+    pub format: u8,
+    pub dtcs: Vec<DtcStatusRecord>,
+}
+
+/// Parse a reportNumberOfDTCByStatusMask response
+pub fn parse_count(data: &[u8]) -> Result<DtcCount> {
+    let [availability_mask, format, high, low] = data else {
+        return Err(malformed("DTC count", data));
+    };
+    Ok(DtcCount {
+        availability_mask: *availability_mask,
+        format: *format,
+        count: u16::from_be_bytes([*high, *low]),
+    })
+}
+
+/// Parse a reportDTCByStatusMask or reportSupportedDTC response
+pub fn parse_dtc_list(data: &[u8]) -> Result<DtcList> {
+    let (&availability_mask, records) = data
+        .split_first()
+        .ok_or_else(|| malformed("DTC list", data))?;
+    Ok(DtcList {
+        availability_mask,
+        dtcs: status_records(records, "DTC list")?,
+    })
+}
+
+/// Parse a reportDTCSnapshotIdentification response into DTCs and their
+/// snapshot record numbers
+pub fn parse_snapshot_identification(data: &[u8]) -> Result<Vec<(u32, u8)>> {
+    if !data.len().is_multiple_of(4) {
+        return Err(malformed("snapshot identification", data));
+    }
+    Ok(data
+        .chunks_exact(4)
+        .map(|chunk| (dtc_number(chunk), chunk[3]))
+        .collect())
+}
+
+/// Parse a reportDTCSnapshotRecordByDTCNumber response
+///
+/// The values of the records are split by the lengths `did_length` returns;
+/// a DID of unknown length takes the rest of its record, so it must be the
+/// last of the record.
+pub fn parse_snapshot_records(
+    data: &[u8],
+    did_length: impl Fn(u16) -> Option<usize>,
+) -> Result<DtcRecords<SnapshotRecord>> {
+    let dtc = dtc_header(data, "snapshot record")?;
+    let mut records = Vec::new();
+    let mut rest = &data[4..];
+    while let [number, count, tail @ ..] = rest {
+        let mut values = Vec::new();
+        rest = tail;
+        for index in 0..*count {
+            let [high, low, tail @ ..] = rest else {
+                return Err(malformed("snapshot record", data));
+            };
+            let did = u16::from_be_bytes([*high, *low]);
+            // Only the last value of a record may be of unknown length
+            let last = index + 1 == *count;
+            let length = match did_length(did) {
+                Some(length) if length <= tail.len() => length,
+                None if last => tail.len(),
+                _ => return Err(malformed("snapshot record", data)),
+            };
+            values.push((did, tail[..length].to_vec()));
+            rest = &tail[length..];
+        }
+        records.push(SnapshotRecord {
+            number: *number,
+            values,
+        });
+    }
+    if !rest.is_empty() {
+        return Err(malformed("snapshot record", data));
+    }
+    Ok(DtcRecords { dtc, records })
+}
+
+/// Parse a reportDTCExtDataRecordByDTCNumber response
+///
+/// Record lengths are not part of the response, so records can only be
+/// separated when a single `record` was requested; for 0xFF (all records)
+/// and 0xFE (all OBD records) the data of all records is returned as one
+/// record with that number.
+pub fn parse_extended_data(data: &[u8], record: u8) -> Result<DtcRecords<ExtendedDataRecord>> {
+    let dtc = dtc_header(data, "extended data record")?;
+    let records = match &data[4..] {
+        [] => Vec::new(),
+        all if record >= 0xFE => vec![ExtendedDataRecord {
+            number: record,
+            data: all.to_vec(),
+        }],
+        [number, data @ ..] => vec![ExtendedDataRecord {
+            number: *number,
+            data: data.to_vec(),
+        }],
+    };
+    Ok(DtcRecords { dtc, records })
+}
+
+/// Parse a reportDTCFaultDetectionCounter response into DTCs and their
+/// counters
+pub fn parse_fault_detection_counters(data: &[u8]) -> Result<Vec<(u32, i8)>> {
+    if !data.len().is_multiple_of(4) {
+        return Err(malformed("fault detection counter", data));
+    }
+    Ok(data
+        .chunks_exact(4)
+        .map(|chunk| (dtc_number(chunk), chunk[3] as i8))
+        .collect())
+}
+
+/// Parse a reportWWHOBDDTCByMaskRecord response
+pub fn parse_wwh_obd_dtcs(data: &[u8]) -> Result<WwhObdDtcList> {
+    let [functional_group, availability_mask, severity_availability_mask, format, records @ ..] =
+        data
+    else {
+        return Err(malformed("WWH-OBD DTC", data));
+    };
+    if !records.len().is_multiple_of(5) {
+        return Err(malformed("WWH-OBD DTC", data));
+    }
+    Ok(WwhObdDtcList {
+        functional_group: *functional_group,
+        availability_mask: *availability_mask,
+        severity_availability_mask: *severity_availability_mask,
+        format: *format,
+        dtcs: records
+            .chunks_exact(5)
+            .map(|chunk| SeverityRecord {
+                severity: chunk[0],
+                dtc: DtcStatusRecord {
+                    code: dtc_number(&chunk[1..]),
+                    status: chunk[4],
+                },
+            })
+            .collect(),
+    })
+}
+
+/// Parse a reportWWHOBDDTCWithPermanentStatus response
+pub fn parse_permanent_dtcs(data: &[u8]) -> Result<PermanentDtcList> {
+    let [functional_group, availability_mask, format, records @ ..] = data else {
+        return Err(malformed("permanent DTC", data));
+    };
+    Ok(PermanentDtcList {
+        functional_group: *functional_group,
+        availability_mask: *availability_mask,
+        format: *format,
+        dtcs: status_records(records, "permanent DTC")?,
+    })
+}
+
+/// Status byte decoded into a boolean per status bit, plus the raw mask
+pub fn status_json(status: u8) -> serde_json::Value {
+    let mut bits = serde_json::Map::new();
+    bits.insert("mask".to_string(), format!("0x{:02X}", status).into());
+    for (bit, name) in DtcStatusMask::BITS {
+        bits.insert(name.to_string(), DtcStatusMask(status).is_set(bit).into());
+    }
+    bits.into()
+}
+
+/// Parse a status filter into a status mask
+///
+/// The filter is a comma-separated list of status bit names, either the
+/// ISO 14229-1 names (`confirmedDTC`, `testFailed`) or the short forms
+/// `confirmed`, `pending`, `failed`, `mil`, ...; case, `_` and `-` are
+/// ignored. A hex mask (`0x0C`) is accepted as well. DTCs match when they
+/// have any of the bits.
+pub fn parse_status_filter(filter: &str) -> Result<u8> {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_ascii_lowercase()
+    };
+    let names = DtcStatusMask::BITS.iter().map(|(bit, name)| (*name, *bit));
+
+    let mut mask = 0;
+    for name in filter
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if let Some(digits) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+            mask |= u8::from_str_radix(digits, 16).map_err(|_| invalid_status(name))?;
+            continue;
+        }
+        let wanted = normalize(name);
+        mask |= names
+            .clone()
+            .chain(STATUS_ALIASES)
+            .find(|(candidate, _)| normalize(candidate) == wanted)
+            .map(|(_, bit)| bit)
+            .ok_or_else(|| invalid_status(name))?;
+    }
+    if mask == 0 {
+        return Err(invalid_status(filter));
+    }
+    Ok(mask)
+}
+
+fn invalid_status(name: &str) -> Sovd2UdsError {
+    Sovd2UdsError::InvalidRequest(format!("Invalid DTC status filter '{}'", name))
+}
+
+/// Names of the bits of a WWH-OBD DTC severity byte: severity in the upper
+/// three bits, DTC class in the lower five
+pub fn severity_names(severity: u8) -> Vec<&'static str> {
+    const NAMES: [(u8, &str); 8] = [
+        (0x80, "checkImmediately"),
+        (0x40, "checkAtNextHalt"),
+        (0x20, "maintenanceOnly"),
+        (0x10, "classC"),
+        (0x08, "classB2"),
+        (0x04, "classB1"),
+        (0x02, "classA"),
+        (0x01, "class0"),
+    ];
+    NAMES
+        .iter()
+        .filter(|(bit, _)| severity & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Failure types (FTBs, third DTC byte) of ISO 15031-6 / SAE J2012-DA
+pub const FAILURE_TYPES: [(u8, &str); 97] = [
+    (0x00, "No sub type information"),
+    (0x01, "General electrical failure"),
+    (0x02, "General signal failure"),
+    (
+        0x03,
+        "FM (frequency modulated) / PWM (pulse width modulated) failures",
+    ),
+    (0x04, "System internal failures"),
+    (0x05, "System programming failures"),
+    (0x06, "Algorithm based failures"),
+    (0x07, "Mechanical failures"),
+    (0x08, "Bus signal / message failures"),
+    (0x09, "Component failures"),
+    (0x11, "Circuit short to ground"),
+    (0x12, "Circuit short to battery"),
+    (0x13, "Circuit open"),
+    (0x14, "Circuit short to ground or open"),
+    (0x15, "Circuit short to battery or open"),
+    (0x16, "Circuit voltage below threshold"),
+    (0x17, "Circuit voltage above threshold"),
+    (0x18, "Circuit current below threshold"),
+    (0x19, "Circuit current above threshold"),
+    (0x1A, "Circuit resistance below threshold"),
+    (0x1B, "Circuit resistance above threshold"),
+    (0x1C, "Circuit voltage out of range"),
+    (0x1D, "Circuit current out of range"),
+    (0x1E, "Circuit resistance out of range"),
+    (0x1F, "Circuit intermittent"),
+    (0x21, "Signal amplitude < minimum"),
+    (0x22, "Signal amplitude > maximum"),
+    (0x23, "Signal stuck low"),
+    (0x24, "Signal stuck high"),
+    (0x25, "Signal shape / waveform failure"),
+    (0x26, "Signal rate of change below threshold"),
+    (0x27, "Signal rate of change above threshold"),
+    (
+        0x28,
+        "Signal bias level out of range / zero adjustment failure",
+    ),
+    (0x29, "Signal invalid"),
+    (0x2F, "Signal erratic"),
+    (0x31, "No signal"),
+    (0x32, "Signal low time < minimum"),
+    (0x33, "Signal low time > maximum"),
+    (0x34, "Signal high time < minimum"),
+    (0x35, "Signal high time > maximum"),
+    (0x36, "Signal frequency too low"),
+    (0x37, "Signal frequency too high"),
+    (0x38, "Signal frequency incorrect"),
+    (0x39, "Incorrect has too few pulses"),
+    (0x3A, "Incorrect has too many pulses"),
+    (0x41, "General checksum failure"),
+    (0x42, "General memory failure"),
+    (0x43, "Special memory failure"),
+    (0x44, "Data memory failure"),
+    (0x45, "Program memory failure"),
+    (0x46, "Calibration / parameter memory failure"),
+    (0x47, "Watchdog / safety microcontroller failure"),
+    (0x48, "Supervision software failure"),
+    (0x49, "Internal electronic failure"),
+    (0x4A, "Incorrect component installed"),
+    (0x4B, "Over temperature"),
+    (0x51, "Not programmed"),
+    (0x52, "Not activated"),
+    (0x53, "Deactivated"),
+    (0x54, "Missing calibration"),
+    (0x55, "Not configured"),
+    (0x61, "Signal calculation failure"),
+    (0x62, "Signal compare failure"),
+    (0x63, "Circuit / component protection time-out"),
+    (0x64, "Signal plausibility failure"),
+    (0x65, "Signal has too few transitions / events"),
+    (0x66, "Signal has too many transitions / events"),
+    (0x67, "Signal incorrect after event"),
+    (0x68, "Event information"),
+    (0x71, "Actuator stuck"),
+    (0x72, "Actuator stuck open"),
+    (0x73, "Actuator stuck closed"),
+    (0x74, "Actuator slipping"),
+    (0x75, "Emergency position not reachable"),
+    (0x76, "Wrong mounting position"),
+    (0x77, "Commanded position not reachable"),
+    (0x78, "Alignment or adjustment incorrect"),
+    (0x79, "Mechanical linkage failure"),
+    (0x7A, "Fluid leak or seal failure"),
+    (0x7B, "Low fluid level"),
+    (0x81, "Invalid serial data received"),
+    (0x82, "Alive / sequence counter incorrect / not updated"),
+    (0x83, "Value of signal protection calculation incorrect"),
+    (0x84, "Signal below allowable range"),
+    (0x85, "Signal above allowable range"),
+    (0x86, "Signal invalid"),
+    (0x87, "Missing message"),
+    (0x88, "Bus off"),
+    (0x8F, "Erratic"),
+    (0x91, "Parametric"),
+    (0x92, "Performance or incorrect operation"),
+    (0x93, "No operation"),
+    (0x94, "Unexpected operation"),
+    (0x95, "Incorrect assembly"),
+    (0x96, "Component internal failure"),
+    (0x97, "Component or system operation obstructed or blocked"),
+    (0x98, "Component or system over temperature"),
+];
+
+/// Systems of ISO 15031-6 / SAE J2012 codes, by the upper two bits
+const SYSTEMS: [char; 4] = [
+    'P', // Powertrain
+    'C', // Chassis
+    'B', // Body
+    'U', // Network
+];
+
+/// Format a DTC number as an ISO 15031-6 / SAE J2012 code with its failure
+/// type byte (P0100-11)
+pub fn format_dtc(code: u32) -> String {
+    let [_, high, low, failure_type] = code.to_be_bytes();
+    format!(
+        "{}{}{:X}{:02X}-{:02X}",
+        SYSTEMS[(high >> 6) as usize],
+        (high >> 4) & 0x03,
+        high & 0x0F,
+        low,
+        failure_type
+    )
+}
+
+/// Format a DTC number in the UDS (ISO 14229-1) form (0x010011)
+pub fn format_uds_dtc(code: u32) -> String {
+    format!("0x{:06X}", code)
+}
+
+/// Parse a DTC given as an ISO 15031-6 / SAE J2012 code or as a UDS DTC
+/// number
+///
+/// Codes may be given with failure type byte ("P0100-11", "P010011") or
+/// without it ("P0100", failure type 0x00). Anything else is read as a hex
+/// number of up to three bytes ("0x010011", "010011"). Texts that are both
+/// ("C0123", "B1234") are read as codes; the numbers need the "0x" prefix.
+pub fn parse_dtc(text: &str) -> Result<u32> {
+    let text = text.trim();
+    parse_obd_dtc(text)
+        .or_else(|| {
+            let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+            hex_number(digits).filter(|_| digits.len() <= 6)
+        })
+        .ok_or_else(|| Sovd2UdsError::InvalidRequest(format!("Invalid DTC '{}'", text)))
+}
+
+/// DTC number of an ISO 15031-6 / SAE J2012 code
+fn parse_obd_dtc(text: &str) -> Option<u32> {
+    let mut chars = text.chars();
+    let letter = chars.next()?.to_ascii_uppercase();
+    let system = SYSTEMS.iter().position(|system| *system == letter)? as u32;
+    let rest = chars.as_str();
+    if !rest.is_ascii() {
+        return None;
+    }
+    let (code, failure_type) = match rest.len() {
+        4 => (rest, "00"),
+        6 => rest.split_at(4),
+        7 => {
+            let (code, failure_type) = rest.split_at(4);
+            (code, failure_type.strip_prefix('-')?)
+        }
+        _ => return None,
+    };
+
+    let (first, number) = code.split_at(1);
+    let first = first.parse::<u32>().ok().filter(|digit| *digit <= 3)?;
+    Some((system << 22) | (first << 20) | (hex_number(number)? << 8) | hex_number(failure_type)?)
+}
+
+/// Value of hex digits, without sign or prefix
+fn hex_number(digits: &str) -> Option<u32> {
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// 24-bit DTC number of the first three bytes
+fn dtc_number(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]])
+}
+
+/// DTC and status that start a record response
+fn dtc_header(data: &[u8], what: &str) -> Result<DtcStatusRecord> {
+    if data.len() < 4 {
+        return Err(malformed(what, data));
+    }
+    Ok(DtcStatusRecord {
+        code: dtc_number(data),
+        status: data[3],
+    })
+}
+
+/// Consecutive DTCAndStatusRecords
+fn status_records(data: &[u8], what: &str) -> Result<Vec<DtcStatusRecord>> {
+    if !data.len().is_multiple_of(4) {
+        return Err(malformed(what, data));
+    }
+    Ok(data
+        .chunks_exact(4)
+        .map(|chunk| DtcStatusRecord {
+            code: dtc_number(chunk),
+            status: chunk[3],
+        })
+        .collect())
+}
+
+fn malformed(what: &str, data: &[u8]) -> Sovd2UdsError {
+    Sovd2UdsError::Translation(format!("Malformed {} response: {:02X?}", what, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DTC: DtcStatusRecord = DtcStatusRecord {
+        code: 0x010011,
+        status: 0x09,
+    };
+
+    fn did_length(did: u16) -> Option<usize> {
+        match did {
+            0xF18C => Some(2),
+            0x0100 => Some(1),
+            _ => None,
+        }
+    }
+
+    fn snapshot(number: u8, values: &[(u16, &[u8])]) -> SnapshotRecord {
+        SnapshotRecord {
+            number,
+            values: values.iter().map(|(did, v)| (*did, v.to_vec())).collect(),
+        }
+    }
+
+    fn extended(number: u8, data: &[u8]) -> ExtendedDataRecord {
+        ExtendedDataRecord {
+            number,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn snapshot_records() {
+        let cases: [(&[u8], Vec<SnapshotRecord>); 5] = [
+            // DTC without records
+            (&[0x01, 0x00, 0x11, 0x09], vec![]),
+            (
+                &[0x01, 0x00, 0x11, 0x09, 0x01, 0x01, 0xF1, 0x8C, 0x12, 0x34],
+                vec![snapshot(0x01, &[(0xF18C, &[0x12, 0x34])])],
+            ),
+            // Two DIDs in one record, two records
+            (
+                &[
+                    0x01, 0x00, 0x11, 0x09, 0x01, 0x02, 0xF1, 0x8C, 0x12, 0x34, 0x01, 0x00, 0x56,
+                    0x02, 0x01, 0x01, 0x00, 0x78,
+                ],
+                vec![
+                    snapshot(0x01, &[(0xF18C, &[0x12, 0x34]), (0x0100, &[0x56])]),
+                    snapshot(0x02, &[(0x0100, &[0x78])]),
+                ],
+            ),
+            // A DID of unknown length takes the rest of the last record
+            (
+                &[
+                    0x01, 0x00, 0x11, 0x09, 0x01, 0x01, 0xAB, 0xCD, 0x01, 0x02, 0x03,
+                ],
+                vec![snapshot(0x01, &[(0xABCD, &[0x01, 0x02, 0x03])])],
+            ),
+            // Record without DIDs
+            (
+                &[0x01, 0x00, 0x11, 0x09, 0x03, 0x00],
+                vec![snapshot(0x03, &[])],
+            ),
+        ];
+        for (data, expected) in cases {
+            let parsed = parse_snapshot_records(data, did_length).unwrap();
+            assert_eq!(parsed.dtc, DTC);
+            assert_eq!(parsed.records, expected, "{:02X?}", data);
+        }
+    }
+
+    #[test]
+    fn malformed_snapshot_records() {
+        let cases: [&[u8]; 5] = [
+            &[0x01, 0x00, 0x11],
+            // Value shorter than its DID
+            &[0x01, 0x00, 0x11, 0x09, 0x01, 0x01, 0xF1, 0x8C, 0x12],
+            // Fewer DIDs than announced
+            &[0x01, 0x00, 0x11, 0x09, 0x01, 0x02, 0x01, 0x00, 0x56],
+            // Unknown DID before the last value of the record
+            &[
+                0x01, 0x00, 0x11, 0x09, 0x01, 0x02, 0xAB, 0xCD, 0x01, 0x01, 0x00, 0x56,
+            ],
+            // Trailing record number without count
+            &[0x01, 0x00, 0x11, 0x09, 0x01, 0x01, 0x01, 0x00, 0x56, 0x02],
+        ];
+        for data in cases {
+            assert!(
+                matches!(
+                    parse_snapshot_records(data, did_length),
+                    Err(Sovd2UdsError::Translation(_))
+                ),
+                "{:02X?}",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn extended_data_records() {
+        let cases: [(&[u8], u8, Vec<ExtendedDataRecord>); 4] = [
+            (&[0x01, 0x00, 0x11, 0x09], 0x01, vec![]),
+            (
+                &[0x01, 0x00, 0x11, 0x09, 0x01, 0x05],
+                0x01,
+                vec![extended(0x01, &[0x05])],
+            ),
+            // All records cannot be separated and are kept together
+            (
+                &[0x01, 0x00, 0x11, 0x09, 0x01, 0x05, 0x02, 0x07],
+                0xFF,
+                vec![extended(0xFF, &[0x01, 0x05, 0x02, 0x07])],
+            ),
+            (
+                &[0x01, 0x00, 0x11, 0x09, 0x90, 0x01],
+                0xFE,
+                vec![extended(0xFE, &[0x90, 0x01])],
+            ),
+        ];
+        for (data, record, expected) in cases {
+            let parsed = parse_extended_data(data, record).unwrap();
+            assert_eq!(parsed.dtc, DTC);
+            assert_eq!(parsed.records, expected, "{:02X?}", data);
+        }
+        assert!(parse_extended_data(&[0x01, 0x00], 0x01).is_err());
+    }
+
+    #[test]
+    fn wwh_obd_dtcs() {
+        let parsed = parse_wwh_obd_dtcs(&[
+            0x33, 0xFF, 0xE0, 0x04, 0x40, 0x01, 0x00, 0x11, 0x09, 0x80, 0xC1, 0x23, 0x00, 0x2F,
+        ])
+        .unwrap();
+        assert_eq!(
+            (
+                parsed.functional_group,
+                parsed.availability_mask,
+                parsed.severity_availability_mask,
+                parsed.format
+            ),
+            (0x33, 0xFF, 0xE0, 0x04)
+        );
+        let dtcs: Vec<(u8, u32, u8)> = parsed
+            .dtcs
+            .iter()
+            .map(|record| (record.severity, record.dtc.code, record.dtc.status))
+            .collect();
+        assert_eq!(dtcs, [(0x40, 0x010011, 0x09), (0x80, 0xC12300, 0x2F)]);
+
+        assert!(parse_wwh_obd_dtcs(&[0x33, 0xFF, 0xE0, 0x04])
+            .unwrap()
+            .dtcs
+            .is_empty());
+        let malformed: [&[u8]; 2] = [
+            &[0x33, 0xFF, 0xE0],
+            &[0x33, 0xFF, 0xE0, 0x04, 0x40, 0x01, 0x00, 0x11],
+        ];
+        for data in malformed {
+            assert!(parse_wwh_obd_dtcs(data).is_err(), "{:02X?}", data);
+        }
+    }
+
+    #[test]
+    fn permanent_dtcs() {
+        let parsed = parse_permanent_dtcs(&[
+            0x33, 0xFF, 0x04, 0x01, 0x00, 0x11, 0x09, 0xC1, 0x23, 0x00, 0x08,
+        ])
+        .unwrap();
+        assert_eq!(
+            (
+                parsed.functional_group,
+                parsed.availability_mask,
+                parsed.format
+            ),
+            (0x33, 0xFF, 0x04)
+        );
+        assert_eq!(
+            parsed.dtcs,
+            [
+                DTC,
+                DtcStatusRecord {
+                    code: 0xC12300,
+                    status: 0x08
+                }
+            ]
+        );
+
+        assert!(parse_permanent_dtcs(&[0x33, 0xFF, 0x04])
+            .unwrap()
+            .dtcs
+            .is_empty());
+        let malformed: [&[u8]; 2] = [&[0x33, 0xFF], &[0x33, 0xFF, 0x04, 0x01, 0x00, 0x11]];
+        for data in malformed {
+            assert!(parse_permanent_dtcs(data).is_err(), "{:02X?}", data);
+        }
+    }
+
+    #[test]
+    fn status_bits_are_named() {
+        let status = status_json(0x09);
+        assert_eq!(status["mask"], "0x09");
+        assert_eq!(status["testFailed"], true);
+        assert_eq!(status["confirmedDTC"], true);
+        assert_eq!(status["pendingDTC"], false);
+        assert_eq!(status["warningIndicatorRequested"], false);
+        assert_eq!(status.as_object().unwrap().len(), 9);
+        assert_eq!(status_json(0x80)["warningIndicatorRequested"], true);
+    }
+
+    #[test]
+    fn status_filters() {
+        let cases = [
+            ("confirmed,pending", 0x0C),
+            ("confirmedDTC", 0x08),
+            ("test_failed", 0x01),
+            ("Pending-DTC", 0x04),
+            (" failed , mil ", 0x81),
+            ("0x0C", 0x0C),
+            ("confirmed,0x20", 0x28),
+        ];
+        for (filter, mask) in cases {
+            assert_eq!(parse_status_filter(filter).unwrap(), mask, "{}", filter);
+        }
+        for filter in ["", ",", "bogus", "confirmed,bogus", "0x", "0x100", "0x00"] {
+            assert!(
+                matches!(parse_status_filter(filter), Err(Sovd2UdsError::InvalidRequest(_))),
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn parse_codes_and_numbers() {
+        let cases = [
+            ("P0100-11", 0x010011),
+            ("P010011", 0x010011),
+            ("P0100", 0x010000),
+            ("p0100-11", 0x010011),
+            (" P0100-11 ", 0x010011),
+            ("U3FFF-FF", 0xFFFFFF),
+            ("B1234", 0x923400),
+            // Codes that are hex numbers as well are read as codes
+            ("C0123", 0x412300),
+            ("C012311", 0x412311),
+            ("0xC0123", 0x0C0123),
+            ("0X0C0123", 0x0C0123),
+            ("0x010011", 0x010011),
+            ("010011", 0x010011),
+            ("C01234", 0xC01234),
+            ("11", 0x000011),
+        ];
+        for (text, code) in cases {
+            assert_eq!(parse_dtc(text).unwrap(), code, "{}", text);
+        }
+    }
+
+    #[test]
+    fn invalid_dtcs_are_rejected() {
+        let cases = [
+            "",
+            "P4100",
+            "P01G0",
+            "P0100-1",
+            "P0100+11",
+            "P0100-111",
+            "0x1000000",
+            "-0x10",
+            "0x",
+            "P0100-1G",
+        ];
+        for text in cases {
+            assert!(
+                matches!(parse_dtc(text), Err(Sovd2UdsError::InvalidRequest(_))),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn format_codes() {
+        let cases = [
+            (0x010011, "P0100-11", "0x010011"),
+            (0x412300, "C0123-00", "0x412300"),
+            (0x923400, "B1234-00", "0x923400"),
+            (0xFFFFFF, "U3FFF-FF", "0xFFFFFF"),
+            (0x0C0123, "P0C01-23", "0x0C0123"),
+        ];
+        for (code, obd, uds) in cases {
+            assert_eq!(format_dtc(code), obd);
+            assert_eq!(format_uds_dtc(code), uds);
+            assert_eq!(parse_dtc(obd).unwrap(), code, "{}", obd);
+            assert_eq!(parse_dtc(uds).unwrap(), code, "{}", uds);
+        }
+    }
+}
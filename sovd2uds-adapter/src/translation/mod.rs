@@ -1,160 +1,443 @@
-use crate::error::{Result, Sovd2UdsError};
-use crate::models::uds::data_identifiers;
+pub mod codec;
+pub mod dictionary;
+pub mod dtc;
+
+pub use codec::{ByteOrder, Codec, Conversion, DataType, FieldDefinition, TextTableEntry};
+pub use dictionary::{
+    DataAccess, DataDictionary, DataItemDefinition, DtcDefinition, FailureTypeDefinition,
+    RoutineDefinition, SessionDefinition,
+};
+
+use crate::config::Config;
+use crate::error::{Result, SharedResult, Sovd2UdsError, UdsNegativeResponseCode};
 use crate::models::*;
+use crate::odx;
 use crate::uds::UdsClient;
 use chrono::Utc;
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use tracing::{debug, info};
 
+/// A data item of a bulk read: its position in the request, DID and length
+#[derive(Debug, Clone, Copy)]
+struct BulkItem {
+    index: usize,
+    did: u16,
+    length: Option<usize>,
+}
+
+/// Results and message of a DTC action
+type DtcOutcome = (Option<serde_json::Value>, String);
+
+/// Definitions of one component, or of all components
+#[derive(Debug, Default)]
+struct Catalog {
+    /// Data items by SOVD ID
+    items: HashMap<String, DataItemDefinition>,
+    /// Routines by SOVD actuator ID
+    routines: HashMap<String, RoutineDefinition>,
+    /// DTCs by 24-bit number
+    dtcs: HashMap<u32, DtcDefinition>,
+    /// Failure types by failure type byte
+    failure_types: HashMap<u8, FailureTypeDefinition>,
+    /// Sessions by DiagnosticSessionControl sub-function
+    sessions: HashMap<u8, SessionDefinition>,
+}
+
+impl Catalog {
+    /// Add definitions, replacing earlier ones with the same ID
+    fn add(&mut self, dictionary: DataDictionary) {
+        for item in dictionary.items {
+            self.items.insert(item.id.clone(), item);
+        }
+        for routine in dictionary.routines {
+            self.routines.insert(routine.id.clone(), routine);
+        }
+        for dtc in dictionary.dtcs {
+            self.dtcs.insert(dtc.code, dtc);
+        }
+        for failure_type in dictionary.failure_types {
+            self.failure_types.insert(failure_type.value, failure_type);
+        }
+        for session in dictionary.sessions {
+            self.sessions.insert(session.id, session);
+        }
+    }
+}
+
+/// A known component and its own definitions
+#[derive(Debug)]
+struct ComponentCatalog {
+    component: Component,
+    catalog: Catalog,
+}
+
+impl ComponentCatalog {
+    /// Component named like its ID, without definitions
+    fn unnamed(component_id: &str) -> Self {
+        Self {
+            component: Component {
+                id: component_id.to_string(),
+                name: component_id.to_string(),
+                description: None,
+            },
+            catalog: Catalog::default(),
+        }
+    }
+}
+
 /// Translator between SOVD and UDS protocols
 pub struct SovdUdsTranslator {
-    /// Mapping of SOVD data item IDs to UDS DIDs
-    did_mappings: HashMap<String, u16>,
-    /// Mapping of SOVD component categories
-    category_mappings: HashMap<String, Vec<String>>,
+    /// Definitions for all components
+    base: Catalog,
+    /// Shared definitions by name, inherited by some components
+    bases: HashMap<String, Catalog>,
+    /// Known components and their definitions, taking precedence over `base`
+    components: HashMap<String, ComponentCatalog>,
+    /// Shared definitions (in `bases`) a component inherits
+    inherits: HashMap<String, String>,
 }
 
 impl SovdUdsTranslator {
     /// Create a new translator with default mappings
     pub fn new() -> Self {
         let mut translator = Self {
-            did_mappings: HashMap::new(),
-            category_mappings: HashMap::new(),
+            base: Catalog::default(),
+            bases: HashMap::new(),
+            components: HashMap::new(),
+            inherits: HashMap::new(),
         };
 
         translator.initialize_default_mappings();
         translator
     }
 
+    /// Create a translator for the component registry and data dictionaries
+    /// of the configuration
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut translator = Self::new();
+        for (component_id, component) in &config.components {
+            translator.add_component(Component {
+                id: component_id.clone(),
+                name: component.name.clone().unwrap_or_else(|| component_id.clone()),
+                description: component.description.clone(),
+            });
+        }
+
+        if !config.dictionary.odx.is_empty() {
+            translator.import_odx(config)?;
+        }
+        for path in &config.dictionary.files {
+            let dictionary = DataDictionary::load(path)?;
+            info!("Loaded {} data items from '{}'", dictionary.items.len(), path);
+            translator.add_dictionary(None, dictionary);
+        }
+        for (name, paths) in &config.dictionary.bases {
+            for path in paths {
+                let dictionary = DataDictionary::load(path)?;
+                info!(
+                    "Loaded {} data items for base '{}' from '{}'",
+                    dictionary.items.len(),
+                    name,
+                    path
+                );
+                translator.add_base(name, dictionary);
+            }
+        }
+        for (component_id, component) in &config.components {
+            if let Some(base) = &component.inherit {
+                translator.inherit(component_id, base)?;
+            }
+            for path in &component.dictionaries {
+                let dictionary = DataDictionary::load(path)?;
+                info!(
+                    "Loaded {} data items for component '{}' from '{}'",
+                    dictionary.items.len(),
+                    component_id,
+                    path
+                );
+                translator.add_dictionary(Some(component_id), dictionary);
+            }
+        }
+
+        Ok(translator)
+    }
+
+    /// Add the definitions of the ODX variant of each component
+    fn import_odx(&mut self, config: &Config) -> Result<()> {
+        let layers = odx::import(&config.dictionary.odx)?;
+        let mut assigned = Vec::new();
+
+        for (component_id, component) in &config.components {
+            let mapped = component.odx_variant.as_ref();
+            let variant = mapped.unwrap_or(component_id);
+            let Some(layer) = layers.iter().find(|layer| layer.name.eq_ignore_ascii_case(variant))
+            else {
+                if mapped.is_some() {
+                    return Err(Sovd2UdsError::Config(format!(
+                        "ODX variant '{}' of component '{}' not found",
+                        variant, component_id
+                    )));
+                }
+                continue;
+            };
+
+            let dictionary = &layer.dictionary;
+            info!(
+                "Imported {} data items, {} routines and {} DTCs of ODX variant '{}' for '{}'",
+                dictionary.items.len(),
+                dictionary.routines.len(),
+                dictionary.dtcs.len(),
+                layer.name,
+                component_id
+            );
+            self.add_dictionary(Some(component_id), dictionary.clone());
+            assigned.push(layer.name.as_str());
+        }
+
+        for layer in layers.iter().filter(|layer| !assigned.contains(&layer.name.as_str())) {
+            debug!("ODX variant '{}' is not assigned to a component", layer.name);
+        }
+        Ok(())
+    }
+
     /// Initialize default SOVD to UDS mappings
     fn initialize_default_mappings(&mut self) {
-        // Map common SOVD data items to UDS DIDs
-        self.did_mappings.insert("vin".to_string(), data_identifiers::VIN);
-        self.did_mappings.insert("ecu_serial_number".to_string(), data_identifiers::ECU_SERIAL_NUMBER);
-        self.did_mappings.insert("ecu_hardware_version".to_string(), data_identifiers::ECU_HARDWARE_VERSION);
-        self.did_mappings.insert("ecu_software_version".to_string(), data_identifiers::ECU_SOFTWARE_VERSION);
-        self.did_mappings.insert("manufacturing_date".to_string(), data_identifiers::ECU_MANUFACTURING_DATE);
-        self.did_mappings.insert("system_supplier_id".to_string(), data_identifiers::SYSTEM_SUPPLIER_ID);
+        // Identification DIDs and simulator routines
+        self.add_dictionary(None, DataDictionary::builtin());
+    }
 
-        // Category mappings
-        let mut ident_data = Vec::new();
-        ident_data.push("vin".to_string());
-        ident_data.push("ecu_serial_number".to_string());
-        ident_data.push("ecu_hardware_version".to_string());
-        ident_data.push("ecu_software_version".to_string());
-        ident_data.push("manufacturing_date".to_string());
-        ident_data.push("system_supplier_id".to_string());
+    /// Add a component to the registry, keeping its definitions if it is
+    /// known already; it has the definitions for all components until it
+    /// gets its own
+    pub fn add_component(&mut self, component: Component) {
+        match self.components.get_mut(&component.id) {
+            Some(known) => known.component = component,
+            None => {
+                let catalog = ComponentCatalog {
+                    component,
+                    catalog: Catalog::default(),
+                };
+                self.components.insert(catalog.component.id.clone(), catalog);
+            }
+        }
+    }
 
-        self.category_mappings.insert("identData".to_string(), ident_data);
+    /// Add definitions for all components, or for one component, which
+    /// becomes known; definitions replace earlier ones with the same ID
+    pub fn add_dictionary(&mut self, component_id: Option<&str>, dictionary: DataDictionary) {
+        let catalog = match component_id {
+            Some(component_id) => {
+                &mut self
+                    .components
+                    .entry(component_id.to_string())
+                    .or_insert_with(|| ComponentCatalog::unnamed(component_id))
+                    .catalog
+            }
+            None => &mut self.base,
+        };
+        catalog.add(dictionary);
     }
 
-    /// Add a custom DID mapping
-    pub fn add_did_mapping(&mut self, sovd_id: String, did: u16) {
-        self.did_mappings.insert(sovd_id, did);
+    /// Add definitions to a shared base, which components inherit with
+    /// [`Self::inherit`]
+    pub fn add_base(&mut self, name: &str, dictionary: DataDictionary) {
+        self.bases.entry(name.to_string()).or_default().add(dictionary);
     }
 
-    /// Get UDS DID for a SOVD data item ID
-    pub fn get_did(&self, data_item_id: &str) -> Option<u16> {
-        self.did_mappings.get(data_item_id).copied()
+    /// Let a component inherit the definitions of a shared base
+    pub fn inherit(&mut self, component_id: &str, base: &str) -> Result<()> {
+        if !self.components.contains_key(component_id) {
+            return Err(Sovd2UdsError::Config(format!(
+                "Base '{}' inherited by unknown component '{}'",
+                base, component_id
+            )));
+        }
+        if !self.bases.contains_key(base) {
+            return Err(Sovd2UdsError::Config(format!(
+                "Unknown base '{}' inherited by component '{}'",
+                base, component_id
+            )));
+        }
+        self.inherits.insert(component_id.to_string(), base.to_string());
+        Ok(())
     }
 
-    /// Get all components (static list for now)
-    pub fn get_components(&self) -> Vec<Component> {
-        vec![
-            Component {
-                id: "engine".to_string(),
-                name: "Engine Control Unit".to_string(),
-                description: Some("Main engine control unit".to_string()),
-            },
-            Component {
-                id: "transmission".to_string(),
-                name: "Transmission Control Unit".to_string(),
-                description: Some("Automatic transmission control".to_string()),
-            },
-            Component {
-                id: "abs".to_string(),
-                name: "ABS Control Unit".to_string(),
-                description: Some("Anti-lock braking system".to_string()),
-            },
-            Component {
-                id: "airbag".to_string(),
-                name: "Airbag Control Unit".to_string(),
-                description: Some("Airbag and restraint system".to_string()),
-            },
-        ]
+    /// Catalogs a component's definitions come from, lowest precedence first,
+    /// or `ComponentNotFound`
+    fn catalogs(&self, component_id: &str) -> Result<Vec<&Catalog>> {
+        let catalog = self
+            .components
+            .get(component_id)
+            .map(|known| &known.catalog)
+            .ok_or_else(|| Sovd2UdsError::ComponentNotFound(component_id.to_string()))?;
+        let base = self
+            .inherits
+            .get(component_id)
+            .and_then(|base| self.bases.get(base));
+        Ok([Some(&self.base), base, Some(catalog)]
+            .into_iter()
+            .flatten()
+            .collect())
     }
 
-    /// Get data items for a component
-    pub fn get_component_data_items(&self, categories: Option<&[String]>) -> Vec<DataItem> {
-        let mut items = Vec::new();
+    /// First definition `find` returns from a component's catalogs, in order
+    /// of precedence; none for unknown components
+    fn lookup<'a, T>(
+        &'a self,
+        component_id: &str,
+        find: impl Fn(&'a Catalog) -> Option<&'a T>,
+    ) -> Option<&'a T> {
+        self.catalogs(component_id)
+            .ok()?
+            .into_iter()
+            .rev()
+            .find_map(find)
+    }
 
-        // Filter by categories if specified
-        let item_ids: Vec<String> = if let Some(cats) = categories {
-            cats.iter()
-                .filter_map(|cat| self.category_mappings.get(cat))
-                .flatten()
-                .cloned()
-                .collect()
-        } else {
-            self.did_mappings.keys().cloned().collect()
-        };
+    /// Run `operation` in the required session, if any; the client enters it
+    /// and restores the previous session afterwards
+    async fn in_session<T>(
+        client: &UdsClient,
+        session: Option<DiagnosticSessionType>,
+        operation: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        match session {
+            Some(session) => client.with_session(session, operation).await,
+            None => operation.await,
+        }
+    }
+
+    /// Definition of a data item of a component
+    pub fn get_definition(
+        &self,
+        component_id: &str,
+        data_item_id: &str,
+    ) -> Option<&DataItemDefinition> {
+        self.lookup(component_id, |catalog| catalog.items.get(data_item_id))
+    }
 
-        for item_id in item_ids {
-            items.push(self.create_data_item(&item_id));
+    /// Definition of a routine of a component
+    pub fn get_routine(&self, component_id: &str, actuator_id: &str) -> Option<&RoutineDefinition> {
+        self.lookup(component_id, |catalog| catalog.routines.get(actuator_id))
+    }
+
+    /// Definitions of the data items of a DID on a component, sorted by ID
+    pub fn did_definitions(&self, component_id: &str, did: u16) -> Vec<&DataItemDefinition> {
+        let mut definitions: HashMap<&str, &DataItemDefinition> = HashMap::new();
+        for catalog in self.catalogs(component_id).unwrap_or_default() {
+            for item in catalog.items.values().filter(|item| item.did == did) {
+                definitions.insert(&item.id, item);
+            }
         }
+        let mut definitions: Vec<_> = definitions.into_values().collect();
+        definitions.sort_by(|a, b| a.id.cmp(&b.id));
+        definitions
+    }
 
-        items
+    /// Definition of a DTC of a component
+    pub fn get_dtc(&self, component_id: &str, code: u32) -> Option<&DtcDefinition> {
+        self.lookup(component_id, |catalog| catalog.dtcs.get(&code))
     }
 
-    /// Create a DataItem from an ID
-    fn create_data_item(&self, item_id: &str) -> DataItem {
-        let (name, category, data_type, description) = match item_id {
-            "vin" => (
-                "Vehicle Identification Number",
-                "identData",
-                "string",
-                "Unique vehicle identification number",
-            ),
-            "ecu_serial_number" => (
-                "ECU Serial Number",
-                "identData",
-                "string",
-                "ECU serial number",
-            ),
-            "ecu_hardware_version" => (
-                "ECU Hardware Version",
-                "identData",
-                "string",
-                "ECU hardware version",
-            ),
-            "ecu_software_version" => (
-                "ECU Software Version",
-                "identData",
-                "string",
-                "ECU software version",
-            ),
-            "manufacturing_date" => (
-                "Manufacturing Date",
-                "identData",
-                "string",
-                "ECU manufacturing date",
-            ),
-            "system_supplier_id" => (
-                "System Supplier ID",
-                "identData",
-                "string",
-                "System supplier identifier",
-            ),
-            _ => (item_id, "unknown", "string", "Unknown data item"),
+    /// Definition of a DTC, or else of its code with failure type 00, which
+    /// stands for all failure types
+    fn dtc_definition(&self, component_id: &str, code: u32) -> Option<&DtcDefinition> {
+        self.get_dtc(component_id, code)
+            .or_else(|| self.get_dtc(component_id, code & 0xFF_FF00))
+    }
+
+    /// Meaning of a DTC failure type byte on a component
+    pub fn get_failure_type(
+        &self,
+        component_id: &str,
+        failure_type: u8,
+    ) -> Option<&FailureTypeDefinition> {
+        self.lookup(component_id, |catalog| catalog.failure_types.get(&failure_type))
+    }
+
+    /// Name of a diagnostic session of a component ("extended", ...)
+    pub fn session_name(&self, component_id: &str, session: u8) -> String {
+        self.lookup(component_id, |catalog| catalog.sessions.get(&session))
+            .map(|definition| definition.name.clone())
+            .or_else(|| DiagnosticSessionType::from_u8(session).map(|s| s.name().to_string()))
+            .unwrap_or_else(|| format!("0x{:02X}", session))
+    }
+
+    /// Security level unlocked before writing a data item, if it needs one
+    pub fn write_security_level(
+        &self,
+        component_id: &str,
+        data_item_id: &str,
+    ) -> Result<Option<u8>> {
+        Ok(self.definition(component_id, data_item_id)?.security_level)
+    }
+
+    /// Definition of a data item, or `ComponentNotFound` / `DataItemNotFound`
+    fn definition(&self, component_id: &str, data_item_id: &str) -> Result<&DataItemDefinition> {
+        self.catalogs(component_id)?
+            .into_iter()
+            .rev()
+            .find_map(|catalog| catalog.items.get(data_item_id))
+            .ok_or_else(|| {
+                Sovd2UdsError::DataItemNotFound(format!(
+                    "'{}' on component '{}'",
+                    data_item_id, component_id
+                ))
+            })
+    }
+
+    /// Get all components of the registry, sorted by ID
+    pub fn get_components(&self) -> Vec<Component> {
+        let mut components: Vec<Component> = self
+            .components
+            .values()
+            .map(|known| known.component.clone())
+            .collect();
+        components.sort_by(|a, b| a.id.cmp(&b.id));
+        components
+    }
+
+    /// Get data items for a component, sorted by DID
+    pub fn get_component_data_items(
+        &self,
+        component_id: &str,
+        categories: Option<&[String]>,
+    ) -> Result<Vec<DataItem>> {
+        let mut definitions: HashMap<&str, &DataItemDefinition> = HashMap::new();
+        for catalog in self.catalogs(component_id)? {
+            definitions.extend(catalog.items.iter().map(|(id, item)| (id.as_str(), item)));
+        }
+
+        // Filter by categories if specified
+        let mut definitions: Vec<&DataItemDefinition> = definitions
+            .into_values()
+            .filter(|item| categories.is_none_or(|cats| cats.contains(&item.category)))
+            .collect();
+        definitions.sort_by(|a, b| (a.did, &a.id).cmp(&(b.did, &b.id)));
+
+        Ok(definitions.into_iter().map(Self::create_data_item).collect())
+    }
+
+    /// Create a DataItem from its definition
+    fn create_data_item(definition: &DataItemDefinition) -> DataItem {
+        let name = if definition.name.is_empty() {
+            &definition.id
+        } else {
+            &definition.name
         };
 
         DataItem {
-            id: item_id.to_string(),
-            name: name.to_string(),
-            category: category.to_string(),
-            data_type: Some(data_type.to_string()),
-            description: Some(description.to_string()),
+            id: definition.id.clone(),
+            name: name.clone(),
+            category: definition.category.clone(),
+            data_type: Some(definition.codec.data_type.name().to_string()),
+            description: definition.description.clone(),
+            session: definition.session.map(|session| session.name().to_string()),
+            length: definition.codec.byte_length(),
+            unit: definition.codec.unit.clone(),
+            access: Some(definition.access.name().to_string()),
         }
     }
 
@@ -166,57 +449,288 @@ impl SovdUdsTranslator {
     ) -> Result<DataItemValue> {
         debug!("Reading data item: {}", data_item_id);
 
-        // Get DID for the data item
-        let did = self
-            .get_did(data_item_id)
-            .ok_or_else(|| Sovd2UdsError::DataItemNotFound(data_item_id.to_string()))?;
+        let definition = self.definition(client.component_id(), data_item_id)?;
+        if !definition.access.readable() {
+            return Err(Sovd2UdsError::InvalidRequest(format!(
+                "Data item '{}' is write-only",
+                data_item_id
+            )));
+        }
 
         // Read from UDS
-        let raw_data = client.read_data_by_identifier(did).await?;
+        let did = definition.did;
+        let raw_data =
+            Self::in_session(client, definition.session, client.read_data_by_identifier(did))
+                .await?;
 
-        // Convert to appropriate format
-        let data_item = self.create_data_item(data_item_id);
-        let data_value = self.convert_uds_data_to_sovd(&raw_data, &data_item.data_type.unwrap_or_else(|| "string".to_string()))?;
+        Self::data_item_value(definition, &raw_data)
+    }
 
-        Ok(DataItemValue {
-            id: data_item_id.to_string(),
-            name: data_item.name,
-            category: data_item.category,
-            data: data_value,
-            timestamp: Some(Utc::now()),
-            quality: Some("good".to_string()),
-        })
+    /// Read several data items, combining them into multi-DID
+    /// ReadDataByIdentifier requests
+    ///
+    /// Items are grouped by the session they are read in. A request holds at
+    /// most `max_dids` DIDs and asks for at most `max_response_length` bytes;
+    /// an item without a fixed length goes last in its request, since the
+    /// response can only be split by the lengths before it. Returns each
+    /// item's value or error in the requested order.
+    pub async fn read_data_items(
+        &self,
+        client: &UdsClient,
+        item_ids: &[String],
+        max_dids: usize,
+        max_response_length: usize,
+    ) -> Vec<(String, SharedResult<DataItemValue>)> {
+        debug!("Reading {} data items", item_ids.len());
+
+        let mut raw: Vec<Option<SharedResult<Vec<u8>>>> = item_ids.iter().map(|_| None).collect();
+        let mut groups: Vec<(Option<DiagnosticSessionType>, Vec<BulkItem>)> = Vec::new();
+        let mut definitions = Vec::new();
+        for (index, item_id) in item_ids.iter().enumerate() {
+            let definition = self.definition(client.component_id(), item_id);
+            let definition = match definition {
+                Ok(definition) if definition.access.readable() => definition,
+                Ok(_) => {
+                    raw[index] = Some(Err(Arc::new(Sovd2UdsError::InvalidRequest(format!(
+                        "Data item '{}' is write-only",
+                        item_id
+                    )))));
+                    definitions.push(None);
+                    continue;
+                }
+                Err(e) => {
+                    raw[index] = Some(Err(Arc::new(e)));
+                    definitions.push(None);
+                    continue;
+                }
+            };
+            definitions.push(Some(definition));
+            let item = BulkItem {
+                index,
+                did: definition.did,
+                length: definition.codec.byte_length(),
+            };
+            let session = definition.session;
+            match groups.iter_mut().find(|(s, _)| *s == session) {
+                Some((_, items)) => items.push(item),
+                None => groups.push((session, vec![item])),
+            }
+        }
+
+        for (session, items) in groups {
+            let batches = Self::plan_batches(&items, max_dids, max_response_length);
+            let read = Self::in_session(client, session, async {
+                let mut values = Vec::new();
+                for batch in &batches {
+                    values.extend(Self::read_batch(client, batch).await);
+                }
+                Ok(values)
+            })
+            .await;
+
+            match read {
+                Ok(values) => {
+                    for (index, value) in values {
+                        raw[index] = Some(value);
+                    }
+                }
+                Err(e) => {
+                    let e = Arc::new(e);
+                    for item in &items {
+                        raw[item.index] = Some(Err(e.clone()));
+                    }
+                }
+            }
+        }
+
+        item_ids
+            .iter()
+            .zip(definitions)
+            .zip(raw)
+            .map(|((item_id, definition), raw_data)| {
+                let value = raw_data
+                    .unwrap_or_else(|| {
+                        Err(Arc::new(Sovd2UdsError::Internal("Item not read".to_string())))
+                    })
+                    .and_then(|data| match definition {
+                        Some(definition) => {
+                            Self::data_item_value(definition, &data).map_err(Arc::new)
+                        }
+                        None => Err(Arc::new(Sovd2UdsError::DataItemNotFound(item_id.clone()))),
+                    });
+                (item_id.clone(), value)
+            })
+            .collect()
     }
 
-    /// Convert UDS raw data to SOVD JSON value
-    fn convert_uds_data_to_sovd(&self, data: &[u8], data_type: &str) -> Result<serde_json::Value> {
-        match data_type {
-            "string" => {
-                let s = String::from_utf8(data.to_vec())
-                    .unwrap_or_else(|_| hex::encode(data));
-                Ok(serde_json::Value::String(s))
+    /// Split items into requests within the DID count and response length
+    /// limits, fixed-length items first
+    fn plan_batches(
+        items: &[BulkItem],
+        max_dids: usize,
+        max_response_length: usize,
+    ) -> Vec<Vec<BulkItem>> {
+        let mut ordered = items.to_vec();
+        ordered.sort_by_key(|item| item.length.is_none());
+
+        let mut batches = Vec::new();
+        let mut batch: Vec<BulkItem> = Vec::new();
+        // Response SID
+        let mut response_length = 1;
+        for item in ordered {
+            let item_length = 2 + item.length.unwrap_or(0);
+            let full = batch.last().is_some_and(|last| last.length.is_none())
+                || batch.len() >= max_dids.max(1)
+                || response_length + item_length > max_response_length;
+            if full && !batch.is_empty() {
+                batches.push(std::mem::take(&mut batch));
+                response_length = 1;
             }
-            "number" => {
-                if data.len() == 1 {
-                    Ok(serde_json::Value::Number(data[0].into()))
-                } else if data.len() == 2 {
-                    let value = u16::from_be_bytes([data[0], data[1]]);
-                    Ok(serde_json::Value::Number(value.into()))
-                } else if data.len() == 4 {
-                    let value = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
-                    Ok(serde_json::Value::Number(value.into()))
-                } else {
-                    Ok(serde_json::Value::String(hex::encode(data)))
+            response_length += item_length;
+            batch.push(item);
+        }
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+        batches
+    }
+
+    /// Read one multi-DID request and split the response by DID
+    ///
+    /// When the ECU rejects the request's length, the items are read one by one.
+    async fn read_batch(
+        client: &UdsClient,
+        batch: &[BulkItem],
+    ) -> Vec<(usize, SharedResult<Vec<u8>>)> {
+        let dids: Vec<u16> = batch.iter().map(|item| item.did).collect();
+        match client.read_data_by_identifiers(&dids).await {
+            Ok(data) => Self::split_records(&data, batch),
+            Err(Sovd2UdsError::UdsProtocol { nrc, .. })
+                if batch.len() > 1
+                    && matches!(
+                        UdsNegativeResponseCode::from_u8(nrc),
+                        Some(
+                            UdsNegativeResponseCode::IncorrectMessageLengthOrInvalidFormat
+                                | UdsNegativeResponseCode::ResponseTooLong
+                        )
+                    ) =>
+            {
+                debug!("Multi-DID request rejected, reading {} DIDs one by one", batch.len());
+                let mut values = Vec::new();
+                for item in batch {
+                    let value = client.read_data_by_identifier(item.did).await;
+                    values.push((item.index, value.map_err(Arc::new)));
                 }
+                values
             }
-            "boolean" => {
-                Ok(serde_json::Value::Bool(data.first().map(|&b| b != 0).unwrap_or(false)))
+            Err(e) => {
+                let e = Arc::new(e);
+                batch.iter().map(|item| (item.index, Err(e.clone()))).collect()
             }
-            _ => {
-                // Default: hex string
-                Ok(serde_json::Value::String(hex::encode(data)))
+        }
+    }
+
+    /// Split a multi-DID response into the data of each requested DID
+    ///
+    /// DIDs the ECU left out get NRC 0x31 (request out of range).
+    fn split_records(data: &[u8], batch: &[BulkItem]) -> Vec<(usize, SharedResult<Vec<u8>>)> {
+        let mut values: Vec<Option<SharedResult<Vec<u8>>>> = batch.iter().map(|_| None).collect();
+        let mut failure = None;
+        let mut position = 0;
+        let mut next = 0;
+        while position < data.len() {
+            let Some(&[high, low]) = data.get(position..position + 2) else {
+                failure = Some("Truncated DID record".to_string());
+                break;
+            };
+            let did = u16::from_be_bytes([high, low]);
+            let Some(slot) = batch[next..].iter().position(|item| item.did == did) else {
+                failure = Some(format!("Unexpected DID 0x{:04X} in response", did));
+                break;
+            };
+            let slot = next + slot;
+            let start = position + 2;
+            let end = batch[slot].length.map_or(data.len(), |length| start + length);
+            if end > data.len() {
+                failure = Some(format!("Truncated data for DID 0x{:04X}", did));
+                break;
             }
+            values[slot] = Some(Ok(data[start..end].to_vec()));
+            position = end;
+            next = slot + 1;
         }
+
+        batch
+            .iter()
+            .zip(values)
+            .map(|(item, value)| {
+                let value = value.unwrap_or_else(|| {
+                    Err(Arc::new(match &failure {
+                        Some(message) => Sovd2UdsError::UdsCommunication(message.clone()),
+                        None => Sovd2UdsError::negative_response(
+                            UdsServiceId::ReadDataByIdentifier as u8,
+                            UdsNegativeResponseCode::RequestOutOfRange as u8,
+                        ),
+                    }))
+                });
+                (item.index, value)
+            })
+            .collect()
+    }
+
+    /// Write a data item value with UDS and read it back
+    ///
+    /// Writes run in the item's session, or the extended session when it has
+    /// none, after unlocking the item's security level if one is required.
+    pub async fn write_data_item(
+        &self,
+        client: &UdsClient,
+        data_item_id: &str,
+        value: &serde_json::Value,
+    ) -> Result<DataItemValue> {
+        debug!("Writing data item: {}", data_item_id);
+
+        let definition = self.definition(client.component_id(), data_item_id)?;
+        if !definition.access.writable() {
+            return Err(Sovd2UdsError::InvalidRequest(format!(
+                "Data item '{}' is read-only",
+                data_item_id
+            )));
+        }
+
+        let did = definition.did;
+        let data = definition.codec.encode(value)?;
+
+        let session = definition
+            .session
+            .unwrap_or(DiagnosticSessionType::ExtendedDiagnosticSession);
+        let raw_data = client
+            .with_session(session, async {
+                if let Some(level) = definition.security_level {
+                    client.unlock_security(level).await?;
+                }
+                client.write_data_by_identifier(did, &data).await?;
+                client.read_data_by_identifier(did).await
+            })
+            .await?;
+
+        Self::data_item_value(definition, &raw_data)
+    }
+
+    /// Build the SOVD value of a data item from its raw UDS data
+    fn data_item_value(definition: &DataItemDefinition, raw_data: &[u8]) -> Result<DataItemValue> {
+        let data_item = Self::create_data_item(definition);
+        let data_value = definition.codec.decode(raw_data)?;
+
+        Ok(DataItemValue {
+            id: data_item.id,
+            name: data_item.name,
+            category: data_item.category,
+            data: data_value,
+            unit: data_item.unit,
+            timestamp: Some(Utc::now()),
+            quality: Some("good".to_string()),
+        })
     }
 
     /// Translate DTC management request to UDS operations
@@ -225,120 +739,421 @@ impl SovdUdsTranslator {
         client: &UdsClient,
         request: &DtcManagementRequest,
     ) -> Result<DtcManagementResponse> {
-        match request.action.as_str() {
-            "clear" => {
-                // Clear all DTCs (group 0xFFFFFF)
-                client.clear_diagnostic_information(0xFFFFFF).await?;
-                
-                Ok(DtcManagementResponse {
-                    action: "clear".to_string(),
-                    status: "success".to_string(),
-                    results: None,
-                    message: Some("All DTCs cleared successfully".to_string()),
-                    timestamp: Some(Utc::now()),
-                })
-            }
-            "read" => {
-                // Read DTCs with status mask (sub-function 0x02: report DTC by status mask)
-                let dtc_data = client.read_dtc_information(0x02).await?;
-                
-                // Parse DTC data
-                let dtcs = self.parse_dtc_data(&dtc_data)?;
-                
-                Ok(DtcManagementResponse {
-                    action: "read".to_string(),
-                    status: "success".to_string(),
-                    results: Some(serde_json::json!({ "dtcs": dtcs })),
-                    message: Some(format!("Found {} DTCs", dtcs.len())),
-                    timestamp: Some(Utc::now()),
-                })
-            }
-            "freeze_frame" => {
-                // Read freeze frame data (sub-function 0x04)
-                let freeze_frame_data = client.read_dtc_information(0x04).await?;
-                
-                Ok(DtcManagementResponse {
-                    action: "freeze_frame".to_string(),
-                    status: "success".to_string(),
-                    results: Some(serde_json::json!({ 
-                        "freeze_frame_data": hex::encode(&freeze_frame_data) 
-                    })),
-                    message: Some("Freeze frame data retrieved".to_string()),
-                    timestamp: Some(Utc::now()),
-                })
-            }
+        let (results, message) = match request.action.as_str() {
+            "clear" => self.clear_dtcs(client, request).await?,
+            "read" => self.read_dtcs(client, request).await?,
+            "count" => self.count_dtcs(client, request).await?,
+            "supported" => self.supported_dtcs(client).await?,
+            "snapshot_ids" => self.snapshot_ids(client).await?,
+            "freeze_frame" => self.freeze_frames(client, request).await?,
+            "extended_data" => self.extended_data(client, request).await?,
+            "fault_detection_counter" => self.fault_detection_counters(client).await?,
+            "wwh_obd" => self.wwh_obd_dtcs(client, request).await?,
+            "permanent" => self.permanent_dtcs(client, request).await?,
             _ => {
-                Err(Sovd2UdsError::InvalidRequest(format!(
+                return Err(Sovd2UdsError::InvalidRequest(format!(
                     "Unknown DTC action: {}",
                     request.action
                 )))
             }
+        };
+
+        Ok(DtcManagementResponse {
+            action: request.action.clone(),
+            status: "success".to_string(),
+            results,
+            message: Some(message),
+            timestamp: Some(Utc::now()),
+        })
+    }
+
+    /// Clear the requested DTCs, or all DTCs (group 0xFFFFFF)
+    async fn clear_dtcs(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let Some(codes) = Self::requested_dtcs(request)? else {
+            client.clear_diagnostic_information(0xFFFFFF).await?;
+            return Ok((None, "All DTCs cleared successfully".to_string()));
+        };
+        for code in &codes {
+            client.clear_diagnostic_information(*code).await?;
         }
+        Ok((None, format!("{} DTCs cleared successfully", codes.len())))
     }
 
-    /// Parse DTC data from UDS response
-    fn parse_dtc_data(&self, data: &[u8]) -> Result<Vec<serde_json::Value>> {
-        let mut dtcs = Vec::new();
-        
-        // Skip status availability mask (first byte)
-        if data.len() < 1 {
-            return Ok(dtcs);
+    /// reportDTCByStatusMask, with all status bits unless a filter or mask
+    /// is given, limited to the requested DTCs, if any
+    async fn read_dtcs(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let mask = match &request.status {
+            Some(filter) => dtc::parse_status_filter(filter)?,
+            None => request.status_mask.unwrap_or(0xFF),
+        };
+        let data = client
+            .read_dtc_information(DtcReportType::DtcByStatusMask, &[mask])
+            .await?;
+        let mut list = dtc::parse_dtc_list(&data)?;
+        // Not every ECU applies the mask itself
+        list.dtcs.retain(|record| record.status & mask != 0);
+        if let Some(codes) = Self::requested_dtcs(request)? {
+            list.dtcs.retain(|record| codes.contains(&record.code));
         }
+        let dtcs = self.dtc_entries(client.component_id(), &list.dtcs);
+        let message = format!("Found {} DTCs", dtcs.len());
+        Ok((
+            Some(serde_json::json!({
+                "status_availability_mask": dtc::status_json(list.availability_mask),
+                "dtcs": dtcs,
+            })),
+            message,
+        ))
+    }
+
+    /// reportNumberOfDTCByStatusMask
+    async fn count_dtcs(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let mask = request.status_mask.unwrap_or(0xFF);
+        let data = client
+            .read_dtc_information(DtcReportType::NumberOfDtcByStatusMask, &[mask])
+            .await?;
+        let count = dtc::parse_count(&data)?;
+        Ok((
+            Some(serde_json::json!({
+                "count": count.count,
+                "status_availability_mask": dtc::status_json(count.availability_mask),
+                "dtc_format": dtc_formats::name(count.format),
+            })),
+            format!("{} DTCs match status mask 0x{:02X}", count.count, mask),
+        ))
+    }
 
-        let mut offset = 1;
-        
-        // Each DTC entry is 4 bytes: 3 bytes DTC + 1 byte status
-        while offset + 4 <= data.len() {
-            let dtc_bytes = &data[offset..offset + 3];
-            let status = data[offset + 3];
-            
-            // Convert DTC bytes to standard format (e.g., P0100)
-            let dtc_string = self.format_dtc(dtc_bytes);
-            
-            dtcs.push(serde_json::json!({
-                "code": dtc_string,
-                "status": format!("0x{:02X}", status),
-                "description": self.get_dtc_description(&dtc_string),
-            }));
-            
-            offset += 4;
+    /// reportSupportedDTC
+    async fn supported_dtcs(&self, client: &UdsClient) -> Result<DtcOutcome> {
+        let data = client
+            .read_dtc_information(DtcReportType::SupportedDtc, &[])
+            .await?;
+        let list = dtc::parse_dtc_list(&data)?;
+        let dtcs = self.dtc_entries(client.component_id(), &list.dtcs);
+        let message = format!("ECU supports {} DTCs", dtcs.len());
+        Ok((
+            Some(serde_json::json!({
+                "status_availability_mask": dtc::status_json(list.availability_mask),
+                "dtcs": dtcs,
+            })),
+            message,
+        ))
+    }
+
+    /// reportDTCSnapshotIdentification
+    async fn snapshot_ids(&self, client: &UdsClient) -> Result<DtcOutcome> {
+        let data = client
+            .read_dtc_information(DtcReportType::DtcSnapshotIdentification, &[])
+            .await?;
+        let snapshots: Vec<serde_json::Value> = dtc::parse_snapshot_identification(&data)?
+            .into_iter()
+            .map(|(code, record)| {
+                let mut entry = self.dtc_entry(client.component_id(), code);
+                entry["record"] = record.into();
+                entry
+            })
+            .collect();
+        let message = format!("Found {} snapshot records", snapshots.len());
+        Ok((
+            Some(serde_json::json!({ "snapshots": snapshots })),
+            message,
+        ))
+    }
+
+    /// reportDTCSnapshotRecordByDTCNumber for the requested DTCs, or for all
+    /// DTCs that have snapshot records
+    async fn freeze_frames(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let component_id = client.component_id();
+        let codes = match Self::requested_dtcs(request)? {
+            Some(codes) => codes,
+            None => {
+                let data = client
+                    .read_dtc_information(DtcReportType::DtcSnapshotIdentification, &[])
+                    .await?;
+                let mut codes: Vec<u32> = dtc::parse_snapshot_identification(&data)?
+                    .into_iter()
+                    .map(|(code, _)| code)
+                    .collect();
+                codes.sort_unstable();
+                codes.dedup();
+                codes
+            }
+        };
+        let record = request.record_number.unwrap_or(0xFF);
+
+        let mut freeze_frames = Vec::new();
+        for code in codes {
+            let mut parameters = code.to_be_bytes()[1..].to_vec();
+            parameters.push(record);
+            let data = client
+                .read_dtc_information(DtcReportType::DtcSnapshotRecordByDtcNumber, &parameters)
+                .await?;
+            let snapshots = dtc::parse_snapshot_records(&data, |did| {
+                self.did_definitions(component_id, did)
+                    .iter()
+                    .filter_map(|definition| definition.codec.byte_length())
+                    .max()
+            })?;
+
+            let mut entry = self.dtc_status_entry(component_id, &snapshots.dtc);
+            entry["records"] = snapshots
+                .records
+                .iter()
+                .map(|record| {
+                    let values: Vec<serde_json::Value> = record
+                        .values
+                        .iter()
+                        .map(|(did, data)| self.snapshot_value(component_id, *did, data))
+                        .collect();
+                    serde_json::json!({ "record": record.number, "values": values })
+                })
+                .collect();
+            freeze_frames.push(entry);
         }
+        let message = format!("Freeze frame data of {} DTCs retrieved", freeze_frames.len());
+        Ok((
+            Some(serde_json::json!({ "freeze_frames": freeze_frames })),
+            message,
+        ))
+    }
 
-        Ok(dtcs)
+    /// A DID value of a snapshot record, decoded into the data items of the
+    /// DID
+    fn snapshot_value(&self, component_id: &str, did: u16, data: &[u8]) -> serde_json::Value {
+        let items: Vec<serde_json::Value> = self
+            .did_definitions(component_id, did)
+            .into_iter()
+            .map(|definition| {
+                let data_item = Self::create_data_item(definition);
+                let mut item = serde_json::json!({ "id": data_item.id, "name": data_item.name });
+                match definition.codec.decode(data) {
+                    Ok(value) => item["value"] = value,
+                    Err(e) => item["error"] = e.to_string().into(),
+                }
+                if let Some(unit) = data_item.unit {
+                    item["unit"] = unit.into();
+                }
+                item
+            })
+            .collect();
+        serde_json::json!({
+            "did": format!("0x{:04X}", did),
+            "data": hex::encode(data),
+            "items": items,
+        })
     }
 
-    /// Format DTC bytes into standard string format
-    fn format_dtc(&self, bytes: &[u8]) -> String {
-        if bytes.len() < 3 {
-            return "UNKNOWN".to_string();
+    /// reportDTCExtDataRecordByDTCNumber for the requested DTCs
+    async fn extended_data(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let codes = Self::requested_dtcs(request)?.ok_or_else(|| {
+            Sovd2UdsError::InvalidRequest("Extended data needs 'dtcs'".to_string())
+        })?;
+        let record = request.record_number.unwrap_or(0xFF);
+
+        let mut extended_data = Vec::new();
+        for code in codes {
+            let mut parameters = code.to_be_bytes()[1..].to_vec();
+            parameters.push(record);
+            let data = client
+                .read_dtc_information(DtcReportType::DtcExtDataRecordByDtcNumber, &parameters)
+                .await?;
+            let records = dtc::parse_extended_data(&data, record)?;
+
+            let mut entry = self.dtc_status_entry(client.component_id(), &records.dtc);
+            entry["records"] = records
+                .records
+                .iter()
+                .map(|record| {
+                    serde_json::json!({
+                        "record": format!("0x{:02X}", record.number),
+                        "data": hex::encode(&record.data),
+                    })
+                })
+                .collect();
+            extended_data.push(entry);
         }
+        let message = format!("Extended data of {} DTCs retrieved", extended_data.len());
+        Ok((
+            Some(serde_json::json!({ "extended_data": extended_data })),
+            message,
+        ))
+    }
 
-        let first_byte = bytes[0];
-        let prefix = match (first_byte >> 6) & 0x03 {
-            0 => 'P', // Powertrain
-            1 => 'C', // Chassis
-            2 => 'B', // Body
-            3 => 'U', // Network
-            _ => 'X',
-        };
+    /// reportDTCFaultDetectionCounter
+    async fn fault_detection_counters(&self, client: &UdsClient) -> Result<DtcOutcome> {
+        let data = client
+            .read_dtc_information(DtcReportType::DtcFaultDetectionCounter, &[])
+            .await?;
+        let dtcs: Vec<serde_json::Value> = dtc::parse_fault_detection_counters(&data)?
+            .into_iter()
+            .map(|(code, counter)| {
+                let mut entry = self.dtc_entry(client.component_id(), code);
+                entry["fault_detection_counter"] = counter.into();
+                entry
+            })
+            .collect();
+        let message = format!("Found {} prefailed DTCs", dtcs.len());
+        Ok((Some(serde_json::json!({ "dtcs": dtcs })), message))
+    }
 
-        let second_digit = (first_byte >> 4) & 0x03;
-        let third_digit = first_byte & 0x0F;
-        let fourth_fifth = bytes[1];
+    /// reportWWHOBDDTCByMaskRecord
+    async fn wwh_obd_dtcs(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let parameters = [
+            request.functional_group.unwrap_or(FUNCTIONAL_GROUP_EMISSIONS),
+            request.status_mask.unwrap_or(0xFF),
+            request.severity_mask.unwrap_or(0xFF),
+        ];
+        let data = client
+            .read_dtc_information(DtcReportType::WwhObdDtcByMaskRecord, &parameters)
+            .await?;
+        let list = dtc::parse_wwh_obd_dtcs(&data)?;
+        let dtcs: Vec<serde_json::Value> = list
+            .dtcs
+            .iter()
+            .map(|record| {
+                let mut entry = self.dtc_status_entry(client.component_id(), &record.dtc);
+                entry["severity"] = format!("0x{:02X}", record.severity).into();
+                entry["severity_flags"] = dtc::severity_names(record.severity).into();
+                entry
+            })
+            .collect();
+        let message = format!("Found {} WWH-OBD DTCs", dtcs.len());
+        Ok((
+            Some(serde_json::json!({
+                "functional_group": format!("0x{:02X}", list.functional_group),
+                "status_availability_mask": dtc::status_json(list.availability_mask),
+                "severity_availability_mask":
+                    format!("0x{:02X}", list.severity_availability_mask),
+                "dtc_format": dtc_formats::name(list.format),
+                "dtcs": dtcs,
+            })),
+            message,
+        ))
+    }
 
-        format!(
-            "{}{}{}{}{}",
-            prefix,
-            second_digit,
-            third_digit,
-            (fourth_fifth >> 4) & 0x0F,
-            fourth_fifth & 0x0F
-        )
+    /// reportWWHOBDDTCWithPermanentStatus
+    async fn permanent_dtcs(
+        &self,
+        client: &UdsClient,
+        request: &DtcManagementRequest,
+    ) -> Result<DtcOutcome> {
+        let group = request.functional_group.unwrap_or(FUNCTIONAL_GROUP_EMISSIONS);
+        let data = client
+            .read_dtc_information(DtcReportType::WwhObdDtcWithPermanentStatus, &[group])
+            .await?;
+        let list = dtc::parse_permanent_dtcs(&data)?;
+        let dtcs = self.dtc_entries(client.component_id(), &list.dtcs);
+        let message = format!("Found {} permanent DTCs", dtcs.len());
+        Ok((
+            Some(serde_json::json!({
+                "functional_group": format!("0x{:02X}", list.functional_group),
+                "status_availability_mask": dtc::status_json(list.availability_mask),
+                "dtc_format": dtc_formats::name(list.format),
+                "dtcs": dtcs,
+            })),
+            message,
+        ))
     }
 
-    /// Get DTC description (placeholder)
-    fn get_dtc_description(&self, _dtc: &str) -> String {
-        "Diagnostic trouble code".to_string()
+    /// DTC numbers given in a request, if any
+    fn requested_dtcs(request: &DtcManagementRequest) -> Result<Option<Vec<u32>>> {
+        request
+            .dtcs
+            .as_ref()
+            .filter(|dtcs| !dtcs.is_empty())
+            .map(|dtcs| dtcs.iter().map(|text| dtc::parse_dtc(text)).collect())
+            .transpose()
+    }
+
+    /// SOVD representation of a DTC: its ISO 15031-6 / SAE J2012 code, raw
+    /// 24-bit value and UDS number, plus the display code of its definition
+    /// and the meaning of its failure type
+    fn dtc_entry(&self, component_id: &str, code: u32) -> serde_json::Value {
+        let mut entry = serde_json::json!({
+            "code": dtc::format_dtc(code),
+            "raw_value": code,
+            "uds_code": dtc::format_uds_dtc(code),
+            "description": self.get_dtc_description(component_id, code),
+        });
+        if let Some(display_code) = self
+            .get_dtc(component_id, code)
+            .and_then(|definition| definition.display_code.as_ref())
+        {
+            entry["display_code"] = display_code.as_str().into();
+        }
+        let failure_type = code as u8;
+        if failure_type != 0 {
+            entry["failure_type"] = serde_json::json!({
+                "value": format!("0x{:02X}", failure_type),
+            });
+            if let Some(meaning) = self.get_failure_type(component_id, failure_type) {
+                entry["failure_type"]["description"] = meaning.description.as_str().into();
+            }
+        }
+        entry
+    }
+
+    /// SOVD representation of a DTC and its status
+    fn dtc_status_entry(
+        &self,
+        component_id: &str,
+        record: &dtc::DtcStatusRecord,
+    ) -> serde_json::Value {
+        let mut entry = self.dtc_entry(component_id, record.code);
+        entry["status"] = dtc::status_json(record.status);
+        entry
+    }
+
+    fn dtc_entries(
+        &self,
+        component_id: &str,
+        records: &[dtc::DtcStatusRecord],
+    ) -> Vec<serde_json::Value> {
+        records
+            .iter()
+            .map(|record| self.dtc_status_entry(component_id, record))
+            .collect()
+    }
+
+    /// Get DTC description from the component's definitions
+    ///
+    /// A definition that covers all failure types is completed with the
+    /// meaning of the DTC's failure type.
+    fn get_dtc_description(&self, component_id: &str, code: u32) -> String {
+        let Some(definition) = self.dtc_definition(component_id, code) else {
+            return "Diagnostic trouble code".to_string();
+        };
+        let failure_type = code as u8;
+        match self.get_failure_type(component_id, failure_type) {
+            Some(meaning) if definition.code != code => {
+                format!("{}: {}", definition.description, meaning.description)
+            }
+            _ => definition.description.clone(),
+        }
     }
 
     /// Execute a routine (actuator control)
@@ -347,8 +1162,11 @@ impl SovdUdsTranslator {
         client: &UdsClient,
         request: &ActuatorControlRequest,
     ) -> Result<ActuatorControlResponse> {
-        // Map actuator ID to routine ID (this is application-specific)
-        let routine_id = self.get_routine_id(&request.actuator_id)?;
+        // Map actuator ID to routine ID
+        self.catalogs(client.component_id())?;
+        let routine = self
+            .get_routine(client.component_id(), &request.actuator_id)
+            .ok_or_else(|| Sovd2UdsError::ActuatorNotFound(request.actuator_id.clone()))?;
 
         let control_type = match request.action.as_str() {
             "start" => RoutineControlType::StartRoutine,
@@ -363,12 +1181,21 @@ impl SovdUdsTranslator {
 
         // Prepare routine parameters
         let params = if let Some(value) = &request.value {
-            self.serialize_value_to_bytes(value)?
+            match &routine.parameters {
+                Some(codec) => codec.encode(value)?,
+                None => self.serialize_value_to_bytes(value)?,
+            }
         } else {
             vec![]
         };
 
-        let _response = client.routine_control(control_type, routine_id, &params).await?;
+        let _response = Self::in_session(client, routine.session, async {
+            if let Some(level) = routine.security_level {
+                client.unlock_security(level).await?;
+            }
+            client.routine_control(control_type, routine.rid, &params).await
+        })
+        .await?;
 
         Ok(ActuatorControlResponse {
             actuator_id: request.actuator_id.clone(),
@@ -380,35 +1207,27 @@ impl SovdUdsTranslator {
         })
     }
 
-    /// Get routine ID for an actuator
-    fn get_routine_id(&self, actuator_id: &str) -> Result<u16> {
-        // This is application-specific mapping
-        // Add your actuator to routine ID mappings here
-        match actuator_id {
-            "fuel_pump" => Ok(0x0201),
-            "cooling_fan" => Ok(0x0202),
-            "throttle" => Ok(0x0203),
-            _ => Err(Sovd2UdsError::InvalidRequest(format!(
-                "Unknown actuator: {}",
-                actuator_id
-            ))),
-        }
-    }
-
     /// Serialize JSON value to bytes
     fn serialize_value_to_bytes(&self, value: &serde_json::Value) -> Result<Vec<u8>> {
         match value {
             serde_json::Value::Number(n) => {
-                if let Some(i) = n.as_u64() {
-                    if i <= 255 {
-                        Ok(vec![i as u8])
-                    } else if i <= 65535 {
-                        Ok((i as u16).to_be_bytes().to_vec())
-                    } else {
-                        Ok((i as u32).to_be_bytes().to_vec())
-                    }
+                let Some(i) = n.as_u64() else {
+                    return Err(Sovd2UdsError::InvalidRequest(format!(
+                        "Invalid number value: {}",
+                        n
+                    )));
+                };
+                if let Ok(byte) = u8::try_from(i) {
+                    Ok(vec![byte])
+                } else if let Ok(word) = u16::try_from(i) {
+                    Ok(word.to_be_bytes().to_vec())
+                } else if let Ok(dword) = u32::try_from(i) {
+                    Ok(dword.to_be_bytes().to_vec())
                 } else {
-                    Err(Sovd2UdsError::InvalidRequest("Invalid number value".to_string()))
+                    Err(Sovd2UdsError::InvalidRequest(format!(
+                        "Value {} does not fit 32 bits",
+                        i
+                    )))
                 }
             }
             serde_json::Value::Bool(b) => Ok(vec![if *b { 1 } else { 0 }]),
@@ -425,11 +1244,414 @@ impl Default for SovdUdsTranslator {
 }
 
 // Helper module for hex encoding
-mod hex {
+pub mod hex {
     pub fn encode(data: &[u8]) -> String {
         data.iter()
             .map(|b| format!("{:02X}", b))
             .collect::<Vec<_>>()
             .join("")
     }
+
+    /// Decode hex bytes such as "0C1F" or "0C 1F"
+    pub fn decode(text: &str) -> Option<Vec<u8>> {
+        let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bulk items of (DID, length), indexed by position
+    fn items(definitions: &[(u16, Option<usize>)]) -> Vec<BulkItem> {
+        definitions
+            .iter()
+            .enumerate()
+            .map(|(index, &(did, length))| BulkItem { index, did, length })
+            .collect()
+    }
+
+    #[test]
+    fn plan_batches_within_limits() {
+        let fixed = |length| {
+            (0..5)
+                .map(move |did| (did, Some(length)))
+                .collect::<Vec<_>>()
+        };
+        let cases = [
+            (fixed(2), 8, 4095, vec![vec![0, 1, 2, 3, 4]]),
+            // DID count limit; 0 is taken as 1
+            (fixed(2), 2, 4095, vec![vec![0, 1], vec![2, 3], vec![4]]),
+            (fixed(2), 0, 4095, (0..5).map(|did| vec![did]).collect()),
+            // SID + 2 * (DID + 10 bytes) = 25 bytes fit exactly
+            (fixed(10), 8, 25, vec![vec![0, 1], vec![2, 3], vec![4]]),
+            (fixed(10), 8, 24, (0..5).map(|did| vec![did]).collect()),
+            // Items of unknown length go last and end their batch
+            (
+                vec![(0xA, None), (0xB, Some(2)), (0xC, None), (0xD, Some(1))],
+                8,
+                4095,
+                vec![vec![0xB, 0xD, 0xA], vec![0xC]],
+            ),
+            // Items beyond the response limit are still read, alone
+            (
+                vec![(0xA, Some(1)), (0xB, Some(5000)), (0xC, Some(1))],
+                8,
+                4095,
+                vec![vec![0xA], vec![0xB], vec![0xC]],
+            ),
+        ];
+        for (definitions, max_dids, max_response_length, expected) in cases {
+            let batches = SovdUdsTranslator::plan_batches(
+                &items(&definitions),
+                max_dids,
+                max_response_length,
+            );
+            let dids: Vec<Vec<u16>> = batches
+                .iter()
+                .map(|batch| batch.iter().map(|item| item.did).collect())
+                .collect();
+            assert_eq!(
+                dids, expected,
+                "{:?}, {} DIDs, {} bytes",
+                definitions, max_dids, max_response_length
+            );
+        }
+    }
+
+    #[test]
+    fn split_records_by_did() {
+        let batch = items(&[(0xF190, Some(2)), (0x0100, Some(1)), (0xF18C, None)]);
+        let split = |data: &[u8]| -> Vec<Result<Vec<u8>>> {
+            SovdUdsTranslator::split_records(data, &batch)
+                .into_iter()
+                .enumerate()
+                .map(|(position, (index, value))| {
+                    assert_eq!(index, position);
+                    value.map_err(|e| Arc::try_unwrap(e).unwrap())
+                })
+                .collect()
+        };
+
+        let values = split(&[
+            0xF1, 0x90, 0x01, 0x02, 0x01, 0x00, 0x03, 0xF1, 0x8C, b'A', b'B',
+        ]);
+        let values: Vec<Vec<u8>> = values.into_iter().map(Result::unwrap).collect();
+        assert_eq!(values, [vec![0x01, 0x02], vec![0x03], b"AB".to_vec()]);
+
+        // DIDs left out by the ECU are out of range
+        let values = split(&[0xF1, 0x90, 0x01, 0x02, 0xF1, 0x8C]);
+        assert_eq!(values[0].as_ref().unwrap(), &[0x01, 0x02]);
+        assert!(matches!(
+            values[1],
+            Err(Sovd2UdsError::UdsProtocol {
+                service: 0x22,
+                nrc: 0x31,
+                ..
+            })
+        ));
+        assert_eq!(values[2].as_ref().unwrap(), &Vec::<u8>::new());
+
+        // Malformed responses fail every DID not read before the error
+        let malformed: [&[u8]; 4] = [
+            &[0xF1, 0x90, 0x01],
+            &[0xF1, 0x90, 0x01, 0x02, 0x01],
+            &[0x02, 0x00, 0x01],
+            // Records out of request order
+            &[0x01, 0x00, 0x03, 0xF1, 0x90, 0x01, 0x02],
+        ];
+        for data in malformed {
+            let values = split(data);
+            let failed = values
+                .iter()
+                .filter(|value| matches!(value, Err(Sovd2UdsError::UdsCommunication(_))))
+                .count();
+            assert!(failed > 0, "{:02X?}", data);
+            assert!(
+                values
+                    .iter()
+                    .all(|value| !matches!(value, Err(Sovd2UdsError::UdsProtocol { .. }))),
+                "{:02X?}",
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_are_serialized_in_the_smallest_width() {
+        let translator = SovdUdsTranslator::new();
+        let serialize = |value| translator.serialize_value_to_bytes(&serde_json::json!(value));
+        assert_eq!(serialize(0x12).unwrap(), [0x12]);
+        assert_eq!(serialize(0x1234).unwrap(), [0x12, 0x34]);
+        assert_eq!(serialize(0x12345678).unwrap(), [0x12, 0x34, 0x56, 0x78]);
+        for value in [0x1_0000_0000_i64, -1] {
+            assert!(matches!(serialize(value), Err(Sovd2UdsError::InvalidRequest(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_actuator_is_not_found() {
+        let config = Config::default();
+        let translator = SovdUdsTranslator::from_config(&config).unwrap();
+        let client = crate::simulator::test_client(config, "engine").await;
+        let request = ActuatorControlRequest {
+            actuator_id: "warp_drive".to_string(),
+            action: "start".to_string(),
+            value: None,
+            duration: None,
+        };
+        assert!(matches!(
+            translator.control_actuator(&client, &request).await,
+            Err(Sovd2UdsError::ActuatorNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn writes_elevate_the_session_and_read_the_value_back() {
+        let dictionary = r#"
+items:
+  - id: odometer
+    did: 0x0101
+    data_type: number
+    length: 2
+    scale: 0.1
+    unit: km
+    access: read_write
+  - id: vin
+    did: 0xF190
+    length: 17
+    access: read_write
+    security_level: 1
+"#;
+        let mut config = Config::default();
+        let path = dictionary::test_file("writable.yaml", dictionary);
+        config.dictionary.files.push(path);
+        config.simulator.ecu.dids.push(crate::config::SimulatedDid {
+            id: 0x0101,
+            ascii: None,
+            hex: Some("00 64".to_string()),
+            writable: true,
+            security_level: None,
+        });
+        let translator = SovdUdsTranslator::from_config(&config).unwrap();
+        let client = crate::simulator::test_client(config, "engine").await;
+
+        let odometer = translator.read_data_item(&client, "odometer").await.unwrap();
+        assert_eq!(odometer.data, serde_json::json!(10.0));
+        assert_eq!(odometer.unit.as_deref(), Some("km"));
+
+        // The ECU only accepts writes in the extended session
+        let written = translator
+            .write_data_item(&client, "odometer", &serde_json::json!(12.5))
+            .await
+            .unwrap();
+        assert_eq!(written.data, serde_json::json!(12.5));
+        assert_eq!(client.session(), 0x01);
+        let odometer = translator.read_data_item(&client, "odometer").await.unwrap();
+        assert_eq!(odometer.data, serde_json::json!(12.5));
+
+        // Unlocks the item's security level first
+        let vin = serde_json::json!("WVWZZZ1JZXW000002");
+        let written = translator.write_data_item(&client, "vin", &vin).await.unwrap();
+        assert_eq!(written.data, vin);
+
+        let serial = translator
+            .write_data_item(&client, "ecu_serial_number", &serde_json::json!("SIM0000002"))
+            .await;
+        assert!(matches!(serial, Err(Sovd2UdsError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn components_have_their_own_catalogs() {
+        let powertrain = r#"
+items:
+  - { id: engine_rpm, did: 0x0C00, data_type: number, length: 2 }
+  - { id: coolant_temperature, did: 0x0105, data_type: number, length: 1, unit: degC }
+"#;
+        let engine = r#"
+items:
+  - { id: oil_temperature, did: 0x0110, data_type: number, length: 1 }
+  - { id: coolant_temperature, did: 0x0105, data_type: number, length: 1, unit: K }
+"#;
+        let mut config = Config::default();
+        let base = vec![dictionary::test_file("powertrain.yaml", powertrain)];
+        config.dictionary.bases.insert("powertrain".to_string(), base);
+        for component_id in ["engine", "transmission"] {
+            let component = config.components.get_mut(component_id).unwrap();
+            component.inherit = Some("powertrain".to_string());
+        }
+        let engine_path = dictionary::test_file("engine.yaml", engine);
+        config.components.get_mut("engine").unwrap().dictionaries.push(engine_path);
+        let translator = SovdUdsTranslator::from_config(&config).unwrap();
+
+        let has = |component_id, data_item_id: &str| {
+            let items = translator.get_component_data_items(component_id, None).unwrap();
+            items.iter().any(|item| item.id == data_item_id)
+        };
+        for data_item_id in ["vin", "engine_rpm", "oil_temperature"] {
+            assert!(has("engine", data_item_id), "{}", data_item_id);
+        }
+        assert!(has("transmission", "engine_rpm"));
+        assert!(!has("transmission", "oil_temperature"));
+        assert!(has("abs", "vin"));
+        assert!(!has("abs", "engine_rpm"));
+
+        // The component's own definition takes precedence over the base
+        let unit = |component_id| {
+            let definition = translator.get_definition(component_id, "coolant_temperature");
+            definition.and_then(|definition| definition.codec.unit.clone())
+        };
+        assert_eq!(unit("engine").as_deref(), Some("K"));
+        assert_eq!(unit("transmission").as_deref(), Some("degC"));
+
+        assert!(matches!(
+            translator.get_component_data_items("gearbox", None),
+            Err(Sovd2UdsError::ComponentNotFound(_))
+        ));
+        assert!(matches!(
+            translator.write_security_level("abs", "oil_temperature"),
+            Err(Sovd2UdsError::DataItemNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn inheriting_needs_a_known_base_and_component() {
+        let mut translator = SovdUdsTranslator::new();
+        translator.add_component(Component {
+            id: "engine".to_string(),
+            name: "Engine".to_string(),
+            description: None,
+        });
+        translator.add_base("powertrain", DataDictionary::default());
+
+        assert!(translator.inherit("engine", "powertrain").is_ok());
+        assert!(matches!(
+            translator.inherit("engine", "chassis"),
+            Err(Sovd2UdsError::Config(_))
+        ));
+        assert!(matches!(
+            translator.inherit("gearbox", "powertrain"),
+            Err(Sovd2UdsError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn dtc_descriptions_combine_the_code_and_failure_type() {
+        let mut translator = SovdUdsTranslator::new();
+        translator.add_dictionary(
+            None,
+            DataDictionary {
+                dtcs: vec![DtcDefinition {
+                    code: 0x010000,
+                    display_code: None,
+                    description: "Mass or volume air flow sensor A circuit".to_string(),
+                }],
+                ..DataDictionary::default()
+            },
+        );
+        translator.add_dictionary(
+            Some("engine"),
+            DataDictionary {
+                dtcs: vec![DtcDefinition {
+                    code: 0xC12387,
+                    display_code: Some("U0123-87".to_string()),
+                    description: "Lost communication with the yaw rate sensor".to_string(),
+                }],
+                ..DataDictionary::default()
+            },
+        );
+        translator.add_component(Component {
+            id: "abs".to_string(),
+            name: "ABS".to_string(),
+            description: None,
+        });
+
+        let entry = translator.dtc_entry("engine", 0x010011);
+        assert_eq!(
+            entry["description"],
+            "Mass or volume air flow sensor A circuit: Circuit short to ground"
+        );
+        assert_eq!(entry["failure_type"]["description"], "Circuit short to ground");
+        assert_eq!(
+            translator.dtc_entry("engine", 0x010000)["description"],
+            "Mass or volume air flow sensor A circuit"
+        );
+
+        // Custom codes only exist on their ECU
+        let entry = translator.dtc_entry("engine", 0xC12387);
+        assert_eq!(
+            entry["description"],
+            "Lost communication with the yaw rate sensor"
+        );
+        assert_eq!(entry["display_code"], "U0123-87");
+        let entry = translator.dtc_entry("abs", 0xC12387);
+        assert_eq!(entry["description"], "Diagnostic trouble code");
+        assert!(entry.get("display_code").is_none());
+    }
+
+    #[tokio::test]
+    async fn freeze_frames_and_extended_data_are_decoded() {
+        let dictionary = r#"
+items:
+  - id: coolant_temperature
+    did: 0x0105
+    data_type: number
+    length: 1
+    offset: -40
+    unit: degC
+"#;
+        let mut config = Config::default();
+        let path = dictionary::test_file("freeze-frame.yaml", dictionary);
+        config.dictionary.files.push(path);
+        let ecu = &mut config.simulator.ecu;
+        ecu.dids.push(crate::config::SimulatedDid {
+            id: 0x0105,
+            ascii: None,
+            hex: Some("5A".to_string()),
+            writable: false,
+            security_level: None,
+        });
+        ecu.dtcs[0].snapshots[0].dids = vec![0x0105, 0xF18C];
+        let translator = SovdUdsTranslator::from_config(&config).unwrap();
+        let client = crate::simulator::test_client(config, "engine").await;
+
+        let request = DtcManagementRequest {
+            action: "freeze_frame".to_string(),
+            dtcs: Some(vec!["P0100-11".to_string()]),
+            ..Default::default()
+        };
+        let response = translator.manage_dtcs(&client, &request).await.unwrap();
+        let frame = &response.results.unwrap()["freeze_frames"][0];
+        assert_eq!(frame["code"], "P0100-11");
+        assert_eq!(frame["status"]["confirmedDTC"], true);
+        let values = &frame["records"][0]["values"];
+        assert_eq!(values[0]["did"], "0x0105");
+        assert_eq!(values[0]["data"], "5A");
+        let coolant = &values[0]["items"][0];
+        assert_eq!(coolant["id"], "coolant_temperature");
+        assert_eq!(coolant["value"], 50.0);
+        assert_eq!(coolant["unit"], "degC");
+        // The serial number has no fixed length and ends the record
+        assert_eq!(values[1]["items"][0]["value"], "SIM0000001");
+
+        let request = DtcManagementRequest {
+            action: "extended_data".to_string(),
+            dtcs: Some(vec!["P0100-11".to_string()]),
+            record_number: Some(0x01),
+            ..Default::default()
+        };
+        let response = translator.manage_dtcs(&client, &request).await.unwrap();
+        let records = &response.results.unwrap()["extended_data"][0]["records"];
+        assert_eq!(
+            records,
+            &serde_json::json!([{ "record": "0x01", "data": "05" }])
+        );
+    }
 }
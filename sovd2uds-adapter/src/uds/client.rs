@@ -1,55 +1,111 @@
-use crate::config::Config;
+use super::{deadline, RecordingTransport, SessionState, UdsTransport};
+use crate::config::{Config, TransportKind, UdsTiming};
+use crate::doip::{DoipClient, DoipTransport};
 use crate::error::{Result, Sovd2UdsError, UdsNegativeResponseCode};
-use crate::ffi::UdsClientHandle;
+#[cfg(not(feature = "mock-uds"))]
+use crate::ffi::{FfiTransport, UdsClientHandle};
+#[cfg(target_os = "linux")]
+use crate::isotp::IsoTpTransport;
 use crate::models::uds::*;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use crate::security::SeedKeyRegistry;
+use crate::simulator::{SimulatorTransport, VirtualEcu};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+/// NRC 0x78: request correctly received, response pending
+const RESPONSE_PENDING: u8 = UdsNegativeResponseCode::RequestCorrectlyReceivedResponsePending as u8;
+
 /// High-level UDS client abstraction
 pub struct UdsClient {
-    handle: Arc<RwLock<UdsClientHandle>>,
+    transport: Arc<dyn UdsTransport>,
     config: Arc<Config>,
     component_id: String,
     ecu_address: u32,
+    /// Serialises request/response exchanges on the transport; waiters are served FIFO
+    exchange_lock: Arc<Mutex<()>>,
+    /// Requests waiting for or holding `exchange_lock`
+    queued: AtomicUsize,
+    /// Current P2/P2*, updated from session control responses
+    timing: std::sync::RwLock<UdsTiming>,
+    /// When the last request was sent
+    last_used: Arc<std::sync::Mutex<Instant>>,
+    /// Seed/key algorithms for this component
+    seed_key: SeedKeyRegistry,
+    /// Session and security access state
+    state: std::sync::Mutex<SessionState>,
+    /// Held while an operation runs in a temporarily entered session
+    session_lock: Mutex<()>,
+    /// TesterPresent task running while a non-default session is held
+    keep_alive: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for UdsClient {
+    fn drop(&mut self) {
+        self.stop_keep_alive();
+    }
+}
+
+/// Place in an ECU's request queue, released when dropped
+struct QueueTicket<'a>(&'a AtomicUsize);
+
+impl Drop for QueueTicket<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl UdsClient {
-    /// Create a new UDS client for a specific component
-    pub fn new(config: Arc<Config>, component_id: String) -> Result<Self> {
+    /// Create a new UDS client for a specific component on top of a transport
+    pub fn new(
+        config: Arc<Config>,
+        component_id: String,
+        transport: Arc<dyn UdsTransport>,
+    ) -> Result<Self> {
         let ecu_address = config
             .get_component_address(&component_id)
             .ok_or_else(|| Sovd2UdsError::ComponentNotFound(component_id.clone()))?;
 
-        let handle = UdsClientHandle::new(
-            &config.uds.interface,
-            ecu_address,
-            config.uds.timeout,
-        )?;
+        let timing = config.get_component_timing(&component_id);
+        let seed_key = SeedKeyRegistry::for_component(&config.security, &component_id)?;
 
         Ok(Self {
-            handle: Arc::new(RwLock::new(handle)),
+            transport,
             config,
             component_id,
             ecu_address,
+            exchange_lock: Arc::new(Mutex::new(())),
+            queued: AtomicUsize::new(0),
+            timing: std::sync::RwLock::new(timing),
+            last_used: Arc::new(std::sync::Mutex::new(Instant::now())),
+            seed_key,
+            state: std::sync::Mutex::new(SessionState::new()),
+            session_lock: Mutex::new(()),
+            keep_alive: std::sync::Mutex::new(None),
         })
     }
 
     /// Connect to the ECU
     pub async fn connect(&self) -> Result<()> {
-        let handle = self.handle.read().await;
-        handle.connect()?;
+        self.transport.connect().await?;
         info!(
-            "Connected to ECU for component '{}' at address 0x{:X}",
-            self.component_id, self.ecu_address
+            "Connected to ECU for component '{}' at address 0x{:X} via {}",
+            self.component_id,
+            self.ecu_address,
+            self.transport.name()
         );
         Ok(())
     }
 
     /// Disconnect from the ECU
     pub async fn disconnect(&self) -> Result<()> {
-        let handle = self.handle.read().await;
-        handle.disconnect()?;
+        self.stop_keep_alive();
+        self.transport.disconnect().await?;
         info!(
             "Disconnected from ECU for component '{}'",
             self.component_id
@@ -64,13 +120,34 @@ impl UdsClient {
             did, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        let data = handle.read_data_by_identifier(did)?;
+        let request = UdsRequest::new(
+            UdsServiceId::ReadDataByIdentifier,
+            did.to_be_bytes().to_vec(),
+        );
+        let data = self.exchange(request, 2).await?;
 
         debug!("Successfully read {} bytes from DID 0x{:04X}", data.len(), did);
         Ok(data)
     }
 
+    /// Read several DIDs with one request
+    ///
+    /// Returns the records the ECU sent, each a DID followed by its data;
+    /// ECUs leave out DIDs they do not support.
+    pub async fn read_data_by_identifiers(&self, dids: &[u16]) -> Result<Vec<u8>> {
+        debug!(
+            "Reading DIDs {:04X?} from component '{}'",
+            dids, self.component_id
+        );
+
+        let payload = dids.iter().flat_map(|did| did.to_be_bytes()).collect();
+        let request = UdsRequest::new(UdsServiceId::ReadDataByIdentifier, payload);
+        let data = self.exchange(request, 0).await?;
+
+        debug!("Successfully read {} bytes for {} DIDs", data.len(), dids.len());
+        Ok(data)
+    }
+
     /// Write data by identifier
     pub async fn write_data_by_identifier(&self, did: u16, data: &[u8]) -> Result<()> {
         debug!(
@@ -85,8 +162,10 @@ impl UdsClient {
             self.perform_security_access().await?;
         }
 
-        let handle = self.handle.read().await;
-        handle.write_data_by_identifier(did, data)?;
+        let mut payload = did.to_be_bytes().to_vec();
+        payload.extend_from_slice(data);
+        self.exchange(UdsRequest::new(UdsServiceId::WriteDataByIdentifier, payload), 2)
+            .await?;
 
         info!("Successfully wrote to DID 0x{:04X}", did);
         Ok(())
@@ -102,8 +181,11 @@ impl UdsClient {
             session_type, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        let response = handle.diagnostic_session_control(session_type as u8)?;
+        let request = UdsRequest::new(
+            UdsServiceId::DiagnosticSessionControl,
+            vec![session_type as u8],
+        );
+        let response = self.exchange(request, 0).await?;
 
         info!("Successfully changed to diagnostic session {:?}", session_type);
         Ok(response)
@@ -116,59 +198,163 @@ impl UdsClient {
             reset_type, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        let response = handle.ecu_reset(reset_type as u8)?;
+        let request = UdsRequest::new(UdsServiceId::EcuReset, vec![reset_type as u8]);
+        let response = self.exchange(request, 0).await?;
 
         warn!("ECU reset {:?} executed", reset_type);
         Ok(response)
     }
 
-    /// Perform security access (request seed and send key)
+    /// Perform security access at the configured level before a write
+    ///
+    /// A level the caller already unlocked (a data item's own, ...) is kept:
+    /// ECUs may reject a seed request for another level while one is unlocked.
     async fn perform_security_access(&self) -> Result<()> {
-        debug!("Performing security access");
-
-        let security_level = self.config.security.security_level;
-        
-        // Request seed (odd sub-function)
-        let request_seed_type = security_level * 2 - 1;
-        
-        let handle = self.handle.read().await;
-        let seed = handle.security_access(request_seed_type, &[])?;
-
-        if seed.is_empty() {
-            info!("Security access already granted");
+        if self.state.lock().unwrap().security_level.is_some() {
             return Ok(());
         }
+        self.unlock_security(self.config.security.security_level).await
+    }
 
-        // Calculate key from seed (this is application-specific)
-        // For now, we'll use a placeholder implementation
-        let key = self.calculate_security_key(&seed);
+    /// Unlock a security level (request seed and send key)
+    ///
+    /// Rejections (NRC 0x33, 0x35, 0x36, 0x37) become `SecurityAccessDenied`
+    /// with the attempts left and the time until the ECU accepts a new seed request.
+    pub async fn unlock_security(&self, security_level: u8) -> Result<()> {
+        debug!(
+            "Performing security access at level {} on component '{}'",
+            security_level, self.component_id
+        );
 
-        // Send key (even sub-function)
-        let send_key_type = security_level * 2;
-        handle.security_access(send_key_type, &key)?;
+        let (request_seed_type, send_key_type) = security_levels::sub_functions(security_level)
+            .ok_or_else(|| {
+                Sovd2UdsError::InvalidRequest(format!(
+                    "Invalid security level: {}",
+                    security_level
+                ))
+            })?;
 
-        info!("Security access granted");
+        let result = async {
+            // Request seed (odd sub-function)
+            let seed = self.security_access(request_seed_type, &[]).await?;
+
+            if seed.iter().all(|&b| b == 0) {
+                info!("Security access already granted");
+                return Ok(());
+            }
+
+            let key = self.calculate_security_key(security_level, seed).await?;
+
+            // Send key (even sub-function)
+            self.security_access(send_key_type, &key).await?;
+
+            info!("Security access granted");
+            Ok(())
+        }
+        .await;
+
+        result.map_err(|e| self.security_access_denied(security_level, e))
+    }
+
+    /// Lock security access again by re-entering the active session
+    ///
+    /// UDS has no service to relock; any session transition, including to
+    /// the active session, locks every security level.
+    pub async fn lock_security(&self) -> Result<()> {
+        let _switching = deadline::within("a session switch", self.session_lock.lock()).await?;
+        let session = self.session();
+        info!(
+            "Locking security access on component '{}' (session 0x{:02X})",
+            self.component_id, session
+        );
+        let request = UdsRequest::new(UdsServiceId::DiagnosticSessionControl, vec![session]);
+        self.request(request).await?;
         Ok(())
     }
 
-    /// Calculate security key from seed (placeholder implementation)
-    /// In a real implementation, this would use the actual security algorithm
-    fn calculate_security_key(&self, seed: &[u8]) -> Vec<u8> {
-        // Placeholder: XOR with a constant
-        // Replace with actual algorithm
-        seed.iter().map(|b| b ^ 0xAA).collect()
+    /// Keys that may still be tried at this component before it locks out
+    pub fn remaining_security_attempts(&self) -> u8 {
+        let max_attempts = self.config.security.max_attempts;
+        self.state.lock().unwrap().remaining_attempts(max_attempts)
+    }
+
+    /// Attach the attempt counters to a rejected security access
+    fn security_access_denied(&self, level: u8, error: Sovd2UdsError) -> Sovd2UdsError {
+        let Sovd2UdsError::UdsProtocol {
+            nrc, description, ..
+        } = &error
+        else {
+            return error;
+        };
+        let denied = matches!(
+            UdsNegativeResponseCode::from_u8(*nrc),
+            Some(
+                UdsNegativeResponseCode::SecurityAccessDenied
+                    | UdsNegativeResponseCode::InvalidKey
+                    | UdsNegativeResponseCode::ExceedNumberOfAttempts
+                    | UdsNegativeResponseCode::RequiredTimeDelayNotExpired
+            )
+        );
+        if !denied {
+            return error;
+        }
+
+        let state = self.state.lock().unwrap();
+        Sovd2UdsError::SecurityAccessDenied {
+            level,
+            nrc: *nrc,
+            description: description.clone(),
+            remaining_attempts: state.remaining_attempts(self.config.security.max_attempts),
+            retry_after: state
+                .retry_after()
+                .map(|left| left.num_milliseconds().max(0) as u64),
+        }
     }
 
-    /// Read DTC information
-    pub async fn read_dtc_information(&self, sub_function: u8) -> Result<Vec<u8>> {
+    /// Send a single SecurityAccess request and return the seed (if any)
+    async fn security_access(&self, access_type: u8, key: &[u8]) -> Result<Vec<u8>> {
+        let mut payload = vec![access_type];
+        payload.extend_from_slice(key);
+        let request = UdsRequest::new(UdsServiceId::SecurityAccess, payload);
+        self.exchange(request, 1).await
+    }
+
+    /// Calculate the key for a seed with the algorithm registered for this
+    /// component and level
+    ///
+    /// Runs on the blocking pool, as external libraries and commands may take
+    /// a while; the request deadline is not extended for them.
+    async fn calculate_security_key(&self, level: u8, seed: Vec<u8>) -> Result<Vec<u8>> {
+        let algorithm = self.seed_key.algorithm(level);
+        debug!(
+            "Computing key for component '{}' level {} with {}",
+            self.component_id,
+            level,
+            algorithm.name()
+        );
+        let key = tokio::task::spawn_blocking(move || algorithm.compute_key(level, &seed));
+        deadline::within("the seed/key algorithm", key)
+            .await?
+            .map_err(|e| Sovd2UdsError::Internal(format!("Seed/key task failed: {}", e)))?
+    }
+
+    /// Read DTC information; `parameters` follow the report type (status
+    /// mask, DTC and record number, ...). Returns the data after the echoed
+    /// report type.
+    pub async fn read_dtc_information(
+        &self,
+        report: DtcReportType,
+        parameters: &[u8],
+    ) -> Result<Vec<u8>> {
         debug!(
             "Reading DTC information (sub-function 0x{:02X}) from component '{}'",
-            sub_function, self.component_id
+            report as u8, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        let data = handle.read_dtc_information(sub_function)?;
+        let mut payload = vec![report as u8];
+        payload.extend_from_slice(parameters);
+        let request = UdsRequest::new(UdsServiceId::ReadDTCInformation, payload);
+        let data = self.exchange(request, 1).await?;
 
         debug!("Successfully read DTC information");
         Ok(data)
@@ -181,8 +367,11 @@ impl UdsClient {
             group, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        handle.clear_diagnostic_information(group)?;
+        let request = UdsRequest::new(
+            UdsServiceId::ClearDiagnosticInformation,
+            group.to_be_bytes()[1..].to_vec(),
+        );
+        self.exchange(request, 0).await?;
 
         info!("Successfully cleared diagnostic information");
         Ok(())
@@ -200,13 +389,99 @@ impl UdsClient {
             control_type, routine_id, self.component_id
         );
 
-        let handle = self.handle.read().await;
-        let response = handle.routine_control(control_type as u8, routine_id, params)?;
+        let mut payload = vec![control_type as u8];
+        payload.extend_from_slice(&routine_id.to_be_bytes());
+        payload.extend_from_slice(params);
+        let request = UdsRequest::new(UdsServiceId::RoutineControl, payload);
+        let response = self.exchange(request, 3).await?;
 
         info!("Routine control {:?} executed successfully", control_type);
         Ok(response)
     }
 
+    /// Read memory by address
+    pub async fn read_memory_by_address(&self, region: MemoryRegion) -> Result<Vec<u8>> {
+        debug!(
+            "Reading {} bytes at 0x{:X} from component '{}'",
+            region.size, region.address, self.component_id
+        );
+
+        let request = UdsRequest::new(UdsServiceId::ReadMemoryByAddress, region.to_bytes());
+        self.exchange(request, 0).await
+    }
+
+    /// Write memory by address
+    pub async fn write_memory_by_address(&self, address: u64, data: &[u8]) -> Result<()> {
+        info!(
+            "Writing {} bytes at 0x{:X} on component '{}'",
+            data.len(),
+            address,
+            self.component_id
+        );
+
+        if self.config.security.require_security_access {
+            self.perform_security_access().await?;
+        }
+
+        let mut payload = MemoryRegion::new(address, data.len() as u64).to_bytes();
+        let echo_length = payload.len();
+        payload.extend_from_slice(data);
+        let request = UdsRequest::new(UdsServiceId::WriteMemoryByAddress, payload);
+        self.exchange(request, echo_length).await?;
+
+        info!("Successfully wrote memory at 0x{:X}", address);
+        Ok(())
+    }
+
+    /// Define or clear a dynamically defined data identifier
+    pub async fn dynamically_define_data_identifier(
+        &self,
+        definition: &DynamicDidDefinition,
+    ) -> Result<()> {
+        debug!(
+            "Dynamically defining data identifier {:?} on component '{}'",
+            definition, self.component_id
+        );
+
+        let payload = definition.to_bytes();
+        // Positive response echoes the sub-function and the DID, if any
+        let echo_length = payload.len().min(3);
+        let request = UdsRequest::new(UdsServiceId::DynamicallyDefineDataIdentifier, payload);
+        self.exchange(request, echo_length).await?;
+        Ok(())
+    }
+
+    /// Enable or disable DTC recording
+    pub async fn control_dtc_setting(&self, setting: DtcSettingType) -> Result<()> {
+        info!(
+            "Setting DTC recording {:?} for component '{}'",
+            setting, self.component_id
+        );
+
+        let request = UdsRequest::new(UdsServiceId::ControlDTCSetting, vec![setting as u8]);
+        self.exchange(request, 1).await?;
+        Ok(())
+    }
+
+    /// Enable or disable transmission/reception of a communication type
+    pub async fn communication_control(
+        &self,
+        control_type: CommunicationControlType,
+        communication_type: u8,
+    ) -> Result<()> {
+        info!(
+            "Communication control {:?} (type 0x{:02X}) for component '{}'",
+            control_type, communication_type, self.component_id
+        );
+
+        let request = UdsRequest::new(
+            UdsServiceId::CommunicationControl,
+            vec![control_type as u8, communication_type],
+        );
+        self.exchange(request, 1).await?;
+        Ok(())
+    }
+
     /// Get VIN (Vehicle Identification Number)
     pub async fn get_vin(&self) -> Result<String> {
         let data = self
@@ -237,6 +512,361 @@ impl UdsClient {
             .map_err(|e| Sovd2UdsError::Translation(format!("Invalid hardware version data: {}", e)))
     }
 
+    /// Send a raw request and return the ECU's positive response
+    ///
+    /// Every service goes through here: the request is encoded with
+    /// `UdsRequest::to_bytes`, the answer parsed with `UdsResponse::from_bytes`,
+    /// negative responses become `UdsProtocol` errors and the response SID is
+    /// checked against the request. Requests with the suppressPosRspMsgIndicationBit
+    /// set do not wait for a response.
+    ///
+    /// Idempotent reads are retried up to `uds.max_retries` times with
+    /// exponential backoff when the ECU is busy (NRC 0x21), does not answer
+    /// in time or the link fails; the transport is reopened after link
+    /// failures. Writes, resets and every other service are sent exactly once.
+    ///
+    /// Inside `deadline::with_deadline` no request is sent once the deadline
+    /// has passed, response waits are shortened to it and retries stop at it.
+    pub async fn request(&self, request: UdsRequest) -> Result<UdsResponse> {
+        let response = if request.is_idempotent() {
+            self.request_with_retries(&request).await
+        } else {
+            self.request_once(&request).await
+        };
+        let response = response.inspect_err(|e| self.track_failure(e))?;
+
+        self.track_session(&request, &response);
+        Ok(response)
+    }
+
+    /// Follow session and security changes made by session control, ECU
+    /// reset and security access
+    fn track_session(&self, request: &UdsRequest, response: &UdsResponse) {
+        let default_session = {
+            let mut state = self.state.lock().unwrap();
+            state.apply(request, response);
+            state.is_default_session()
+        };
+
+        match request.service_id {
+            0x10 => self.update_timing(&response.data),
+            0x11 => {}
+            _ => return,
+        }
+
+        if default_session {
+            self.stop_keep_alive();
+        } else {
+            self.start_keep_alive();
+        }
+    }
+
+    /// Count invalid keys and lockouts reported for security access
+    fn track_failure(&self, error: &Sovd2UdsError) {
+        if let Sovd2UdsError::UdsProtocol { service, nrc, .. } = error {
+            if *service == UdsServiceId::SecurityAccess as u8 {
+                let security = &self.config.security;
+                self.state.lock().unwrap().record_security_failure(
+                    *nrc,
+                    security.max_attempts,
+                    chrono::Duration::milliseconds(security.lockout_delay as i64),
+                );
+            }
+        }
+    }
+
+    /// Take P2/P2* from a session control response
+    fn update_timing(&self, response: &[u8]) {
+        // sessionParameterRecord: P2server_max (1 ms) and P2*server_max (10 ms)
+        if let [_, p2_high, p2_low, p2_star_high, p2_star_low, ..] = *response {
+            let margin = self.config.uds.p2_margin;
+            let timing = UdsTiming {
+                p2: u16::from_be_bytes([p2_high, p2_low]) as u32 + margin,
+                p2_star: u16::from_be_bytes([p2_star_high, p2_star_low]) as u32 * 10 + margin,
+            };
+            debug!(
+                "Component '{}' timing from session response: P2 {} ms, P2* {} ms",
+                self.component_id, timing.p2, timing.p2_star
+            );
+            *self.timing.write().unwrap() = timing;
+        }
+    }
+
+    /// Active diagnostic session as last confirmed by the ECU
+    pub fn session(&self) -> u8 {
+        self.state.lock().unwrap().session
+    }
+
+    /// Session and security access state as last confirmed by the ECU
+    pub fn session_state(&self) -> SessionState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Whether the TesterPresent keep-alive is running
+    pub fn keep_alive_active(&self) -> bool {
+        self.keep_alive
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
+    /// Run `operation` in `session`, entering it first and returning to the
+    /// previous session afterwards when the ECU is in a different one
+    ///
+    /// Concurrent callers are serialised so one does not switch the session
+    /// away under another. The previous session is restored even when the
+    /// request deadline passed during `operation`.
+    pub async fn with_session<T>(
+        &self,
+        session: DiagnosticSessionType,
+        operation: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let _switching = deadline::within("a session switch", self.session_lock.lock()).await?;
+        let previous = self.session();
+        if previous == session as u8 {
+            return operation.await;
+        }
+
+        info!(
+            "Component '{}' needs the {} session for this request, switching from 0x{:02X}",
+            self.component_id,
+            session.name(),
+            previous
+        );
+        self.diagnostic_session_control(session).await?;
+        let result = operation.await;
+
+        let restore = UdsRequest::new(UdsServiceId::DiagnosticSessionControl, vec![previous]);
+        if let Err(e) = deadline::without_deadline(self.request(restore)).await {
+            warn!(
+                "Failed to restore session 0x{:02X} on component '{}': {}",
+                previous, self.component_id, e
+            );
+        }
+        result
+    }
+
+    /// Send TesterPresent (3E 80) whenever the link has been idle for
+    /// `uds.tester_present_interval`, so the ECU keeps a non-default session
+    ///
+    /// The keep-alive ends when the transport refuses to send TesterPresent.
+    fn start_keep_alive(&self) {
+        let interval = Duration::from_millis(self.config.uds.tester_present_interval as u64);
+        let mut keep_alive = self.keep_alive.lock().unwrap();
+        if interval.is_zero() || keep_alive.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
+
+        let transport = Arc::clone(&self.transport);
+        let exchange_lock = Arc::clone(&self.exchange_lock);
+        let last_used = Arc::clone(&self.last_used);
+        let component_id = self.component_id.clone();
+        let tester_present = UdsRequest::new(UdsServiceId::TesterPresent, vec![0x80]).to_bytes();
+
+        debug!("Starting TesterPresent keep-alive for component '{}'", component_id);
+        *keep_alive = Some(tokio::spawn(async move {
+            loop {
+                let idle = last_used.lock().unwrap().elapsed();
+                if idle < interval {
+                    sleep(interval - idle).await;
+                    continue;
+                }
+
+                let _exchange = exchange_lock.lock().await;
+                match transport.send(&tester_present).await {
+                    Ok(()) => {}
+                    Err(e @ (Sovd2UdsError::InvalidRequest(_) | Sovd2UdsError::Ffi(_))) => {
+                        warn!(
+                            "Transport {} cannot keep component '{}' alive: {}",
+                            transport.name(),
+                            component_id,
+                            e
+                        );
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("TesterPresent to component '{}' failed: {}", component_id, e)
+                    }
+                }
+                *last_used.lock().unwrap() = Instant::now();
+            }
+        }));
+    }
+
+    fn stop_keep_alive(&self) {
+        if let Some(keep_alive) = self.keep_alive.lock().unwrap().take() {
+            debug!("Stopping TesterPresent keep-alive for component '{}'", self.component_id);
+            keep_alive.abort();
+        }
+    }
+
+    /// `request` for idempotent reads, repeated after transient failures
+    async fn request_with_retries(&self, request: &UdsRequest) -> Result<UdsResponse> {
+        let max_retries = self.config.uds.max_retries;
+        let max_delay = Duration::from_millis(self.config.uds.retry_max_delay as u64);
+        let mut delay = Duration::from_millis(self.config.uds.retry_delay as u64);
+        let mut attempt = 0;
+        loop {
+            let error = match self.request_once(request).await {
+                Err(e) if e.is_transient() && attempt < max_retries => e,
+                result => return result,
+            };
+            if deadline::remaining().is_some_and(|remaining| remaining <= delay) {
+                return Err(error);
+            }
+            attempt += 1;
+            warn!(
+                "Service 0x{:02X} on component '{}' failed ({}), retry {}/{} in {:?}",
+                request.service_id, self.component_id, error, attempt, max_retries, delay
+            );
+            tokio::time::sleep(delay).await;
+            if error.is_link_error() {
+                self.reconnect().await;
+            }
+            delay = (delay * 2).min(max_delay);
+        }
+    }
+
+    /// Wait for this ECU's link; one request is outstanding at a time
+    ///
+    /// Fails with `Overloaded` when `performance.ecu_queue_length` requests,
+    /// the one using the link included, are already queued.
+    async fn enter_queue(&self) -> Result<(QueueTicket<'_>, MutexGuard<'_, ()>)> {
+        let ahead = self.queued.fetch_add(1, Ordering::SeqCst);
+        let ticket = QueueTicket(&self.queued);
+        if ahead >= self.config.performance.ecu_queue_length.max(1) {
+            return Err(Sovd2UdsError::Overloaded(format!(
+                "Request queue for component '{}' is full",
+                self.component_id
+            )));
+        }
+        let exchange = deadline::within("the ECU link", self.exchange_lock.lock()).await?;
+        Ok((ticket, exchange))
+    }
+
+    /// Whether no request is waiting for or using the link
+    pub fn is_idle(&self) -> bool {
+        self.queued.load(Ordering::SeqCst) == 0
+    }
+
+    /// When the last request was sent to the ECU
+    pub fn last_used(&self) -> Instant {
+        *self.last_used.lock().unwrap()
+    }
+
+    /// Reopen the transport after a link failure
+    async fn reconnect(&self) {
+        let _ = self.transport.disconnect().await;
+        if let Err(e) = self.transport.connect().await {
+            warn!(
+                "Reconnecting {} transport for component '{}' failed: {}",
+                self.transport.name(),
+                self.component_id,
+                e
+            );
+        }
+    }
+
+    /// Send a raw request once and return the ECU's positive response
+    async fn request_once(&self, request: &UdsRequest) -> Result<UdsResponse> {
+        let timing = self.timing();
+        let _exchange = self.enter_queue().await?;
+        *self.last_used.lock().unwrap() = Instant::now();
+        if deadline::remaining().is_some_and(|remaining| remaining.is_zero()) {
+            return Err(Sovd2UdsError::Timeout(format!(
+                "Request deadline passed before service 0x{:02X} was sent",
+                request.service_id
+            )));
+        }
+
+        deadline::within("the request to be sent", self.transport.send(&request.to_bytes()))
+            .await??;
+        if request.suppress_positive_response() {
+            return Ok(UdsResponse::new_positive(
+                request.service_id.wrapping_add(0x40),
+                Vec::new(),
+            ));
+        }
+
+        // P2 for the first response, P2* after every response pending
+        let mut wait = Duration::from_millis(timing.p2 as u64);
+        let response = loop {
+            let raw = self.transport.receive(deadline::cap(wait)).await?;
+            let response = UdsResponse::from_bytes(&raw).ok_or_else(|| {
+                Sovd2UdsError::UdsCommunication(format!("Malformed UDS response: {:02X?}", raw))
+            })?;
+
+            if response.nrc == Some(RESPONSE_PENDING) && response.service_id == request.service_id {
+                debug!(
+                    "Response pending for service 0x{:02X} on component '{}'",
+                    request.service_id, self.component_id
+                );
+                wait = Duration::from_millis(timing.p2_star as u64);
+                continue;
+            }
+            // Answers to a keep-alive TesterPresent, or late answers to a
+            // request that timed out, are not meant for this request
+            if response.service_id != request.service_id
+                && response.service_id != request.service_id.wrapping_add(0x40)
+            {
+                debug!(
+                    "Ignoring response to service 0x{:02X} while waiting for 0x{:02X}",
+                    response.service_id & !0x40,
+                    request.service_id
+                );
+                continue;
+            }
+            break response;
+        };
+
+        if let Some(nrc) = response.nrc {
+            return Err(Sovd2UdsError::negative_response(response.service_id, nrc));
+        }
+
+        Ok(response)
+    }
+
+    /// Send a request and return the response data after its `echo_length`
+    /// echoed request parameters (sub-function, DID, ...), which are verified
+    async fn exchange(&self, request: UdsRequest, echo_length: usize) -> Result<Vec<u8>> {
+        let expected_echo = request.data[..echo_length.min(request.data.len())].to_vec();
+        let has_sub_function = request.has_sub_function();
+        let suppressed = request.suppress_positive_response();
+        let mut data = self.request(request).await?.data;
+        if suppressed {
+            return Ok(data);
+        }
+
+        let echo_matches = data.len() >= expected_echo.len()
+            && data
+                .iter()
+                .zip(&expected_echo)
+                .enumerate()
+                .all(|(i, (actual, expected))| {
+                    // The suppress bit is never echoed in the sub-function
+                    if i == 0 && has_sub_function {
+                        actual & 0x7F == expected & 0x7F
+                    } else {
+                        actual == expected
+                    }
+                });
+        if !echo_matches {
+            return Err(Sovd2UdsError::UdsCommunication(format!(
+                "Response {:02X?} does not echo request parameters {:02X?}",
+                data, expected_echo
+            )));
+        }
+
+        data.drain(..expected_echo.len());
+        Ok(data)
+    }
+
+    /// Current P2/P2* timing
+    pub fn timing(&self) -> UdsTiming {
+        *self.timing.read().unwrap()
+    }
+
     /// Get component ID
     pub fn component_id(&self) -> &str {
         &self.component_id
@@ -252,6 +882,8 @@ impl UdsClient {
 pub struct UdsClientPool {
     config: Arc<Config>,
     clients: Arc<RwLock<std::collections::HashMap<String, Arc<UdsClient>>>>,
+    /// DoIP connection shared by every component using the DoIP transport
+    doip: OnceLock<Arc<DoipClient>>,
 }
 
 impl UdsClientPool {
@@ -260,29 +892,158 @@ impl UdsClientPool {
         Self {
             config,
             clients: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            doip: OnceLock::new(),
         }
     }
 
     /// Get or create a UDS client for a component
+    ///
+    /// A new client connects without holding the pool, so an ECU that is slow
+    /// to connect does not hold up requests to other components.
     pub async fn get_client(&self, component_id: &str) -> Result<Arc<UdsClient>> {
-        let mut clients = self.clients.write().await;
-
-        if let Some(client) = clients.get(component_id) {
-            return Ok(Arc::clone(client));
+        if let Some(client) = self.existing_client(component_id).await {
+            return Ok(client);
         }
 
         // Create new client
+        let transport = self.create_transport(component_id)?;
         let client = Arc::new(UdsClient::new(
             Arc::clone(&self.config),
             component_id.to_string(),
+            transport,
         )?);
 
         // Connect to ECU
-        client.connect().await?;
+        deadline::within("the ECU connection", client.connect()).await??;
 
-        clients.insert(component_id.to_string(), Arc::clone(&client));
+        let added = async {
+            let mut clients = deadline::within("the connection pool", self.clients.write()).await?;
+            // Another request may have connected the component meanwhile
+            if let Some(existing) = clients.get(component_id) {
+                return Ok(Some(Arc::clone(existing)));
+            }
+            if clients.len() >= self.config.performance.connection_pool_size {
+                Self::evict_idle_client(&mut clients).await?;
+            }
+            clients.insert(component_id.to_string(), Arc::clone(&client));
+            Ok(None)
+        }
+        .await;
 
-        Ok(client)
+        match added {
+            Ok(None) => Ok(client),
+            Ok(Some(existing)) => {
+                let _ = client.disconnect().await;
+                Ok(existing)
+            }
+            Err(e) => {
+                let _ = client.disconnect().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Client already connected to a component, without connecting one
+    pub async fn existing_client(&self, component_id: &str) -> Option<Arc<UdsClient>> {
+        self.clients.read().await.get(component_id).cloned()
+    }
+
+    /// Close the least recently used client nobody is using to stay within
+    /// `performance.connection_pool_size`
+    async fn evict_idle_client(
+        clients: &mut std::collections::HashMap<String, Arc<UdsClient>>,
+    ) -> Result<()> {
+        let idle = clients
+            .iter()
+            .filter(|(_, client)| {
+                Arc::strong_count(client) == 1
+                    && client.is_idle()
+                    && client.session() == DiagnosticSessionType::DefaultSession as u8
+            })
+            .min_by_key(|(_, client)| client.last_used())
+            .map(|(id, _)| id.clone())
+            .ok_or_else(|| {
+                Sovd2UdsError::Overloaded(format!(
+                    "All {} ECU connections are in use",
+                    clients.len()
+                ))
+            })?;
+
+        if let Some(client) = clients.remove(&idle) {
+            info!("Closing idle connection to component '{}'", idle);
+            if let Err(e) = client.disconnect().await {
+                warn!("Failed to disconnect component '{}': {}", idle, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the transport configured for a component
+    fn create_transport(&self, component_id: &str) -> Result<Arc<dyn UdsTransport>> {
+        let ecu_address = self
+            .config
+            .get_component_address(component_id)
+            .ok_or_else(|| Sovd2UdsError::ComponentNotFound(component_id.to_string()))?;
+
+        let transport: Box<dyn UdsTransport> = match self.config.get_component_transport(component_id) {
+            #[cfg(not(feature = "mock-uds"))]
+            TransportKind::Ffi => Box::new(FfiTransport::new(UdsClientHandle::new(
+                &self.config.uds.interface,
+                ecu_address,
+                self.config.uds.timeout,
+            )?)),
+            #[cfg(feature = "mock-uds")]
+            TransportKind::Ffi => {
+                return Err(Sovd2UdsError::Config(
+                    "libudsclient is not linked in mock-uds builds; use the simulator transport"
+                        .to_string(),
+                ))
+            }
+            TransportKind::Doip => {
+                let doip = match self.doip.get() {
+                    Some(doip) => doip,
+                    None => {
+                        let doip = Arc::new(DoipClient::new(self.config.doip.clone())?);
+                        self.doip.get_or_init(|| doip)
+                    }
+                };
+                let logical_address = u16::try_from(ecu_address).map_err(|_| {
+                    Sovd2UdsError::Config(format!(
+                        "DoIP logical address 0x{:X} of component '{}' does not fit 16 bits",
+                        ecu_address, component_id
+                    ))
+                })?;
+                Box::new(DoipTransport::new(Arc::clone(doip), logical_address))
+            }
+            TransportKind::IsoTp => self.create_isotp_transport(ecu_address)?,
+            TransportKind::Simulator => {
+                let ecu = self.config.simulator.get_component_ecu(component_id);
+                Box::new(SimulatorTransport::new(VirtualEcu::new(ecu.clone())?))
+            }
+        };
+
+        if self.config.logging.log_requests {
+            Ok(Arc::new(RecordingTransport::new(transport, component_id)))
+        } else {
+            Ok(Arc::from(transport))
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create_isotp_transport(&self, ecu_address: u32) -> Result<Box<dyn UdsTransport>> {
+        Ok(Box::new(IsoTpTransport::new(
+            &self.config.uds.interface,
+            ecu_address,
+            ecu_address + self.config.isotp.response_id_offset,
+            self.config.isotp.clone(),
+        )))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_isotp_transport(&self, _ecu_address: u32) -> Result<Box<dyn UdsTransport>> {
+        Err(Sovd2UdsError::Config(
+            "ISO-TP over SocketCAN is only available on Linux".to_string(),
+        ))
     }
 
     /// Remove a client from the pool
@@ -306,6 +1067,343 @@ impl UdsClientPool {
             }
         }
 
+        if let Some(doip) = self.doip.get() {
+            doip.disconnect().await?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SeedKeyAlgorithmConfig, SeedKeyRegistration};
+    use crate::simulator::test_client;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+
+    /// Failure injected in front of the simulator
+    #[derive(Debug, Clone, Copy)]
+    enum Fault {
+        Busy,
+        Timeout,
+        LinkDown,
+        /// The request is never sent
+        Stall,
+    }
+
+    /// Simulator transport failing requests as scripted
+    struct FaultyTransport {
+        simulator: SimulatorTransport,
+        faults: std::sync::Mutex<VecDeque<Fault>>,
+        /// Fault of the last request and its service ID, reported by `receive`
+        pending: std::sync::Mutex<Option<(Fault, u8)>>,
+        sent: AtomicUsize,
+        connects: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl UdsTransport for FaultyTransport {
+        async fn connect(&self) -> Result<()> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            self.simulator.connect().await
+        }
+
+        async fn disconnect(&self) -> Result<()> {
+            self.simulator.disconnect().await
+        }
+
+        async fn send(&self, request: &[u8]) -> Result<()> {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let fault = self.faults.lock().unwrap().pop_front();
+            match fault {
+                Some(Fault::LinkDown) => Err(Sovd2UdsError::Io(
+                    std::io::ErrorKind::ConnectionReset.into(),
+                )),
+                Some(Fault::Stall) => std::future::pending().await,
+                Some(fault) => {
+                    *self.pending.lock().unwrap() = Some((fault, request[0]));
+                    Ok(())
+                }
+                None => self.simulator.send(request).await,
+            }
+        }
+
+        async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+            let pending = self.pending.lock().unwrap().take();
+            match pending {
+                Some((Fault::Busy, service)) => Ok(vec![0x7F, service, 0x21]),
+                Some(_) => Err(Sovd2UdsError::Timeout("No response".to_string())),
+                None => self.simulator.receive(timeout).await,
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            "faulty"
+        }
+    }
+
+    /// Client of "engine" whose simulated ECU fails as `faults` lists, with
+    /// short retry delays
+    fn faulty_client(
+        configure: impl FnOnce(&mut Config),
+        faults: &[Fault],
+    ) -> (UdsClient, Arc<FaultyTransport>) {
+        let mut config = Config::default();
+        config.uds.max_retries = 3;
+        config.uds.retry_delay = 10;
+        config.uds.retry_max_delay = 20;
+        configure(&mut config);
+        let ecu = VirtualEcu::new(config.simulator.ecu.clone()).unwrap();
+        let transport = Arc::new(FaultyTransport {
+            simulator: SimulatorTransport::new(ecu),
+            faults: std::sync::Mutex::new(faults.iter().copied().collect()),
+            pending: std::sync::Mutex::new(None),
+            sent: AtomicUsize::new(0),
+            connects: AtomicUsize::new(0),
+        });
+        let client = UdsClient::new(
+            Arc::new(config),
+            "engine".to_string(),
+            Arc::clone(&transport) as Arc<dyn UdsTransport>,
+        )
+        .unwrap();
+        (client, transport)
+    }
+
+    fn busy(error: Result<impl std::fmt::Debug>) -> bool {
+        matches!(error, Err(Sovd2UdsError::UdsProtocol { nrc: 0x21, .. }))
+    }
+
+    #[tokio::test]
+    async fn transport_is_chosen_per_component() {
+        let mut config = Config::default();
+        config.uds.transport = Some(TransportKind::Ffi);
+        config.components.get_mut("engine").unwrap().transport = Some(TransportKind::Simulator);
+        config.logging.log_requests = true;
+        let pool = UdsClientPool::new(Arc::new(config));
+
+        let engine = pool.get_client("engine").await.unwrap();
+        assert_eq!(engine.get_vin().await.unwrap(), "WVWZZZ1JZXW000001");
+        // libudsclient is not linked into mock-uds builds
+        #[cfg(feature = "mock-uds")]
+        assert!(matches!(
+            pool.get_client("transmission").await,
+            Err(Sovd2UdsError::Config(_))
+        ));
+        assert!(matches!(
+            pool.get_client("gearbox").await,
+            Err(Sovd2UdsError::ComponentNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn transient_read_failures_are_retried() {
+        let faults = [Fault::Busy, Fault::Timeout, Fault::LinkDown];
+        let (client, transport) = faulty_client(|_| {}, &faults);
+
+        let vin = client.read_data_by_identifier(0xF190).await.unwrap();
+        assert_eq!(vin, b"WVWZZZ1JZXW000001");
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 4);
+        // Reconnected after the link failure only
+        assert_eq!(transport.connects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_max_retries() {
+        let (client, transport) =
+            faulty_client(|config| config.uds.max_retries = 2, &[Fault::Busy; 4]);
+        assert!(busy(client.read_data_by_identifier(0xF190).await));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn writes_and_resets_are_not_retried() {
+        let (client, transport) = faulty_client(|_| {}, &[Fault::Busy; 2]);
+        assert!(busy(client.write_data_by_identifier(0xF190, b"WVWZZZ1JZXW000002").await));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 1);
+        assert!(busy(client.ecu_reset(EcuResetType::SoftReset).await));
+        assert_eq!(transport.sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_delay_doubles_up_to_the_maximum() {
+        let (client, _) = faulty_client(
+            |config| {
+                config.uds.retry_delay = 40;
+                config.uds.retry_max_delay = 60;
+            },
+            &[Fault::Busy; 3],
+        );
+        let started = Instant::now();
+        client.read_data_by_identifier(0xF190).await.unwrap();
+        // 40 + 60 + 60 ms
+        assert!(started.elapsed() >= Duration::from_millis(160));
+    }
+
+    #[tokio::test]
+    async fn session_response_replaces_the_configured_timing() {
+        let (client, _) = faulty_client(
+            |config| {
+                let timing = crate::config::ComponentTimingConfig {
+                    p2: Some(150),
+                    p2_star: Some(900),
+                };
+                config.uds.timing.insert("engine".to_string(), timing);
+                config.uds.p2_margin = 10;
+                config.simulator.ecu.p2 = 40;
+                config.simulator.ecu.p2_star = 2000;
+            },
+            &[],
+        );
+        assert_eq!(client.timing(), UdsTiming { p2: 150, p2_star: 900 });
+
+        client
+            .diagnostic_session_control(DiagnosticSessionType::ExtendedDiagnosticSession)
+            .await
+            .unwrap();
+        assert_eq!(client.timing(), UdsTiming { p2: 50, p2_star: 2010 });
+    }
+
+    #[tokio::test]
+    async fn response_pending_waits_for_p2_star() {
+        let (client, _) = faulty_client(
+            |config| {
+                config.uds.p2_margin = 0;
+                config.simulator.ecu.p2 = 20;
+                config.simulator.ecu.p2_star = 100;
+                config.simulator.ecu.routines[0].duration = 300;
+            },
+            &[],
+        );
+        client
+            .diagnostic_session_control(DiagnosticSessionType::ExtendedDiagnosticSession)
+            .await
+            .unwrap();
+
+        // The routine takes far longer than P2; the ECU keeps sending 0x78
+        let started = Instant::now();
+        let status = client
+            .routine_control(RoutineControlType::StartRoutine, 0x0201, &[])
+            .await
+            .unwrap();
+        assert_eq!(status, [0x00]);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn tester_present_holds_the_session_past_s3() {
+        let (client, transport) = faulty_client(
+            |config| {
+                config.uds.tester_present_interval = 50;
+                config.simulator.ecu.s3_timeout = 200;
+            },
+            &[],
+        );
+        let extended = DiagnosticSessionType::ExtendedDiagnosticSession;
+        client.diagnostic_session_control(extended).await.unwrap();
+        assert!(client.keep_alive_active());
+
+        sleep(Duration::from_millis(500)).await;
+        assert_eq!(transport.simulator.ecu().session(), extended as u8);
+        assert!(transport.sent.load(Ordering::SeqCst) > 3);
+
+        // Back in the default session there is nothing to keep alive
+        let default = DiagnosticSessionType::DefaultSession;
+        client.diagnostic_session_control(default).await.unwrap();
+        assert!(!client.keep_alive_active());
+        let sent = transport.sent.load(Ordering::SeqCst);
+        sleep(Duration::from_millis(150)).await;
+        assert_eq!(transport.sent.load(Ordering::SeqCst), sent);
+
+        client.diagnostic_session_control(extended).await.unwrap();
+        client.disconnect().await.unwrap();
+        assert!(!client.keep_alive_active());
+    }
+
+    #[tokio::test]
+    async fn session_falls_back_after_s3_without_keep_alive() {
+        let (client, transport) = faulty_client(
+            |config| {
+                config.uds.tester_present_interval = 0;
+                config.simulator.ecu.s3_timeout = 100;
+            },
+            &[],
+        );
+        let extended = DiagnosticSessionType::ExtendedDiagnosticSession;
+        client.diagnostic_session_control(extended).await.unwrap();
+        assert!(!client.keep_alive_active());
+
+        sleep(Duration::from_millis(250)).await;
+        assert_eq!(transport.simulator.ecu().session(), 0x01);
+    }
+
+    #[tokio::test]
+    async fn full_queue_is_overloaded() {
+        let mut config = Config::default();
+        config.performance.ecu_queue_length = 2;
+        config.simulator.ecu.response_delay = 200;
+        let client = test_client(config, "engine").await;
+
+        let queued: Vec<_> = (0..2)
+            .map(|_| {
+                let client = Arc::clone(&client);
+                tokio::spawn(async move { client.read_data_by_identifier(0xF190).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(matches!(
+            client.read_data_by_identifier(0xF190).await,
+            Err(Sovd2UdsError::Overloaded(_))
+        ));
+
+        for request in queued {
+            request.await.unwrap().unwrap();
+        }
+        assert!(client.is_idle());
+        client.read_data_by_identifier(0xF190).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stalled_ecu_fails_at_the_deadline() {
+        let (client, _) = faulty_client(|_| {}, &[Fault::Stall]);
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(100);
+        let read = deadline::with_deadline(deadline, client.read_data_by_identifier(0xF190));
+        assert!(matches!(read.await, Err(Sovd2UdsError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // The link is free for the next request
+        assert!(client.is_idle());
+        client.read_data_by_identifier(0xF190).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stalled_seed_key_hook_fails_at_the_deadline() {
+        let (client, _) = faulty_client(
+            |config| {
+                config.security.algorithms.push(SeedKeyRegistration {
+                    component: None,
+                    level: None,
+                    algorithm: SeedKeyAlgorithmConfig::Command {
+                        command: ["sh", "-c", "sleep 1", "hook"].map(String::from).to_vec(),
+                        timeout: 10000,
+                    },
+                });
+            },
+            &[],
+        );
+        client
+            .diagnostic_session_control(DiagnosticSessionType::ExtendedDiagnosticSession)
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+        let unlock = deadline::with_deadline(deadline, client.unlock_security(1));
+        assert!(matches!(unlock.await, Err(Sovd2UdsError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_millis(800));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::error::{Result, Sovd2UdsError};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static DEADLINE: Option<Instant>;
+}
+
+/// Run `future` with a deadline that UDS requests made inside it respect
+pub async fn with_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(Some(deadline), future).await
+}
+
+/// Run `future` without the current deadline, for cleanup that has to reach
+/// the ECU even after the deadline passed
+pub async fn without_deadline<F: Future>(future: F) -> F::Output {
+    DEADLINE.scope(None, future).await
+}
+
+fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok().flatten()
+}
+
+/// Time left before the current deadline; `None` outside `with_deadline`
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Shorten `wait` so it does not run past the current deadline
+pub fn cap(wait: Duration) -> Duration {
+    remaining().map_or(wait, |remaining| wait.min(remaining))
+}
+
+/// Wait for `future` until the current deadline; fails with `Timeout` once
+/// it passes
+///
+/// `future` is dropped at the deadline, so it has to be cancel-safe: a lock,
+/// a transport send or a spawned task.
+pub async fn within<F: Future>(what: &str, future: F) -> Result<F::Output> {
+    match current() {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.map_err(|_| {
+            Sovd2UdsError::Timeout(format!("Request deadline passed while waiting for {}", what))
+        }),
+        None => Ok(future.await),
+    }
+}
@@ -1,3 +1,8 @@
 pub mod client;
+pub mod deadline;
+pub mod session;
+pub mod transport;
 
 pub use client::{UdsClient, UdsClientPool};
+pub use session::SessionState;
+pub use transport::{RecordingTransport, UdsTransport};
@@ -0,0 +1,135 @@
+use crate::error::UdsNegativeResponseCode;
+use crate::models::uds::{DiagnosticSessionType, UdsRequest, UdsResponse, UdsServiceId};
+use chrono::{DateTime, Duration, Utc};
+
+/// Diagnostic session and security access state of one ECU
+///
+/// Follows the positive responses the ECU sends: session control changes the
+/// session and locks security access again, ECU reset returns to the default
+/// session, and a sendKey (or a requestSeed answered with an all-zero seed)
+/// unlocks its security level. Rejected keys are counted so clients can see
+/// how many attempts are left before the ECU locks them out.
+#[derive(Debug, Clone)]
+pub struct SessionState {
+    /// Active diagnostic session (0x01 default, 0x02 programming, ...)
+    pub session: u8,
+    /// Unlocked security level (requestSeed sub-function = level * 2 - 1)
+    pub security_level: Option<u8>,
+    /// When the active session was entered
+    pub session_since: DateTime<Utc>,
+    /// When the security level was unlocked
+    pub security_since: Option<DateTime<Utc>>,
+    /// When the last positive response was received
+    pub last_activity: DateTime<Utc>,
+    /// Invalid keys sent since the last unlock or lockout
+    pub failed_attempts: u8,
+    /// When the ECU is expected to accept requestSeed again after a lockout
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            session: DiagnosticSessionType::DefaultSession as u8,
+            security_level: None,
+            session_since: now,
+            security_since: None,
+            last_activity: now,
+            failed_attempts: 0,
+            locked_until: None,
+        }
+    }
+
+    pub fn is_default_session(&self) -> bool {
+        self.session == DiagnosticSessionType::DefaultSession as u8
+    }
+
+    /// Apply a positive response; returns whether the session changed
+    pub fn apply(&mut self, request: &UdsRequest, response: &UdsResponse) -> bool {
+        let now = Utc::now();
+        self.last_activity = now;
+
+        let sub_function = request.data.first().map(|b| b & 0x7F);
+        let session = match (request.service_id, sub_function) {
+            (sid, Some(session)) if sid == UdsServiceId::DiagnosticSessionControl as u8 => session,
+            (sid, _) if sid == UdsServiceId::EcuReset as u8 => {
+                DiagnosticSessionType::DefaultSession as u8
+            }
+            (sid, Some(access_type)) if sid == UdsServiceId::SecurityAccess as u8 => {
+                let seed = response.data.get(1..).unwrap_or_default();
+                let unlocked = if access_type.is_multiple_of(2) {
+                    true
+                } else {
+                    !seed.is_empty() && seed.iter().all(|&b| b == 0)
+                };
+                if unlocked && access_type > 0 {
+                    self.security_level = Some(access_type.div_ceil(2));
+                    self.security_since = Some(now);
+                    self.failed_attempts = 0;
+                    self.locked_until = None;
+                }
+                return false;
+            }
+            _ => return false,
+        };
+
+        let changed = session != self.session;
+        self.session = session;
+        self.session_since = now;
+        self.security_level = None;
+        self.security_since = None;
+        changed
+    }
+
+    /// Count a negative response to security access
+    ///
+    /// `max_attempts` and `delay` are the ECU's limits: after that many
+    /// invalid keys (or NRC 0x36) it refuses requestSeed for `delay`. NRC
+    /// 0x37 without a known lockout (e.g. the delay after power-on) assumes
+    /// the full delay from now.
+    pub fn record_security_failure(&mut self, nrc: u8, max_attempts: u8, delay: Duration) {
+        let now = Utc::now();
+        match UdsNegativeResponseCode::from_u8(nrc) {
+            Some(UdsNegativeResponseCode::InvalidKey) => {
+                self.failed_attempts = self.failed_attempts.saturating_add(1);
+                if self.failed_attempts >= max_attempts {
+                    self.failed_attempts = 0;
+                    self.locked_until = Some(now + delay);
+                }
+            }
+            Some(UdsNegativeResponseCode::ExceedNumberOfAttempts) => {
+                self.failed_attempts = 0;
+                self.locked_until = Some(now + delay);
+            }
+            Some(UdsNegativeResponseCode::RequiredTimeDelayNotExpired)
+                if self.retry_after().is_none() =>
+            {
+                self.locked_until = Some(now + delay);
+            }
+            _ => {}
+        }
+    }
+
+    /// Time left until the ECU accepts requestSeed again, if it is locked out
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.locked_until
+            .map(|until| until - Utc::now())
+            .filter(|left| *left > Duration::zero())
+    }
+
+    /// Keys that may still be tried before the ECU locks security access out
+    pub fn remaining_attempts(&self, max_attempts: u8) -> u8 {
+        if self.retry_after().is_some() {
+            0
+        } else {
+            max_attempts.saturating_sub(self.failed_attempts)
+        }
+    }
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+use tracing::info;
+
+/// Link carrying raw UDS PDUs between the tester and one ECU
+///
+/// Implemented by libudsclient (FFI), the native DoIP and ISO-TP transports
+/// and test doubles; `UdsClient` only ever talks to this trait.
+#[async_trait]
+pub trait UdsTransport: Send + Sync {
+    /// Open the link to the ECU
+    async fn connect(&self) -> Result<()>;
+
+    /// Close the link to the ECU
+    async fn disconnect(&self) -> Result<()>;
+
+    /// Send a raw UDS request (SID + parameters)
+    ///
+    /// Dropped at the request deadline; a send cancelled part way may lose
+    /// its request but must leave the link usable for the next one.
+    async fn send(&self, request: &[u8]) -> Result<()>;
+
+    /// Wait up to `timeout` for the next raw UDS response from the ECU
+    async fn receive(&self, timeout: Duration) -> Result<Vec<u8>>;
+
+    /// Short transport name used in logs
+    fn name(&self) -> &'static str;
+}
+
+/// Transport decorator logging every PDU exchanged with the ECU
+pub struct RecordingTransport {
+    inner: Box<dyn UdsTransport>,
+    component_id: String,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Box<dyn UdsTransport>, component_id: &str) -> Self {
+        Self {
+            inner,
+            component_id: component_id.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl UdsTransport for RecordingTransport {
+    async fn connect(&self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    async fn send(&self, request: &[u8]) -> Result<()> {
+        info!(
+            "[{}] {} -> {}",
+            self.component_id,
+            self.inner.name(),
+            format_pdu(request)
+        );
+        self.inner.send(request).await
+    }
+
+    async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+        let response = self.inner.receive(timeout).await;
+        match &response {
+            Ok(bytes) => info!(
+                "[{}] {} <- {}",
+                self.component_id,
+                self.inner.name(),
+                format_pdu(bytes)
+            ),
+            Err(e) => info!(
+                "[{}] {} <- error: {}",
+                self.component_id,
+                self.inner.name(),
+                e
+            ),
+        }
+        response
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+}
+
+fn format_pdu(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SimulatedEcuConfig;
+    use crate::simulator::{SimulatorTransport, VirtualEcu};
+
+    #[tokio::test]
+    async fn recording_transport_forwards_pdus() {
+        let ecu = VirtualEcu::new(SimulatedEcuConfig::default()).unwrap();
+        let transport = RecordingTransport::new(Box::new(SimulatorTransport::new(ecu)), "engine");
+        assert_eq!(transport.name(), "simulator");
+
+        transport.connect().await.unwrap();
+        transport.send(&[0x22, 0xF1, 0x90]).await.unwrap();
+        let response = transport.receive(Duration::from_millis(100)).await.unwrap();
+        assert_eq!(&response[..3], &[0x62, 0xF1, 0x90]);
+
+        // Nothing was requested, so nothing arrives
+        assert!(transport.receive(Duration::from_millis(10)).await.is_err());
+        transport.disconnect().await.unwrap();
+    }
+
+    #[test]
+    fn pdus_are_logged_as_hex_bytes() {
+        assert_eq!(format_pdu(&[0x7F, 0x22, 0x0A]), "7F 22 0A");
+        assert_eq!(format_pdu(&[]), "");
+    }
+}